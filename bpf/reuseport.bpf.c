@@ -0,0 +1,139 @@
+// Reference SO_REUSEPORT socket selector. Picks the socket in the slot
+// named by a trait value, e.g. a tenant key set by a tc program at ingress.
+// Packets without the trait, or naming an empty slot, go where the kernel
+// would send them by flow hash. Sockets are managed from userspace with
+// skb_traits::ReuseportGroup.
+//
+// sk_reuseport programs can't call the trait kfuncs, which take an skb.
+// record_shard_trait runs at tc ingress after whatever sets the trait, and
+// notes its value by flow for reuseport_by_trait to find. The flow is keyed
+// by its headers, as the hash sk_reuseport programs see is the socket
+// lookup's own, not the skb's.
+#include <linux/bpf.h>
+#include <linux/if_ether.h>
+#include <linux/in.h>
+#include <linux/ip.h>
+#include <linux/ipv6.h>
+#include <linux/pkt_cls.h>
+#include <linux/types.h>
+#include <bpf/bpf_endian.h>
+#include <bpf/bpf_helpers.h>
+
+/* u32 trait holding the slot index. Edit to shard on another trait. */
+#define SHARD_TRAIT_KEY 1
+#define MAX_SHARDS 64
+#define MAX_FLOWS 65536
+
+int bpf_skb_trait_get(const struct __sk_buff *skb, __u64 key, void *val,
+		      __u64 val__sz) __ksym __weak;
+
+/* IPv4 addresses are stored IPv4-mapped */
+struct shard_flow {
+	__u32 saddr[4];
+	__u32 daddr[4];
+	__be16 sport;
+	__be16 dport;
+	__u8 proto;
+	__u8 _pad[3];
+};
+
+struct {
+	__uint(type, BPF_MAP_TYPE_REUSEPORT_SOCKARRAY);
+	__uint(max_entries, MAX_SHARDS);
+	__type(key, __u32);
+	__type(value, __u64);
+} reuseport_socks SEC(".maps");
+
+/* Slot by flow, left to age out */
+struct {
+	__uint(type, BPF_MAP_TYPE_LRU_HASH);
+	__uint(max_entries, MAX_FLOWS);
+	__type(key, struct shard_flow);
+	__type(value, __u32);
+} reuseport_flows SEC(".maps");
+
+static __always_inline void map_ip4(__u32 *dst, __be32 addr)
+{
+	dst[2] = bpf_htonl(0xffff);
+	dst[3] = addr;
+}
+
+/* Works on both contexts, bpf_skb_load_bytes_relative takes either */
+static __always_inline int parse_flow(const void *ctx, __be16 eth_proto,
+				      struct shard_flow *flow)
+{
+	__be16 ports[2];
+	__u32 l4_off;
+
+	if (eth_proto == bpf_htons(ETH_P_IP)) {
+		struct iphdr ip;
+
+		if (bpf_skb_load_bytes_relative(ctx, 0, &ip, sizeof(ip),
+						BPF_HDR_START_NET))
+			return -1;
+
+		map_ip4(flow->saddr, ip.saddr);
+		map_ip4(flow->daddr, ip.daddr);
+		flow->proto = ip.protocol;
+		l4_off = ip.ihl * 4;
+	} else if (eth_proto == bpf_htons(ETH_P_IPV6)) {
+		struct ipv6hdr ip6;
+
+		if (bpf_skb_load_bytes_relative(ctx, 0, &ip6, sizeof(ip6),
+						BPF_HDR_START_NET))
+			return -1;
+
+		__builtin_memcpy(flow->saddr, &ip6.saddr, 16);
+		__builtin_memcpy(flow->daddr, &ip6.daddr, 16);
+		/* Extension headers are not walked */
+		flow->proto = ip6.nexthdr;
+		l4_off = sizeof(ip6);
+	} else {
+		return -1;
+	}
+
+	if (flow->proto != IPPROTO_TCP && flow->proto != IPPROTO_UDP)
+		return -1;
+	if (bpf_skb_load_bytes_relative(ctx, l4_off, ports, sizeof(ports),
+					BPF_HDR_START_NET))
+		return -1;
+
+	flow->sport = ports[0];
+	flow->dport = ports[1];
+	return 0;
+}
+
+SEC("tc")
+int record_shard_trait(struct __sk_buff *skb)
+{
+	struct shard_flow flow = {};
+	__u32 slot = 0;
+
+	if (parse_flow(skb, skb->protocol, &flow))
+		return TC_ACT_OK;
+	if (bpf_skb_trait_get(skb, SHARD_TRAIT_KEY, &slot, sizeof(slot)) < 0)
+		return TC_ACT_OK;
+
+	bpf_map_update_elem(&reuseport_flows, &flow, &slot, BPF_ANY);
+	return TC_ACT_OK;
+}
+
+SEC("sk_reuseport")
+int reuseport_by_trait(struct sk_reuseport_md *ctx)
+{
+	struct shard_flow flow = {};
+	__u32 *slot;
+
+	if (parse_flow(ctx, ctx->eth_protocol, &flow))
+		return SK_PASS;
+
+	slot = bpf_map_lookup_elem(&reuseport_flows, &flow);
+	if (!slot)
+		return SK_PASS;
+
+	/* Falls back to the kernel's pick if the slot is out of range or empty */
+	bpf_sk_select_reuseport(ctx, &reuseport_socks, slot, 0);
+	return SK_PASS;
+}
+
+const char _license[] SEC("license") = "GPL";
@@ -0,0 +1,174 @@
+// Reference sk_lookup dispatcher. Sends connections and datagrams to the
+// socket routed for a trait value, e.g. a tenant key set by a tc program at
+// ingress, whatever address and port they are for. Lookups without a route
+// proceed as usual. Routes and target sockets are managed from userspace
+// with skb_traits::TraitDispatch.
+//
+// sk_lookup programs see no skb, so can't read traits. record_dispatch_trait
+// runs at tc ingress after whatever sets the trait, and notes its value by
+// flow for dispatch_by_trait to find.
+#include <linux/bpf.h>
+#include <linux/if_ether.h>
+#include <linux/in.h>
+#include <linux/ip.h>
+#include <linux/ipv6.h>
+#include <linux/pkt_cls.h>
+#include <linux/types.h>
+#include <bpf/bpf_endian.h>
+#include <bpf/bpf_helpers.h>
+
+/* u32 trait holding the route. Edit to dispatch on another trait. */
+#define DISPATCH_TRAIT_KEY 1
+#define MAX_TARGETS 64
+#define MAX_ROUTES 1024
+#define MAX_FLOWS 65536
+
+#define AF_INET 2
+
+int bpf_skb_trait_get(const struct __sk_buff *skb, __u64 key, void *val,
+		      __u64 val__sz) __ksym __weak;
+
+/* As seen by the receiver. IPv4 addresses are stored IPv4-mapped. */
+struct dispatch_flow {
+	__u32 remote_ip[4];
+	__u32 local_ip[4];
+	__be16 remote_port;
+	__be16 local_port;
+	__u8 proto;
+	__u8 _pad[3];
+};
+
+struct {
+	__uint(type, BPF_MAP_TYPE_SOCKMAP);
+	__uint(max_entries, MAX_TARGETS);
+	__type(key, __u32);
+	__type(value, __u64);
+} dispatch_socks SEC(".maps");
+
+/* Trait value to dispatch_socks slot */
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, MAX_ROUTES);
+	__type(key, __u32);
+	__type(value, __u32);
+} dispatch_routes SEC(".maps");
+
+/* Trait value by flow, left to age out */
+struct {
+	__uint(type, BPF_MAP_TYPE_LRU_HASH);
+	__uint(max_entries, MAX_FLOWS);
+	__type(key, struct dispatch_flow);
+	__type(value, __u32);
+} dispatch_flows SEC(".maps");
+
+static __always_inline void map_ip4(__u32 *dst, __be32 addr)
+{
+	dst[2] = bpf_htonl(0xffff);
+	dst[3] = addr;
+}
+
+/* sk_lookup context fields only take 4-byte loads */
+static __always_inline void copy_ip6(__u32 *dst, const __u32 *src)
+{
+	dst[0] = src[0];
+	dst[1] = src[1];
+	dst[2] = src[2];
+	dst[3] = src[3];
+}
+
+static __always_inline int parse_flow(struct __sk_buff *skb,
+				      struct dispatch_flow *flow)
+{
+	__be16 ports[2];
+	__u32 l4_off;
+
+	if (skb->protocol == bpf_htons(ETH_P_IP)) {
+		struct iphdr ip;
+
+		if (bpf_skb_load_bytes_relative(skb, 0, &ip, sizeof(ip),
+						BPF_HDR_START_NET))
+			return -1;
+
+		map_ip4(flow->remote_ip, ip.saddr);
+		map_ip4(flow->local_ip, ip.daddr);
+		flow->proto = ip.protocol;
+		l4_off = ip.ihl * 4;
+	} else if (skb->protocol == bpf_htons(ETH_P_IPV6)) {
+		struct ipv6hdr ip6;
+
+		if (bpf_skb_load_bytes_relative(skb, 0, &ip6, sizeof(ip6),
+						BPF_HDR_START_NET))
+			return -1;
+
+		__builtin_memcpy(flow->remote_ip, &ip6.saddr, 16);
+		__builtin_memcpy(flow->local_ip, &ip6.daddr, 16);
+		/* Extension headers are not walked */
+		flow->proto = ip6.nexthdr;
+		l4_off = sizeof(ip6);
+	} else {
+		return -1;
+	}
+
+	if (flow->proto != IPPROTO_TCP && flow->proto != IPPROTO_UDP)
+		return -1;
+	if (bpf_skb_load_bytes_relative(skb, l4_off, ports, sizeof(ports),
+					BPF_HDR_START_NET))
+		return -1;
+
+	flow->remote_port = ports[0];
+	flow->local_port = ports[1];
+	return 0;
+}
+
+SEC("tc")
+int record_dispatch_trait(struct __sk_buff *skb)
+{
+	struct dispatch_flow flow = {};
+	__u32 val = 0;
+
+	if (parse_flow(skb, &flow))
+		return TC_ACT_OK;
+	if (bpf_skb_trait_get(skb, DISPATCH_TRAIT_KEY, &val, sizeof(val)) < 0)
+		return TC_ACT_OK;
+
+	bpf_map_update_elem(&dispatch_flows, &flow, &val, BPF_ANY);
+	return TC_ACT_OK;
+}
+
+SEC("sk_lookup")
+int dispatch_by_trait(struct bpf_sk_lookup *ctx)
+{
+	struct dispatch_flow flow = {};
+	struct bpf_sock *sk;
+	__u32 *val, *slot;
+
+	if (ctx->family == AF_INET) {
+		map_ip4(flow.remote_ip, ctx->remote_ip4);
+		map_ip4(flow.local_ip, ctx->local_ip4);
+	} else {
+		copy_ip6(flow.remote_ip, ctx->remote_ip6);
+		copy_ip6(flow.local_ip, ctx->local_ip6);
+	}
+	flow.remote_port = ctx->remote_port;
+	flow.local_port = bpf_htons(ctx->local_port);
+	flow.proto = ctx->protocol;
+
+	val = bpf_map_lookup_elem(&dispatch_flows, &flow);
+	if (!val)
+		return SK_PASS;
+
+	slot = bpf_map_lookup_elem(&dispatch_routes, val);
+	if (!slot)
+		return SK_PASS;
+
+	sk = bpf_map_lookup_elem(&dispatch_socks, slot);
+	if (!sk)
+		return SK_PASS;
+
+	/* Fails for a socket of the wrong protocol, lookup goes on then */
+	bpf_sk_assign(ctx, sk, 0);
+	bpf_sk_release(sk);
+	return SK_PASS;
+}
+
+const char _license[] SEC("license") = "GPL";
@@ -0,0 +1,175 @@
+// Generic trait tagger. Looks up the traits to set on a packet first by
+// exact 5-tuple, then by longest source prefix. Rules are managed from
+// userspace with skb_traits::Tagger.
+#include <linux/bpf.h>
+#include <linux/if_ether.h>
+#include <linux/in.h>
+#include <linux/ip.h>
+#include <linux/ipv6.h>
+#include <linux/pkt_cls.h>
+#include <linux/types.h>
+#include <bpf/bpf_endian.h>
+#include <bpf/bpf_helpers.h>
+
+#define MAX_RULE_TRAITS 8
+#define MAX_RULES 1024
+
+int bpf_skb_trait_set(const struct __sk_buff *skb, __u64 key,
+		      const void *val, __u64 val__sz,
+		      __u64 flags) __ksym __weak;
+
+/* IPv4 addresses are stored IPv4-mapped, ::ffff:a.b.c.d */
+struct flow_key {
+	__u8 saddr[16];
+	__u8 daddr[16];
+	__be16 sport;
+	__be16 dport;
+	__u8 proto;
+	__u8 _pad[3];
+};
+
+struct prefix_key {
+	__u32 prefixlen;
+	__u8 saddr[16];
+};
+
+/* Same layout as struct pkt_trait passed to TCP_SYN_TRAITS */
+struct pkt_trait {
+	__u8 _zpad_1;
+	__u8 key;
+	__u8 len;
+	__u8 io_err;
+	__u32 _zpad_2;
+	__u64 val[2];
+};
+
+struct tag_rule {
+	__u32 cnt;
+	__u32 _pad;
+	struct pkt_trait traits[MAX_RULE_TRAITS];
+};
+
+struct {
+	__uint(type, BPF_MAP_TYPE_HASH);
+	__uint(max_entries, MAX_RULES);
+	__type(key, struct flow_key);
+	__type(value, struct tag_rule);
+} tagger_flows SEC(".maps");
+
+struct {
+	__uint(type, BPF_MAP_TYPE_LPM_TRIE);
+	__uint(max_entries, MAX_RULES);
+	__uint(map_flags, BPF_F_NO_PREALLOC);
+	__type(key, struct prefix_key);
+	__type(value, struct tag_rule);
+} tagger_prefixes SEC(".maps");
+
+static __always_inline int parse_flow(struct __sk_buff *skb,
+				      struct flow_key *flow)
+{
+	__u32 l4_off;
+
+	if (skb->protocol == bpf_htons(ETH_P_IP)) {
+		struct iphdr ip;
+
+		if (bpf_skb_load_bytes_relative(skb, 0, &ip, sizeof(ip),
+						BPF_HDR_START_NET))
+			return -1;
+
+		flow->saddr[10] = 0xff;
+		flow->saddr[11] = 0xff;
+		__builtin_memcpy(&flow->saddr[12], &ip.saddr, 4);
+		flow->daddr[10] = 0xff;
+		flow->daddr[11] = 0xff;
+		__builtin_memcpy(&flow->daddr[12], &ip.daddr, 4);
+		flow->proto = ip.protocol;
+		l4_off = ip.ihl * 4;
+	} else if (skb->protocol == bpf_htons(ETH_P_IPV6)) {
+		struct ipv6hdr ip6;
+
+		if (bpf_skb_load_bytes_relative(skb, 0, &ip6, sizeof(ip6),
+						BPF_HDR_START_NET))
+			return -1;
+
+		__builtin_memcpy(flow->saddr, &ip6.saddr, 16);
+		__builtin_memcpy(flow->daddr, &ip6.daddr, 16);
+		/* Extension headers are not walked */
+		flow->proto = ip6.nexthdr;
+		l4_off = sizeof(ip6);
+	} else {
+		return -1;
+	}
+
+	if (flow->proto == IPPROTO_TCP || flow->proto == IPPROTO_UDP) {
+		__be16 ports[2];
+
+		if (bpf_skb_load_bytes_relative(skb, l4_off, ports,
+						sizeof(ports),
+						BPF_HDR_START_NET))
+			return -1;
+
+		flow->sport = ports[0];
+		flow->dport = ports[1];
+	}
+
+	return 0;
+}
+
+static __always_inline void tag(struct __sk_buff *skb)
+{
+	struct flow_key flow = {};
+	struct prefix_key prefix = {};
+	struct tag_rule *rule;
+	__u32 i;
+
+	if (parse_flow(skb, &flow))
+		return;
+
+	rule = bpf_map_lookup_elem(&tagger_flows, &flow);
+	if (!rule) {
+		prefix.prefixlen = 128;
+		__builtin_memcpy(prefix.saddr, flow.saddr, 16);
+		rule = bpf_map_lookup_elem(&tagger_prefixes, &prefix);
+	}
+	if (!rule)
+		return;
+
+	/* Traits which fail to set, e.g. for lack of room, are skipped */
+	for (i = 0; i < MAX_RULE_TRAITS; i++) {
+		struct pkt_trait *t = &rule->traits[i];
+
+		if (i >= rule->cnt)
+			break;
+
+		switch (t->len) {
+		case 2:
+			bpf_skb_trait_set(skb, t->key, &(__u16){ t->val[0] },
+					  sizeof(__u16), 0);
+			break;
+		case 4:
+			bpf_skb_trait_set(skb, t->key, &(__u32){ t->val[0] },
+					  sizeof(__u32), 0);
+			break;
+		case 8:
+			bpf_skb_trait_set(skb, t->key, &t->val[0],
+					  sizeof(__u64), 0);
+			break;
+		}
+	}
+}
+
+SEC("socket")
+int tagger_sk(struct __sk_buff *skb)
+{
+	tag(skb);
+	return skb->len;
+}
+
+SEC("tc")
+int tagger_tc(struct __sk_buff *skb)
+{
+	tag(skb);
+	return TC_ACT_OK;
+}
+
+const char _license[] SEC("license") = "GPL";
@@ -0,0 +1,87 @@
+// Reference XDP and tc program pair passing traits through the metadata
+// area. xdp_classify records the ingress interface and RX queue as traits,
+// tc_meta_to_traits sets them on the skb. skb_traits::MetaBridge loads an
+// equivalent of the latter without clang.
+#include <linux/bpf.h>
+#include <linux/pkt_cls.h>
+#include <linux/types.h>
+#include <bpf/bpf_helpers.h>
+
+#include "trait_meta.h"
+
+#define TRAIT_KEY_IFINDEX 1
+#define TRAIT_KEY_RX_QUEUE 2
+
+int bpf_skb_trait_set(const struct __sk_buff *skb, __u64 key,
+		      const void *val, __u64 val__sz,
+		      __u64 flags) __ksym __weak;
+
+SEC("xdp")
+int xdp_classify(struct xdp_md *ctx)
+{
+	__u32 ifindex = ctx->ingress_ifindex;
+	__u32 rx_queue = ctx->rx_queue_index;
+	struct trait_meta_hdr *hdr;
+
+	hdr = trait_meta_reserve(ctx, 2);
+	if (!hdr)
+		return XDP_PASS;
+
+	trait_meta_set(ctx, hdr, 0, TRAIT_KEY_IFINDEX, sizeof(__u32), ifindex);
+	trait_meta_set(ctx, hdr, 1, TRAIT_KEY_RX_QUEUE, sizeof(__u32), rx_queue);
+	return XDP_PASS;
+}
+
+struct trait_slot {
+	__u64 val;
+	__u8 key;
+	__u8 len;
+};
+
+SEC("tc")
+int tc_meta_to_traits(struct __sk_buff *skb)
+{
+	struct trait_slot slots[MAX_META_TRAITS] = {};
+	void *data_meta = (void *)(long)skb->data_meta;
+	void *data = (void *)(long)skb->data;
+	struct trait_meta_hdr *hdr = data_meta;
+	struct trait_meta_ent *ent;
+	__u32 i, cnt;
+
+	if ((void *)(hdr + 1) > data || hdr->magic != TRAIT_META_MAGIC)
+		return TC_ACT_OK;
+	cnt = hdr->cnt;
+
+	/* Copy entries out first, setting traits may reuse the metadata area */
+	ent = (void *)(hdr + 1);
+	for (i = 0; i < MAX_META_TRAITS && i < cnt; i++) {
+		if ((void *)(ent + i + 1) > data)
+			return TC_ACT_OK;
+		slots[i].val = (__u64)ent[i].val_hi << 32 | ent[i].val_lo;
+		slots[i].key = ent[i].key;
+		slots[i].len = ent[i].len;
+	}
+
+	for (i = 0; i < MAX_META_TRAITS && i < cnt; i++) {
+		struct trait_slot *s = &slots[i];
+
+		switch (s->len) {
+		case 2:
+			bpf_skb_trait_set(skb, s->key, &(__u16){ s->val },
+					  sizeof(__u16), 0);
+			break;
+		case 4:
+			bpf_skb_trait_set(skb, s->key, &(__u32){ s->val },
+					  sizeof(__u32), 0);
+			break;
+		case 8:
+			bpf_skb_trait_set(skb, s->key, &s->val,
+					  sizeof(__u64), 0);
+			break;
+		}
+	}
+
+	return TC_ACT_OK;
+}
+
+const char _license[] SEC("license") = "GPL";
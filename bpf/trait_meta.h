@@ -0,0 +1,74 @@
+/* Compact trait record handed from XDP to tc in the metadata area.
+ * Mirrors skb_traits::trait_meta. Host byte order.
+ */
+#ifndef __TRAIT_META_H
+#define __TRAIT_META_H
+
+#include <linux/bpf.h>
+#include <linux/types.h>
+#include <bpf/bpf_helpers.h>
+
+#define TRAIT_META_MAGIC 0x5452
+/* bpf_xdp_adjust_meta grants at most 32 bytes, room for two entries */
+#define MAX_META_TRAITS 2
+
+struct trait_meta_hdr {
+	__u16 magic;
+	__u8 cnt;
+	__u8 _rsvd;
+};
+
+struct trait_meta_ent {
+	__u8 key;
+	__u8 len;
+	__u16 _rsvd;
+	__u32 val_lo;
+	__u32 val_hi;
+};
+
+/* Reserves a record for cnt traits in front of the packet. Fill in the
+ * entries with trait_meta_set(). Returns NULL if there is no room.
+ */
+static __always_inline struct trait_meta_hdr *
+trait_meta_reserve(struct xdp_md *ctx, __u8 cnt)
+{
+	int len = sizeof(struct trait_meta_hdr) +
+		  cnt * sizeof(struct trait_meta_ent);
+	struct trait_meta_hdr *hdr;
+	void *data;
+
+	if (cnt > MAX_META_TRAITS)
+		return NULL;
+	if (bpf_xdp_adjust_meta(ctx, -len))
+		return NULL;
+
+	hdr = (void *)(long)ctx->data_meta;
+	data = (void *)(long)ctx->data;
+	if ((void *)hdr + len > data)
+		return NULL;
+
+	hdr->magic = TRAIT_META_MAGIC;
+	hdr->cnt = cnt;
+	hdr->_rsvd = 0;
+	return hdr;
+}
+
+static __always_inline int trait_meta_set(struct xdp_md *ctx,
+					  struct trait_meta_hdr *hdr, __u8 idx,
+					  __u8 key, __u8 len, __u64 val)
+{
+	struct trait_meta_ent *ent = (void *)(hdr + 1);
+	void *data = (void *)(long)ctx->data;
+
+	if (idx >= hdr->cnt || (void *)(ent + idx + 1) > data)
+		return -1;
+
+	ent[idx].key = key;
+	ent[idx].len = len;
+	ent[idx]._rsvd = 0;
+	ent[idx].val_lo = val;
+	ent[idx].val_hi = val >> 32;
+	return 0;
+}
+
+#endif /* __TRAIT_META_H */
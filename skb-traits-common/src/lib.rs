@@ -0,0 +1,162 @@
+//! Trait key and value definitions shared by BPF programs and userspace.
+#![no_std]
+
+use core::marker::PhantomData;
+
+pub type TraitKey = u8;
+
+/// Highest key the kernel accepts.
+pub const MAX_KEY: TraitKey = (u64::BITS - 1) as TraitKey;
+
+/// Size of a trait value in bytes.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+#[repr(u8)]
+pub enum TraitWidth {
+    U16 = 2,
+    U32 = 4,
+    U64 = 8,
+}
+
+impl TraitWidth {
+    pub const fn bytes(self) -> usize {
+        self as usize
+    }
+
+    pub const fn from_bytes(len: usize) -> Option<TraitWidth> {
+        match len {
+            2 => Some(TraitWidth::U16),
+            4 => Some(TraitWidth::U32),
+            8 => Some(TraitWidth::U64),
+            _ => None,
+        }
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u16 {}
+    impl Sealed for u32 {}
+    impl Sealed for u64 {}
+}
+
+/// Integer type a trait value can have.
+pub trait TraitVal: sealed::Sealed + Copy {
+    const WIDTH: TraitWidth;
+
+    /// Truncates to the value's width.
+    fn from_u64(val: u64) -> Self;
+
+    fn to_u64(self) -> u64;
+}
+
+impl TraitVal for u16 {
+    const WIDTH: TraitWidth = TraitWidth::U16;
+
+    fn from_u64(val: u64) -> u16 {
+        val as u16
+    }
+
+    fn to_u64(self) -> u64 {
+        self.into()
+    }
+}
+
+impl TraitVal for u32 {
+    const WIDTH: TraitWidth = TraitWidth::U32;
+
+    fn from_u64(val: u64) -> u32 {
+        val as u32
+    }
+
+    fn to_u64(self) -> u64 {
+        self.into()
+    }
+}
+
+impl TraitVal for u64 {
+    const WIDTH: TraitWidth = TraitWidth::U64;
+
+    fn from_u64(val: u64) -> u64 {
+        val
+    }
+
+    fn to_u64(self) -> u64 {
+        self
+    }
+}
+
+/// Trait key bound to a value type. Define keys as constants in a crate
+/// shared by the BPF program and userspace, so that both agree on the key
+/// and the value width:
+///
+/// ```
+/// use skb_traits_common::Key;
+///
+/// pub const CLIENT_ID: Key<u32> = Key::new(42);
+/// ```
+///
+/// Out of range keys fail to compile when used in a constant:
+///
+/// ```compile_fail
+/// use skb_traits_common::Key;
+///
+/// pub const BAD: Key<u16> = Key::new(64);
+/// ```
+#[derive(Debug, Eq, Hash, PartialEq)]
+pub struct Key<T: TraitVal> {
+    key: TraitKey,
+    _val: PhantomData<T>,
+}
+
+impl<T: TraitVal> Key<T> {
+    pub const fn new(key: TraitKey) -> Key<T> {
+        assert!(key <= MAX_KEY, "trait key out of range");
+
+        Key {
+            key,
+            _val: PhantomData,
+        }
+    }
+
+    pub const fn key(&self) -> TraitKey {
+        self.key
+    }
+
+    pub const fn width(&self) -> TraitWidth {
+        T::WIDTH
+    }
+}
+
+impl<T: TraitVal> Clone for Key<T> {
+    fn clone(&self) -> Key<T> {
+        *self
+    }
+}
+
+impl<T: TraitVal> Copy for Key<T> {}
+
+impl<T: TraitVal> From<Key<T>> for TraitKey {
+    fn from(key: Key<T>) -> TraitKey {
+        key.key
+    }
+}
+
+/// Flags for setting a trait. The kernel defines none yet and rejects
+/// anything but `TraitFlags::empty()`.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct TraitFlags(u64);
+
+impl TraitFlags {
+    pub const fn empty() -> TraitFlags {
+        TraitFlags(0)
+    }
+
+    pub const fn from_bits(bits: u64) -> TraitFlags {
+        TraitFlags(bits)
+    }
+
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+}
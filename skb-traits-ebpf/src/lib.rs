@@ -0,0 +1,107 @@
+//! Safe wrappers for the skb trait kfuncs, for BPF programs written with aya.
+//!
+//! Calls resolve to kernel functions at load time. Link with `bpf-linker
+//! --btf` so that the object carries BTF for the extern declarations below.
+#![no_std]
+
+use aya_ebpf::bindings::__sk_buff;
+use aya_ebpf::programs::{SkBuffContext, TcContext};
+use aya_ebpf::EbpfContext;
+use core::ffi::{c_int, c_void};
+use core::mem;
+
+pub use skb_traits_common::*;
+
+const ENOENT: c_int = 2;
+
+extern "C" {
+    fn bpf_skb_trait_set(
+        skb: *const __sk_buff,
+        key: u64,
+        val: *const c_void,
+        val_sz: u64,
+        flags: u64,
+    ) -> c_int;
+
+    fn bpf_skb_trait_get(skb: *const __sk_buff, key: u64, val: *mut c_void, val_sz: u64) -> c_int;
+
+    fn bpf_skb_trait_del(skb: *const __sk_buff, key: u64) -> c_int;
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for aya_ebpf::programs::SkBuffContext {}
+    impl Sealed for aya_ebpf::programs::TcContext {}
+}
+
+/// Program context backed by an `__sk_buff`.
+pub trait SkbContext: sealed::Sealed {
+    fn skb(&self) -> *const __sk_buff;
+}
+
+impl SkbContext for SkBuffContext {
+    fn skb(&self) -> *const __sk_buff {
+        self.as_ptr().cast()
+    }
+}
+
+impl SkbContext for TcContext {
+    fn skb(&self) -> *const __sk_buff {
+        self.as_ptr().cast()
+    }
+}
+
+/// Sets a trait on the packet, replacing any previous value. Errors carry
+/// the negative errno returned by the kernel.
+pub fn set<C: SkbContext, T: TraitVal>(
+    ctx: &C,
+    key: Key<T>,
+    val: T,
+    flags: TraitFlags,
+) -> Result<(), c_int> {
+    let ret = unsafe {
+        bpf_skb_trait_set(
+            ctx.skb(),
+            key.key().into(),
+            &val as *const T as *const c_void,
+            mem::size_of::<T>() as u64,
+            flags.bits(),
+        )
+    };
+
+    match ret {
+        0 => Ok(()),
+        err => Err(err),
+    }
+}
+
+/// Reads a trait from the packet. Yields `None` if the trait is not set.
+pub fn get<C: SkbContext, T: TraitVal>(ctx: &C, key: Key<T>) -> Result<Option<T>, c_int> {
+    let mut val = T::from_u64(0);
+    let ret = unsafe {
+        bpf_skb_trait_get(
+            ctx.skb(),
+            key.key().into(),
+            &mut val as *mut T as *mut c_void,
+            mem::size_of::<T>() as u64,
+        )
+    };
+
+    match ret {
+        err if err == -ENOENT => Ok(None),
+        err if err < 0 => Err(err),
+        _ => Ok(Some(val)),
+    }
+}
+
+/// Removes a trait from the packet. Yields `false` if it was not set.
+pub fn delete<C: SkbContext>(ctx: &C, key: TraitKey) -> Result<bool, c_int> {
+    let ret = unsafe { bpf_skb_trait_del(ctx.skb(), key.into()) };
+
+    match ret {
+        0 => Ok(true),
+        err if err == -ENOENT => Ok(false),
+        err => Err(err),
+    }
+}
@@ -0,0 +1,14 @@
+use axum::extract::connect_info::Connected;
+use axum::serve::IncomingStream;
+use tokio::net::TcpListener;
+
+use crate::SynTraits;
+
+/// Lets handlers extract `ConnectInfo<SynTraits>`. Listener needs to have
+/// `TcpSaveSynTraits` enabled. Traits which can't be read back are reported
+/// as an empty set.
+impl Connected<IncomingStream<'_, TcpListener>> for SynTraits {
+    fn connect_info(stream: IncomingStream<'_, TcpListener>) -> Self {
+        SynTraits::from_socket(stream.io()).unwrap_or_default()
+    }
+}
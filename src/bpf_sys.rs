@@ -0,0 +1,507 @@
+//! Minimal bpf(2) syscall bindings.
+use libc::{c_int, c_long, c_void};
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::Path;
+
+use crate::ebpf::BpfInsn;
+
+const BPF_MAP_CREATE: c_int = 0;
+const BPF_MAP_LOOKUP_ELEM: c_int = 1;
+const BPF_MAP_UPDATE_ELEM: c_int = 2;
+const BPF_MAP_DELETE_ELEM: c_int = 3;
+const BPF_MAP_GET_NEXT_KEY: c_int = 4;
+const BPF_PROG_LOAD: c_int = 5;
+const BPF_OBJ_PIN: c_int = 6;
+const BPF_OBJ_GET: c_int = 7;
+const BPF_PROG_TEST_RUN: c_int = 10;
+const BPF_OBJ_GET_INFO_BY_FD: c_int = 15;
+const BPF_PROG_QUERY: c_int = 16;
+const BPF_LINK_CREATE: c_int = 28;
+const BPF_LINK_DETACH: c_int = 34;
+
+pub(crate) const BPF_PROG_TYPE_SOCKET_FILTER: u32 = 1;
+pub(crate) const BPF_PROG_TYPE_SCHED_CLS: u32 = 3;
+pub(crate) const BPF_PROG_TYPE_XDP: u32 = 6;
+pub(crate) const BPF_PROG_TYPE_SK_REUSEPORT: u32 = 21;
+pub(crate) const BPF_PROG_TYPE_SK_LOOKUP: u32 = 30;
+
+pub(crate) const BPF_SK_LOOKUP: u32 = 36;
+pub(crate) const BPF_XDP: u32 = 37;
+pub(crate) const BPF_TCX_INGRESS: u32 = 46;
+pub(crate) const BPF_TCX_EGRESS: u32 = 47;
+
+pub(crate) const BPF_MAP_TYPE_HASH: u32 = 1;
+pub(crate) const BPF_MAP_TYPE_LPM_TRIE: u32 = 11;
+pub(crate) const BPF_MAP_TYPE_SOCKMAP: u32 = 15;
+pub(crate) const BPF_MAP_TYPE_REUSEPORT_SOCKARRAY: u32 = 20;
+
+pub(crate) const BPF_F_NO_PREALLOC: u32 = 1;
+
+/// Leading part of `struct bpf_prog_info`. Kernel fills in as much as fits.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BpfProgInfo {
+    pub prog_type: u32,
+    pub id: u32,
+    pub tag: [u8; 8],
+}
+
+/// Leading part of `struct bpf_map_info`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BpfMapInfo {
+    pub map_type: u32,
+    pub id: u32,
+    pub key_size: u32,
+    pub value_size: u32,
+    pub max_entries: u32,
+    pub map_flags: u32,
+}
+
+#[repr(C, align(8))]
+#[derive(Default)]
+struct ProgLoadAttr {
+    prog_type: u32,
+    insn_cnt: u32,
+    insns: u64,
+    license: u64,
+    log_level: u32,
+    log_size: u32,
+    log_buf: u64,
+    kern_version: u32,
+    prog_flags: u32,
+    prog_name: [u8; 16],
+    prog_ifindex: u32,
+    expected_attach_type: u32,
+}
+
+/// Leading part of `struct bpf_link_info`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct BpfLinkInfo {
+    pub link_type: u32,
+    pub id: u32,
+    pub prog_id: u32,
+}
+
+#[repr(C, align(8))]
+#[derive(Default)]
+struct MapCreateAttr {
+    map_type: u32,
+    key_size: u32,
+    value_size: u32,
+    max_entries: u32,
+    map_flags: u32,
+    inner_map_fd: u32,
+}
+
+#[repr(C, align(8))]
+#[derive(Default)]
+struct MapElemAttr {
+    map_fd: u32,
+    key: u64,
+    value: u64,
+    flags: u64,
+}
+
+#[repr(C, align(8))]
+#[derive(Default)]
+struct LinkCreateAttr {
+    prog_fd: u32,
+    /// Network device index, or network namespace fd
+    target: u32,
+    attach_type: u32,
+    flags: u32,
+}
+
+#[repr(C)]
+#[derive(Default)]
+struct LinkDetachAttr {
+    link_fd: u32,
+}
+
+#[repr(C, align(8))]
+#[derive(Default)]
+struct ProgQueryAttr {
+    target_ifindex: u32,
+    attach_type: u32,
+    query_flags: u32,
+    attach_flags: u32,
+    prog_ids: u64,
+    prog_cnt: u32,
+    _pad: u32,
+}
+
+#[repr(C, align(8))]
+#[derive(Default)]
+struct ObjGetAttr {
+    pathname: u64,
+    bpf_fd: u32,
+    file_flags: u32,
+}
+
+#[repr(C, align(8))]
+#[derive(Default)]
+struct ObjGetInfoAttr {
+    bpf_fd: u32,
+    info_len: u32,
+    info: u64,
+}
+
+/// `test` member of `union bpf_attr`.
+#[repr(C, align(8))]
+#[derive(Default)]
+pub(crate) struct TestRunAttr {
+    pub prog_fd: u32,
+    pub retval: u32,
+    pub data_size_in: u32,
+    pub data_size_out: u32,
+    pub data_in: u64,
+    pub data_out: u64,
+    pub repeat: u32,
+    pub duration: u32,
+    pub ctx_size_in: u32,
+    pub ctx_size_out: u32,
+    pub ctx_in: u64,
+    pub ctx_out: u64,
+    pub flags: u32,
+    pub cpu: u32,
+    pub batch_size: u32,
+    pub _pad: u32,
+}
+
+fn bpf<T>(cmd: c_int, attr: &mut T) -> io::Result<c_long> {
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            cmd,
+            attr as *mut T as *mut c_void,
+            mem::size_of::<T>(),
+        )
+    };
+
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(res)
+}
+
+/// Error for arguments the caller got wrong.
+pub(crate) fn invalid(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+/// Checks that the fd refers to a BPF object of given kind. Info queries
+/// alone can't tell, as they accept map, prog and link fds alike.
+fn is_obj_fd(fd: BorrowedFd<'_>, kind: &str) -> bool {
+    let link = format!("/proc/self/fd/{}", fd.as_raw_fd());
+    match std::fs::read_link(link) {
+        Ok(target) => target.as_os_str() == format!("anon_inode:bpf-{kind}").as_str(),
+        // Without procfs leave it to the kernel
+        Err(_) => true,
+    }
+}
+
+/// Pins an object in bpffs, so that it outlives its file descriptors.
+pub(crate) fn obj_pin(fd: BorrowedFd<'_>, path: &Path) -> io::Result<()> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let mut attr = ObjGetAttr {
+        pathname: path.as_ptr() as u64,
+        bpf_fd: fd.as_raw_fd() as u32,
+        ..Default::default()
+    };
+    bpf(BPF_OBJ_PIN, &mut attr).map(drop)
+}
+
+/// Opens an object pinned in bpffs.
+pub(crate) fn obj_get(path: &Path) -> io::Result<OwnedFd> {
+    let path = CString::new(path.as_os_str().as_bytes())
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    let mut attr = ObjGetAttr {
+        pathname: path.as_ptr() as u64,
+        ..Default::default()
+    };
+    let fd = bpf(BPF_OBJ_GET, &mut attr)?;
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as c_int) })
+}
+
+pub(crate) fn prog_info(fd: BorrowedFd<'_>) -> io::Result<BpfProgInfo> {
+    if !is_obj_fd(fd, "prog") {
+        return Err(invalid(format!(
+            "File descriptor {} is not a BPF program",
+            fd.as_raw_fd()
+        )));
+    }
+
+    let mut info = BpfProgInfo::default();
+    let mut attr = ObjGetInfoAttr {
+        bpf_fd: fd.as_raw_fd() as u32,
+        info_len: mem::size_of::<BpfProgInfo>() as u32,
+        info: &mut info as *mut BpfProgInfo as u64,
+    };
+    bpf(BPF_OBJ_GET_INFO_BY_FD, &mut attr)?;
+
+    Ok(info)
+}
+
+pub(crate) fn prog_test_run(attr: &mut TestRunAttr) -> io::Result<()> {
+    bpf(BPF_PROG_TEST_RUN, attr).map(drop)
+}
+
+pub(crate) fn map_info(fd: BorrowedFd<'_>) -> io::Result<BpfMapInfo> {
+    if !is_obj_fd(fd, "map") {
+        return Err(invalid(format!(
+            "File descriptor {} is not a BPF map",
+            fd.as_raw_fd()
+        )));
+    }
+
+    let mut info = BpfMapInfo::default();
+    let mut attr = ObjGetInfoAttr {
+        bpf_fd: fd.as_raw_fd() as u32,
+        info_len: mem::size_of::<BpfMapInfo>() as u32,
+        info: &mut info as *mut BpfMapInfo as u64,
+    };
+    bpf(BPF_OBJ_GET_INFO_BY_FD, &mut attr)?;
+
+    Ok(info)
+}
+
+/// Checks that a map has the type and key and value sizes a program
+/// expects. `name` is what the program calls it.
+pub(crate) fn check_map(
+    fd: BorrowedFd<'_>,
+    name: &str,
+    map_type: u32,
+    key: usize,
+    value: usize,
+) -> io::Result<BpfMapInfo> {
+    let info = map_info(fd)?;
+    if info.map_type != map_type
+        || info.key_size as usize != key
+        || info.value_size as usize != value
+    {
+        return Err(invalid(format!(
+            "Map {} doesn't look like {} (type {}, key size {}, value size {})",
+            info.id, name, info.map_type, info.key_size, info.value_size
+        )));
+    }
+
+    Ok(info)
+}
+
+pub(crate) fn map_create(
+    map_type: u32,
+    key_size: usize,
+    value_size: usize,
+    max_entries: u32,
+    map_flags: u32,
+) -> io::Result<OwnedFd> {
+    let mut attr = MapCreateAttr {
+        map_type,
+        key_size: key_size as u32,
+        value_size: value_size as u32,
+        max_entries,
+        map_flags,
+        ..Default::default()
+    };
+    let fd = bpf(BPF_MAP_CREATE, &mut attr)?;
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as c_int) })
+}
+
+/// Yields `false` if there is no such key.
+pub(crate) fn map_lookup<K, V>(fd: BorrowedFd<'_>, key: &K, val: &mut V) -> io::Result<bool> {
+    let mut attr = MapElemAttr {
+        map_fd: fd.as_raw_fd() as u32,
+        key: key as *const K as u64,
+        value: val as *mut V as u64,
+        flags: 0,
+    };
+
+    match bpf(BPF_MAP_LOOKUP_ELEM, &mut attr) {
+        Ok(_) => Ok(true),
+        Err(err) if err.raw_os_error() == Some(libc::ENOENT) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+pub(crate) fn map_update<K, V>(fd: BorrowedFd<'_>, key: &K, val: &V) -> io::Result<()> {
+    let mut attr = MapElemAttr {
+        map_fd: fd.as_raw_fd() as u32,
+        key: key as *const K as u64,
+        value: val as *const V as u64,
+        flags: 0,
+    };
+    bpf(BPF_MAP_UPDATE_ELEM, &mut attr).map(drop)
+}
+
+/// Yields `false` if there is no such key.
+pub(crate) fn map_delete<K>(fd: BorrowedFd<'_>, key: &K) -> io::Result<bool> {
+    let mut attr = MapElemAttr {
+        map_fd: fd.as_raw_fd() as u32,
+        key: key as *const K as u64,
+        ..Default::default()
+    };
+
+    match bpf(BPF_MAP_DELETE_ELEM, &mut attr) {
+        Ok(_) => Ok(true),
+        Err(err) if err.raw_os_error() == Some(libc::ENOENT) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Yields `false` past the last key. Starts from the first key if `key` is
+/// `None`.
+pub(crate) fn map_next_key<K>(
+    fd: BorrowedFd<'_>,
+    key: Option<&K>,
+    next: &mut K,
+) -> io::Result<bool> {
+    let mut attr = MapElemAttr {
+        map_fd: fd.as_raw_fd() as u32,
+        key: key.map_or(0, |key| key as *const K as u64),
+        value: next as *mut K as u64,
+        flags: 0,
+    };
+
+    match bpf(BPF_MAP_GET_NEXT_KEY, &mut attr) {
+        Ok(_) => Ok(true),
+        Err(err) if err.raw_os_error() == Some(libc::ENOENT) => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Lists the keys of a map. Keys added or removed meanwhile may or may not
+/// show up.
+pub(crate) fn map_keys<K: Copy + Default>(fd: BorrowedFd<'_>) -> io::Result<Vec<K>> {
+    let mut keys = vec![];
+    let mut next = K::default();
+
+    while map_next_key(fd, keys.last(), &mut next)? {
+        keys.push(next);
+    }
+
+    Ok(keys)
+}
+
+/// Loads a GPL program. On rejection the error carries the verifier log.
+pub(crate) fn prog_load(prog_type: u32, name: &str, insns: &[BpfInsn]) -> io::Result<OwnedFd> {
+    prog_load_for(prog_type, 0, name, insns)
+}
+
+/// Same as `prog_load` for program types which must declare their attach
+/// type at load time.
+pub(crate) fn prog_load_for(
+    prog_type: u32,
+    expected_attach_type: u32,
+    name: &str,
+    insns: &[BpfInsn],
+) -> io::Result<OwnedFd> {
+    const LOG_SIZE: usize = 64 * 1024;
+
+    let license = c"GPL";
+    let mut attr = ProgLoadAttr {
+        prog_type,
+        expected_attach_type,
+        insn_cnt: insns.len() as u32,
+        insns: insns.as_ptr() as u64,
+        license: license.as_ptr() as u64,
+        ..Default::default()
+    };
+    // Name is optional, cut to fit with the terminating NUL
+    let name = &name.as_bytes()[..name.len().min(attr.prog_name.len() - 1)];
+    attr.prog_name[..name.len()].copy_from_slice(name);
+
+    let err = match bpf(BPF_PROG_LOAD, &mut attr) {
+        Ok(fd) => return Ok(unsafe { OwnedFd::from_raw_fd(fd as c_int) }),
+        Err(err) if err.raw_os_error() == Some(libc::EACCES) => err,
+        Err(err) if err.raw_os_error() == Some(libc::EINVAL) => err,
+        Err(err) => return Err(err),
+    };
+
+    // Load again to find out why the verifier said no
+    let mut log = vec![0u8; LOG_SIZE];
+    attr.log_level = 1;
+    attr.log_size = log.len() as u32;
+    attr.log_buf = log.as_mut_ptr() as u64;
+    if let Ok(fd) = bpf(BPF_PROG_LOAD, &mut attr) {
+        return Ok(unsafe { OwnedFd::from_raw_fd(fd as c_int) });
+    }
+
+    let len = log.iter().position(|&b| b == 0).unwrap_or(log.len());
+    let log = String::from_utf8_lossy(&log[..len]);
+    Err(io::Error::new(
+        err.kind(),
+        format!("{}\n{}", err, log.trim_end()),
+    ))
+}
+
+/// Attaches a program with a link, to a network device by index or to a
+/// network namespace by fd, depending on the attach type.
+pub(crate) fn link_create(
+    prog: BorrowedFd<'_>,
+    target: u32,
+    attach_type: u32,
+) -> io::Result<OwnedFd> {
+    let mut attr = LinkCreateAttr {
+        prog_fd: prog.as_raw_fd() as u32,
+        target,
+        attach_type,
+        flags: 0,
+    };
+    let fd = bpf(BPF_LINK_CREATE, &mut attr)?;
+
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as c_int) })
+}
+
+pub(crate) fn link_info(link: BorrowedFd<'_>) -> io::Result<BpfLinkInfo> {
+    let mut info = BpfLinkInfo::default();
+    let mut attr = ObjGetInfoAttr {
+        bpf_fd: link.as_raw_fd() as u32,
+        info_len: mem::size_of::<BpfLinkInfo>() as u32,
+        info: &mut info as *mut BpfLinkInfo as u64,
+    };
+    bpf(BPF_OBJ_GET_INFO_BY_FD, &mut attr)?;
+
+    Ok(info)
+}
+
+pub(crate) fn link_detach(link: BorrowedFd<'_>) -> io::Result<()> {
+    let mut attr = LinkDetachAttr {
+        link_fd: link.as_raw_fd() as u32,
+    };
+    bpf(BPF_LINK_DETACH, &mut attr).map(drop)
+}
+
+/// Lists ids of programs attached to a network device.
+pub(crate) fn prog_query(ifindex: u32, attach_type: u32) -> io::Result<Vec<u32>> {
+    loop {
+        let mut attr = ProgQueryAttr {
+            target_ifindex: ifindex,
+            attach_type,
+            ..Default::default()
+        };
+        bpf(BPF_PROG_QUERY, &mut attr)?;
+
+        let mut ids = vec![0u32; attr.prog_cnt as usize];
+        attr.prog_ids = ids.as_mut_ptr() as u64;
+        match bpf(BPF_PROG_QUERY, &mut attr) {
+            Ok(_) => {
+                ids.truncate(attr.prog_cnt as usize);
+                return Ok(ids);
+            }
+            // More programs attached in the meantime
+            Err(err) if err.raw_os_error() == Some(libc::ENOSPC) => continue,
+            Err(err) => return Err(err),
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Classic BPF program builder and ready-made socket filters.
+//!
+//! Programs see the packet from the transport header on for TCP and UDP
+//! sockets. Canned filters address headers relative to `SKF_NET_OFF`
+//! instead, so they work with any socket type.
+use crate::SockFilter;
+
+// Instruction classes
+pub const BPF_LD: u16 = 0x00;
+pub const BPF_LDX: u16 = 0x01;
+pub const BPF_ST: u16 = 0x02;
+pub const BPF_STX: u16 = 0x03;
+pub const BPF_ALU: u16 = 0x04;
+pub const BPF_JMP: u16 = 0x05;
+pub const BPF_RET: u16 = 0x06;
+pub const BPF_MISC: u16 = 0x07;
+
+// Load sizes
+pub const BPF_W: u16 = 0x00;
+pub const BPF_H: u16 = 0x08;
+pub const BPF_B: u16 = 0x10;
+
+// Load modes
+pub const BPF_IMM: u16 = 0x00;
+pub const BPF_ABS: u16 = 0x20;
+pub const BPF_IND: u16 = 0x40;
+pub const BPF_MEM: u16 = 0x60;
+pub const BPF_LEN: u16 = 0x80;
+pub const BPF_MSH: u16 = 0xa0;
+
+// ALU operations
+pub const BPF_ADD: u16 = 0x00;
+pub const BPF_SUB: u16 = 0x10;
+pub const BPF_MUL: u16 = 0x20;
+pub const BPF_DIV: u16 = 0x30;
+pub const BPF_OR: u16 = 0x40;
+pub const BPF_AND: u16 = 0x50;
+pub const BPF_LSH: u16 = 0x60;
+pub const BPF_RSH: u16 = 0x70;
+pub const BPF_NEG: u16 = 0x80;
+pub const BPF_MOD: u16 = 0x90;
+pub const BPF_XOR: u16 = 0xa0;
+
+// Jump operations
+pub const BPF_JA: u16 = 0x00;
+pub const BPF_JEQ: u16 = 0x10;
+pub const BPF_JGT: u16 = 0x20;
+pub const BPF_JGE: u16 = 0x30;
+pub const BPF_JSET: u16 = 0x40;
+
+// Operand sources
+pub const BPF_K: u16 = 0x00;
+pub const BPF_X: u16 = 0x08;
+pub const BPF_A: u16 = 0x10;
+
+// Register transfers
+pub const BPF_TAX: u16 = 0x00;
+pub const BPF_TXA: u16 = 0x80;
+
+/// Base of ancillary data loads, e.g. `SKF_AD_OFF + SKF_AD_PROTOCOL`.
+pub const SKF_AD_OFF: i32 = -0x1000;
+pub const SKF_AD_PROTOCOL: i32 = 0;
+pub const SKF_AD_PKTTYPE: i32 = 4;
+pub const SKF_AD_IFINDEX: i32 = 8;
+pub const SKF_AD_MARK: i32 = 20;
+/// Offsets from here on address the network header.
+pub const SKF_NET_OFF: i32 = -0x100000;
+/// Offsets from here on address the link-layer header.
+pub const SKF_LL_OFF: i32 = -0x200000;
+
+/// Return value accepting the whole packet.
+pub const ACCEPT: u32 = u32::MAX;
+/// Return value dropping the packet.
+pub const DROP: u32 = 0;
+
+/// Assembles a classic BPF program. Jump offsets count instructions to skip,
+/// as in `BPF_JUMP`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct FilterBuilder {
+    insns: Vec<SockFilter>,
+}
+
+impl FilterBuilder {
+    pub fn new() -> FilterBuilder {
+        FilterBuilder::default()
+    }
+
+    pub fn stmt(mut self, code: u16, k: u32) -> FilterBuilder {
+        self.insns.push(SockFilter::stmt(code, k));
+        self
+    }
+
+    pub fn jump(mut self, code: u16, k: u32, jt: u8, jf: u8) -> FilterBuilder {
+        self.insns.push(SockFilter::jump(code, k, jt, jf));
+        self
+    }
+
+    /// Loads `size` bytes at `off` into A.
+    pub fn ld_abs(self, size: u16, off: i32) -> FilterBuilder {
+        self.stmt(BPF_LD | size | BPF_ABS, off as u32)
+    }
+
+    /// Loads `size` bytes at X + `off` into A.
+    pub fn ld_ind(self, size: u16, off: i32) -> FilterBuilder {
+        self.stmt(BPF_LD | size | BPF_IND, off as u32)
+    }
+
+    pub fn ld_imm(self, k: u32) -> FilterBuilder {
+        self.stmt(BPF_LD | BPF_IMM, k)
+    }
+
+    pub fn ld_len(self) -> FilterBuilder {
+        self.stmt(BPF_LD | BPF_W | BPF_LEN, 0)
+    }
+
+    /// Loads IPv4 header length at `off` into X, i.e. `4 * ([off] & 0xf)`.
+    pub fn ldx_msh(self, off: i32) -> FilterBuilder {
+        self.stmt(BPF_LDX | BPF_B | BPF_MSH, off as u32)
+    }
+
+    pub fn alu(self, op: u16, k: u32) -> FilterBuilder {
+        self.stmt(BPF_ALU | op | BPF_K, k)
+    }
+
+    pub fn tax(self) -> FilterBuilder {
+        self.stmt(BPF_MISC | BPF_TAX, 0)
+    }
+
+    pub fn txa(self) -> FilterBuilder {
+        self.stmt(BPF_MISC | BPF_TXA, 0)
+    }
+
+    pub fn ja(self, off: u32) -> FilterBuilder {
+        self.stmt(BPF_JMP | BPF_JA, off)
+    }
+
+    pub fn jeq(self, k: u32, jt: u8, jf: u8) -> FilterBuilder {
+        self.jump(BPF_JMP | BPF_JEQ | BPF_K, k, jt, jf)
+    }
+
+    pub fn jgt(self, k: u32, jt: u8, jf: u8) -> FilterBuilder {
+        self.jump(BPF_JMP | BPF_JGT | BPF_K, k, jt, jf)
+    }
+
+    pub fn jge(self, k: u32, jt: u8, jf: u8) -> FilterBuilder {
+        self.jump(BPF_JMP | BPF_JGE | BPF_K, k, jt, jf)
+    }
+
+    pub fn jset(self, k: u32, jt: u8, jf: u8) -> FilterBuilder {
+        self.jump(BPF_JMP | BPF_JSET | BPF_K, k, jt, jf)
+    }
+
+    /// Returns `k`, the number of bytes to keep.
+    pub fn ret(self, k: u32) -> FilterBuilder {
+        self.stmt(BPF_RET | BPF_K, k)
+    }
+
+    pub fn ret_a(self) -> FilterBuilder {
+        self.stmt(BPF_RET | BPF_A, 0)
+    }
+
+    pub fn len(&self) -> usize {
+        self.insns.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.insns.is_empty()
+    }
+
+    pub fn build(self) -> Vec<SockFilter> {
+        self.insns
+    }
+}
+
+pub fn accept_all() -> Vec<SockFilter> {
+    FilterBuilder::new().ret(ACCEPT).build()
+}
+
+pub fn drop_all() -> Vec<SockFilter> {
+    FilterBuilder::new().ret(DROP).build()
+}
+
+/// Accepts only UDP datagrams to the given port, over IPv4 or IPv6.
+pub fn udp_dst_port(port: u16) -> Vec<SockFilter> {
+    dst_port(libc::IPPROTO_UDP as u8, port)
+}
+
+/// Accepts only TCP segments to the given port, over IPv4 or IPv6.
+pub fn tcp_dst_port(port: u16) -> Vec<SockFilter> {
+    dst_port(libc::IPPROTO_TCP as u8, port)
+}
+
+/// Matches the destination port of a transport protocol which places it at
+/// offset 2. Drops IPv4 fragments past the first one and IPv6 packets with
+/// extension headers.
+fn dst_port(proto: u8, port: u16) -> Vec<SockFilter> {
+    let (proto, port) = (proto as u32, port as u32);
+
+    FilterBuilder::new()
+        .ld_abs(BPF_B, SKF_NET_OFF)
+        .alu(BPF_AND, 0xf0)
+        .jeq(0x40, 0, 7) // not IPv4: 10
+        // IPv4
+        .ld_abs(BPF_B, SKF_NET_OFF + 9)
+        .jeq(proto, 0, 11) // drop: 16
+        .ld_abs(BPF_H, SKF_NET_OFF + 6)
+        .jset(0x1fff, 9, 0) // drop: 16
+        .ldx_msh(SKF_NET_OFF)
+        .ld_ind(BPF_H, SKF_NET_OFF + 2)
+        .jeq(port, 5, 6) // accept: 15, drop: 16
+        // IPv6
+        .jeq(0x60, 0, 5) // drop: 16
+        .ld_abs(BPF_B, SKF_NET_OFF + 6)
+        .jeq(proto, 0, 3) // drop: 16
+        .ld_abs(BPF_H, SKF_NET_OFF + 40 + 2)
+        .jeq(port, 0, 1) // accept: 15, drop: 16
+        .ret(ACCEPT)
+        .ret(DROP)
+        .build()
+}
@@ -0,0 +1,598 @@
+//! eBPF bytecode generated at runtime, for stamping fixed traits on every
+//! packet without shipping clang or object files.
+//!
+//! [`trait_stamp_insns`] emits the program, [`load_trait_stamp`] resolves
+//! the `bpf_skb_trait_set` kfunc and loads it as a socket filter ready for
+//! `SoAttachBpf` or `attach_bpf`. The tc flavour is for `attach_tc`.
+use std::io;
+use std::os::fd::OwnedFd;
+
+use crate::bpf_sys::{
+    self, BPF_PROG_TYPE_SCHED_CLS, BPF_PROG_TYPE_SK_LOOKUP, BPF_PROG_TYPE_SK_REUSEPORT,
+    BPF_PROG_TYPE_SOCKET_FILTER, BPF_PROG_TYPE_XDP, BPF_SK_LOOKUP,
+};
+use crate::trait_meta::{check_meta_traits, META_ENTRY_LEN, META_HDR_LEN};
+use crate::{TraitKey, TraitValue, MAX_KEY};
+use crate::{MAX_META_TRAITS, TRAIT_META_MAGIC};
+
+// Instruction classes
+pub const BPF_LD: u8 = 0x00;
+pub const BPF_LDX: u8 = 0x01;
+pub const BPF_ST: u8 = 0x02;
+pub const BPF_STX: u8 = 0x03;
+pub const BPF_ALU: u8 = 0x04;
+pub const BPF_JMP: u8 = 0x05;
+pub const BPF_ALU64: u8 = 0x07;
+
+// Load and store sizes
+pub const BPF_W: u8 = 0x00;
+pub const BPF_H: u8 = 0x08;
+pub const BPF_B: u8 = 0x10;
+pub const BPF_DW: u8 = 0x18;
+
+// Load and store modes
+pub const BPF_IMM: u8 = 0x00;
+pub const BPF_MEM: u8 = 0x60;
+
+// Operand sources
+pub const BPF_K: u8 = 0x00;
+pub const BPF_X: u8 = 0x08;
+
+// ALU operations used here
+pub const BPF_ADD: u8 = 0x00;
+pub const BPF_OR: u8 = 0x40;
+pub const BPF_LSH: u8 = 0x60;
+pub const BPF_MOV: u8 = 0xb0;
+
+// Jump operations used here
+pub const BPF_JA: u8 = 0x00;
+pub const BPF_JEQ: u8 = 0x10;
+pub const BPF_JGT: u8 = 0x20;
+pub const BPF_JNE: u8 = 0x50;
+pub const BPF_CALL: u8 = 0x80;
+pub const BPF_EXIT: u8 = 0x90;
+pub const BPF_JLE: u8 = 0xb0;
+
+/// Source register value marking a 64-bit load as a map reference by fd.
+pub const BPF_PSEUDO_MAP_FD: u8 = 1;
+/// Source register value marking a call as a kfunc call by BTF id.
+pub const BPF_PSEUDO_KFUNC_CALL: u8 = 2;
+
+// Registers. R1 holds the context on entry, R10 is the frame pointer.
+pub const R0: u8 = 0;
+pub const R1: u8 = 1;
+pub const R2: u8 = 2;
+pub const R3: u8 = 3;
+pub const R4: u8 = 4;
+pub const R5: u8 = 5;
+pub const R6: u8 = 6;
+pub const R7: u8 = 7;
+pub const R8: u8 = 8;
+pub const R9: u8 = 9;
+pub const R10: u8 = 10;
+
+/// Name of the kfunc the stamp program calls.
+pub const TRAIT_SET_KFUNC: &str = "bpf_skb_trait_set";
+
+const VMLINUX_BTF: &str = "/sys/kernel/btf/vmlinux";
+
+/// Offset of `len` in `struct __sk_buff`.
+const SKB_LEN_OFF: i16 = 0;
+
+/// tc verdict letting the packet through.
+pub const TC_ACT_OK: i32 = 0;
+/// XDP verdict letting the packet through.
+pub const XDP_PASS: i32 = 2;
+/// sk_reuseport verdict accepting the packet, on the selected socket if
+/// any, else on one picked by the kernel.
+pub const SK_PASS: i32 = 1;
+
+/// eBPF instruction, `struct bpf_insn`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct BpfInsn {
+    pub code: u8,
+    /// Destination register in the low nibble, source in the high one.
+    pub regs: u8,
+    pub off: i16,
+    pub imm: i32,
+}
+
+impl BpfInsn {
+    pub const fn new(code: u8, dst: u8, src: u8, off: i16, imm: i32) -> BpfInsn {
+        BpfInsn {
+            code,
+            regs: (src << 4) | (dst & 0x0f),
+            off,
+            imm,
+        }
+    }
+
+    pub const fn dst(&self) -> u8 {
+        self.regs & 0x0f
+    }
+
+    pub const fn src(&self) -> u8 {
+        self.regs >> 4
+    }
+
+    /// `dst = src`
+    pub const fn mov64_reg(dst: u8, src: u8) -> BpfInsn {
+        BpfInsn::new(BPF_ALU64 | BPF_MOV | BPF_X, dst, src, 0, 0)
+    }
+
+    /// `dst = imm`
+    pub const fn mov64_imm(dst: u8, imm: i32) -> BpfInsn {
+        BpfInsn::new(BPF_ALU64 | BPF_MOV | BPF_K, dst, 0, 0, imm)
+    }
+
+    /// `dst += imm`
+    pub const fn add64_imm(dst: u8, imm: i32) -> BpfInsn {
+        BpfInsn::new(BPF_ALU64 | BPF_ADD | BPF_K, dst, 0, 0, imm)
+    }
+
+    /// `dst op= imm`
+    pub const fn alu64_imm(op: u8, dst: u8, imm: i32) -> BpfInsn {
+        BpfInsn::new(BPF_ALU64 | op | BPF_K, dst, 0, 0, imm)
+    }
+
+    /// `dst op= src`
+    pub const fn alu64_reg(op: u8, dst: u8, src: u8) -> BpfInsn {
+        BpfInsn::new(BPF_ALU64 | op | BPF_X, dst, src, 0, 0)
+    }
+
+    /// `dst = imm`, 64-bit wide. Takes two instruction slots.
+    pub const fn ld_imm64(dst: u8, imm: u64) -> [BpfInsn; 2] {
+        [
+            BpfInsn::new(BPF_LD | BPF_DW | BPF_IMM, dst, 0, 0, imm as i32),
+            BpfInsn::new(0, 0, 0, 0, (imm >> 32) as i32),
+        ]
+    }
+
+    /// `dst = map`, loads a map by its file descriptor. Takes two slots.
+    pub const fn ld_map_fd(dst: u8, map_fd: i32) -> [BpfInsn; 2] {
+        [
+            BpfInsn::new(BPF_LD | BPF_DW | BPF_IMM, dst, BPF_PSEUDO_MAP_FD, 0, map_fd),
+            BpfInsn::new(0, 0, 0, 0, 0),
+        ]
+    }
+
+    /// `dst = *(size *)(src + off)`
+    pub const fn ldx_mem(size: u8, dst: u8, src: u8, off: i16) -> BpfInsn {
+        BpfInsn::new(BPF_LDX | size | BPF_MEM, dst, src, off, 0)
+    }
+
+    /// `*(size *)(dst + off) = imm`
+    pub const fn st_mem(size: u8, dst: u8, off: i16, imm: i32) -> BpfInsn {
+        BpfInsn::new(BPF_ST | size | BPF_MEM, dst, 0, off, imm)
+    }
+
+    /// `*(size *)(dst + off) = src`
+    pub const fn stx_mem(size: u8, dst: u8, src: u8, off: i16) -> BpfInsn {
+        BpfInsn::new(BPF_STX | size | BPF_MEM, dst, src, off, 0)
+    }
+
+    /// `if dst op imm goto +off`
+    pub const fn jmp_imm(op: u8, dst: u8, imm: i32, off: i16) -> BpfInsn {
+        BpfInsn::new(BPF_JMP | op | BPF_K, dst, 0, off, imm)
+    }
+
+    /// `if dst op src goto +off`
+    pub const fn jmp_reg(op: u8, dst: u8, src: u8, off: i16) -> BpfInsn {
+        BpfInsn::new(BPF_JMP | op | BPF_X, dst, src, off, 0)
+    }
+
+    /// `goto +off`
+    pub const fn ja(off: i16) -> BpfInsn {
+        BpfInsn::new(BPF_JMP | BPF_JA, 0, 0, off, 0)
+    }
+
+    /// Calls a BPF helper by its id.
+    pub const fn call_helper(id: i32) -> BpfInsn {
+        BpfInsn::new(BPF_JMP | BPF_CALL, 0, 0, 0, id)
+    }
+
+    /// Calls a kernel function by its BTF id in vmlinux.
+    pub const fn call_kfunc(btf_id: u32) -> BpfInsn {
+        BpfInsn::new(
+            BPF_JMP | BPF_CALL,
+            0,
+            BPF_PSEUDO_KFUNC_CALL,
+            0,
+            btf_id as i32,
+        )
+    }
+
+    pub const fn exit() -> BpfInsn {
+        BpfInsn::new(BPF_JMP | BPF_EXIT, 0, 0, 0, 0)
+    }
+}
+
+/// Emits a socket filter that sets the given traits on every packet and
+/// accepts it whole. Errors from setting a trait are ignored, so a packet
+/// whose trait area is full still goes through.
+///
+/// `trait_set_id` is the BTF id of `bpf_skb_trait_set` in vmlinux, see
+/// [`vmlinux_func_id`].
+pub fn trait_stamp_insns(traits: &[(TraitKey, TraitValue)], trait_set_id: u32) -> Vec<BpfInsn> {
+    let mut insns = trait_set_insns(traits, trait_set_id);
+    insns.extend([
+        BpfInsn::ldx_mem(BPF_W, R0, R6, SKB_LEN_OFF),
+        BpfInsn::exit(),
+    ]);
+
+    insns
+}
+
+/// Same as [`trait_stamp_insns`] but emits a tc classifier, which lets
+/// every packet through with `TC_ACT_OK`.
+pub fn trait_stamp_tc_insns(traits: &[(TraitKey, TraitValue)], trait_set_id: u32) -> Vec<BpfInsn> {
+    let mut insns = trait_set_insns(traits, trait_set_id);
+    insns.extend([BpfInsn::mov64_imm(R0, TC_ACT_OK), BpfInsn::exit()]);
+
+    insns
+}
+
+/// Sets the traits, leaving the context in R6.
+fn trait_set_insns(traits: &[(TraitKey, TraitValue)], trait_set_id: u32) -> Vec<BpfInsn> {
+    // Value slot on the stack
+    const VAL_OFF: i16 = -8;
+
+    let mut insns = vec![BpfInsn::mov64_reg(R6, R1)];
+
+    for (key, val) in traits {
+        match *val {
+            TraitValue::U16(v) => insns.push(BpfInsn::st_mem(BPF_H, R10, VAL_OFF, v.into())),
+            TraitValue::U32(v) => insns.push(BpfInsn::st_mem(BPF_W, R10, VAL_OFF, v as i32)),
+            TraitValue::U64(v) => {
+                insns.extend(BpfInsn::ld_imm64(R1, v));
+                insns.push(BpfInsn::stx_mem(BPF_DW, R10, R1, VAL_OFF));
+            }
+        }
+        insns.extend([
+            BpfInsn::mov64_reg(R1, R6),
+            BpfInsn::mov64_imm(R2, (*key).into()),
+            BpfInsn::mov64_reg(R3, R10),
+            BpfInsn::add64_imm(R3, VAL_OFF.into()),
+            BpfInsn::mov64_imm(R4, val.width().bytes() as i32),
+            BpfInsn::mov64_imm(R5, 0),
+            BpfInsn::call_kfunc(trait_set_id),
+        ]);
+    }
+
+    insns
+}
+
+/// Assembles instructions with forward jumps to labels.
+#[derive(Default)]
+struct Asm {
+    insns: Vec<BpfInsn>,
+    labels: Vec<Option<usize>>,
+    fixups: Vec<(usize, usize)>,
+}
+
+impl Asm {
+    fn label(&mut self) -> usize {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    fn bind(&mut self, label: usize) {
+        self.labels[label] = Some(self.insns.len());
+    }
+
+    fn push(&mut self, insn: BpfInsn) {
+        self.insns.push(insn);
+    }
+
+    /// Emits a jump with its offset pointing at `label`.
+    fn jump(&mut self, insn: BpfInsn, label: usize) {
+        self.fixups.push((self.insns.len(), label));
+        self.insns.push(insn);
+    }
+
+    fn finish(mut self) -> Vec<BpfInsn> {
+        for (at, label) in self.fixups {
+            let target = self.labels[label].expect("jump to unbound label");
+            self.insns[at].off = (target - at - 1) as i16;
+        }
+        self.insns
+    }
+}
+
+/// Emits an XDP program which writes a fixed trait record into the
+/// metadata area of every packet, a reference for the format described in
+/// `trait_meta`. Packets always pass, with or without the record.
+pub fn xdp_meta_stamp_insns(traits: &[(TraitKey, TraitValue)]) -> Vec<BpfInsn> {
+    const BPF_FUNC_XDP_ADJUST_META: i32 = 54;
+    const XDP_MD_DATA_OFF: i16 = 0;
+    const XDP_MD_DATA_META_OFF: i16 = 8;
+
+    let meta_len = (META_HDR_LEN + traits.len() * META_ENTRY_LEN) as i32;
+
+    let mut asm = Asm::default();
+    let pass = asm.label();
+
+    asm.push(BpfInsn::mov64_reg(R6, R1));
+    asm.push(BpfInsn::mov64_imm(R2, -meta_len));
+    asm.push(BpfInsn::call_helper(BPF_FUNC_XDP_ADJUST_META));
+    asm.jump(BpfInsn::jmp_imm(BPF_JNE, R0, 0, 0), pass);
+
+    asm.push(BpfInsn::ldx_mem(BPF_W, R2, R6, XDP_MD_DATA_META_OFF));
+    asm.push(BpfInsn::ldx_mem(BPF_W, R3, R6, XDP_MD_DATA_OFF));
+    asm.push(BpfInsn::mov64_reg(R1, R2));
+    asm.push(BpfInsn::add64_imm(R1, meta_len));
+    asm.jump(BpfInsn::jmp_reg(BPF_JGT, R1, R3, 0), pass);
+
+    asm.push(BpfInsn::st_mem(BPF_H, R2, 0, TRAIT_META_MAGIC.into()));
+    asm.push(BpfInsn::st_mem(BPF_B, R2, 2, traits.len() as i32));
+    asm.push(BpfInsn::st_mem(BPF_B, R2, 3, 0));
+    for (i, (key, val)) in traits.iter().enumerate() {
+        let off = (META_HDR_LEN + i * META_ENTRY_LEN) as i16;
+        let v = val.to_u64();
+
+        asm.push(BpfInsn::st_mem(BPF_B, R2, off, (*key).into()));
+        asm.push(BpfInsn::st_mem(
+            BPF_B,
+            R2,
+            off + 1,
+            val.width().bytes() as i32,
+        ));
+        asm.push(BpfInsn::st_mem(BPF_H, R2, off + 2, 0));
+        asm.push(BpfInsn::st_mem(BPF_W, R2, off + 4, v as i32));
+        asm.push(BpfInsn::st_mem(BPF_W, R2, off + 8, (v >> 32) as i32));
+    }
+
+    asm.bind(pass);
+    asm.push(BpfInsn::mov64_imm(R0, XDP_PASS));
+    asm.push(BpfInsn::exit());
+
+    asm.finish()
+}
+
+/// Emits the tc classifier behind `MetaBridge`, which sets the traits
+/// found in a metadata record. Entries are copied to the stack before any
+/// trait is set, as setting traits may reuse the metadata area.
+pub fn meta_to_traits_insns(trait_set_id: u32) -> Vec<BpfInsn> {
+    const SKB_DATA_OFF: i16 = 76;
+    const SKB_DATA_META_OFF: i16 = 140;
+    // Stack slot per entry: u64 value, u8 key, u8 len
+    const SLOT_LEN: i16 = 16;
+
+    let slot = |i: usize| -SLOT_LEN * (i as i16 + 1);
+
+    let mut asm = Asm::default();
+    let out = asm.label();
+    let set = asm.label();
+
+    // R8 = data_meta, R7 = data, R9 = entry count
+    asm.push(BpfInsn::mov64_reg(R6, R1));
+    asm.push(BpfInsn::ldx_mem(BPF_W, R7, R6, SKB_DATA_OFF));
+    asm.push(BpfInsn::ldx_mem(BPF_W, R8, R6, SKB_DATA_META_OFF));
+    asm.push(BpfInsn::mov64_reg(R1, R8));
+    asm.push(BpfInsn::add64_imm(R1, META_HDR_LEN as i32));
+    asm.jump(BpfInsn::jmp_reg(BPF_JGT, R1, R7, 0), out);
+    asm.push(BpfInsn::ldx_mem(BPF_H, R1, R8, 0));
+    asm.jump(
+        BpfInsn::jmp_imm(BPF_JNE, R1, TRAIT_META_MAGIC.into(), 0),
+        out,
+    );
+    asm.push(BpfInsn::ldx_mem(BPF_B, R9, R8, 2));
+
+    // Copy entries while the packet pointers are valid
+    for i in 0..MAX_META_TRAITS {
+        let off = (META_HDR_LEN + i * META_ENTRY_LEN) as i16;
+
+        asm.jump(BpfInsn::jmp_imm(BPF_JLE, R9, i as i32, 0), set);
+        asm.push(BpfInsn::mov64_reg(R1, R8));
+        asm.push(BpfInsn::add64_imm(
+            R1,
+            (off as usize + META_ENTRY_LEN) as i32,
+        ));
+        asm.jump(BpfInsn::jmp_reg(BPF_JGT, R1, R7, 0), out);
+
+        asm.push(BpfInsn::ldx_mem(BPF_W, R2, R8, off + 4));
+        asm.push(BpfInsn::ldx_mem(BPF_W, R3, R8, off + 8));
+        asm.push(BpfInsn::alu64_imm(BPF_LSH, R3, 32));
+        asm.push(BpfInsn::alu64_reg(BPF_OR, R3, R2));
+        asm.push(BpfInsn::stx_mem(BPF_DW, R10, R3, slot(i)));
+        asm.push(BpfInsn::ldx_mem(BPF_B, R2, R8, off));
+        asm.push(BpfInsn::stx_mem(BPF_B, R10, R2, slot(i) + 8));
+        asm.push(BpfInsn::ldx_mem(BPF_B, R2, R8, off + 1));
+        asm.push(BpfInsn::stx_mem(BPF_B, R10, R2, slot(i) + 9));
+    }
+
+    asm.bind(set);
+    for i in 0..MAX_META_TRAITS {
+        let (w2, w4, call, next) = (asm.label(), asm.label(), asm.label(), asm.label());
+
+        asm.jump(BpfInsn::jmp_imm(BPF_JLE, R9, i as i32, 0), out);
+        asm.push(BpfInsn::ldx_mem(BPF_DW, R3, R10, slot(i)));
+        asm.push(BpfInsn::ldx_mem(BPF_B, R4, R10, slot(i) + 9));
+        // Store the value at its width, so that the kfunc sees a constant
+        // size
+        asm.jump(BpfInsn::jmp_imm(BPF_JEQ, R4, 2, 0), w2);
+        asm.jump(BpfInsn::jmp_imm(BPF_JEQ, R4, 4, 0), w4);
+        asm.jump(BpfInsn::jmp_imm(BPF_JNE, R4, 8, 0), next);
+        asm.jump(BpfInsn::ja(0), call);
+        asm.bind(w2);
+        asm.push(BpfInsn::stx_mem(BPF_H, R10, R3, slot(i)));
+        asm.jump(BpfInsn::ja(0), call);
+        asm.bind(w4);
+        asm.push(BpfInsn::stx_mem(BPF_W, R10, R3, slot(i)));
+        asm.bind(call);
+        asm.push(BpfInsn::mov64_reg(R1, R6));
+        asm.push(BpfInsn::ldx_mem(BPF_B, R2, R10, slot(i) + 8));
+        asm.push(BpfInsn::mov64_reg(R3, R10));
+        asm.push(BpfInsn::add64_imm(R3, slot(i).into()));
+        asm.push(BpfInsn::mov64_imm(R5, 0));
+        asm.push(BpfInsn::call_kfunc(trait_set_id));
+        asm.bind(next);
+    }
+
+    asm.bind(out);
+    asm.push(BpfInsn::mov64_imm(R0, TC_ACT_OK));
+    asm.push(BpfInsn::exit());
+
+    asm.finish()
+}
+
+fn btf_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("Bad BTF: {}", msg))
+}
+
+/// Looks up a function by name in raw BTF data, as found in
+/// `/sys/kernel/btf/`. Yields its type id, or `None` if there is no such
+/// function.
+pub fn find_btf_func(btf: &[u8], name: &str) -> io::Result<Option<u32>> {
+    const BTF_MAGIC: u16 = 0xeb9f;
+    const BTF_KIND_FUNC: u32 = 12;
+
+    let u16_at = |off: usize| -> io::Result<u16> {
+        let b = btf
+            .get(off..off + 2)
+            .ok_or_else(|| btf_error("truncated"))?;
+        Ok(u16::from_ne_bytes([b[0], b[1]]))
+    };
+    let u32_at = |off: usize| -> io::Result<u32> {
+        let b = btf
+            .get(off..off + 4)
+            .ok_or_else(|| btf_error("truncated"))?;
+        Ok(u32::from_ne_bytes([b[0], b[1], b[2], b[3]]))
+    };
+
+    if u16_at(0)? != BTF_MAGIC {
+        return Err(btf_error("wrong magic or byte order"));
+    }
+    let hdr_len = u32_at(4)? as usize;
+    let type_off = hdr_len + u32_at(8)? as usize;
+    let type_end = type_off + u32_at(12)? as usize;
+    let str_off = hdr_len + u32_at(16)? as usize;
+    let str_end = str_off + u32_at(20)? as usize;
+    let strings = btf
+        .get(str_off..str_end)
+        .ok_or_else(|| btf_error("string section out of bounds"))?;
+
+    let mut off = type_off;
+    let mut id = 1;
+    while off < type_end {
+        let name_off = u32_at(off)? as usize;
+        let info = u32_at(off + 4)?;
+        let kind = (info >> 24) & 0x1f;
+        let vlen = (info & 0xffff) as usize;
+
+        if kind == BTF_KIND_FUNC {
+            let found = strings
+                .get(name_off..)
+                .and_then(|s| s.split(|&b| b == 0).next())
+                .ok_or_else(|| btf_error("name out of bounds"))?;
+            if found == name.as_bytes() {
+                return Ok(Some(id));
+            }
+        }
+
+        // Kind specific data following struct btf_type
+        off += 12
+            + match kind {
+                // INT, VAR, DECL_TAG
+                1 | 14 | 17 => 4,
+                // ARRAY
+                3 => 12,
+                // STRUCT, UNION, DATASEC, ENUM64
+                4 | 5 | 15 | 19 => vlen * 12,
+                // ENUM, FUNC_PROTO
+                6 | 13 => vlen * 8,
+                // PTR, FWD, TYPEDEF, VOLATILE, CONST, RESTRICT, FUNC, FLOAT,
+                // TYPE_TAG
+                2 | 7..=12 | 16 | 18 => 0,
+                _ => return Err(btf_error(&format!("unknown kind {}", kind))),
+            };
+        id += 1;
+    }
+
+    Ok(None)
+}
+
+/// Looks up a kernel function in vmlinux BTF. Fails with `NotFound` if the
+/// kernel has no such function.
+pub fn vmlinux_func_id(name: &str) -> io::Result<u32> {
+    let btf = std::fs::read(VMLINUX_BTF)?;
+
+    find_btf_func(&btf, name)?.ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Function {} not found in kernel BTF", name),
+        )
+    })
+}
+
+/// Loads a socket filter. On rejection the error carries the verifier log.
+pub fn load_socket_filter(name: &str, insns: &[BpfInsn]) -> io::Result<OwnedFd> {
+    bpf_sys::prog_load(BPF_PROG_TYPE_SOCKET_FILTER, name, insns)
+}
+
+/// Loads an XDP program, for `attach_xdp`.
+pub fn load_xdp_prog(name: &str, insns: &[BpfInsn]) -> io::Result<OwnedFd> {
+    bpf_sys::prog_load(BPF_PROG_TYPE_XDP, name, insns)
+}
+
+/// Loads a tc classifier, for `attach_tc`.
+pub fn load_tc_prog(name: &str, insns: &[BpfInsn]) -> io::Result<OwnedFd> {
+    bpf_sys::prog_load(BPF_PROG_TYPE_SCHED_CLS, name, insns)
+}
+
+/// Loads an `SO_REUSEPORT` socket selector, for `ReuseportGroup::attach`.
+pub fn load_sk_reuseport_prog(name: &str, insns: &[BpfInsn]) -> io::Result<OwnedFd> {
+    bpf_sys::prog_load(BPF_PROG_TYPE_SK_REUSEPORT, name, insns)
+}
+
+/// Loads a socket lookup program, for `attach_sk_lookup`.
+pub fn load_sk_lookup_prog(name: &str, insns: &[BpfInsn]) -> io::Result<OwnedFd> {
+    bpf_sys::prog_load_for(BPF_PROG_TYPE_SK_LOOKUP, BPF_SK_LOOKUP, name, insns)
+}
+
+pub(crate) fn check_keys(traits: &[(TraitKey, TraitValue)]) -> io::Result<()> {
+    match traits.iter().find(|(key, _)| *key > MAX_KEY) {
+        Some((key, _)) => Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Key must be in 0..={} range, got {}", MAX_KEY, key),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// Builds and loads a socket filter setting the given traits on every
+/// packet. Needs a kernel with trait kfuncs and privileges to load programs.
+pub fn load_trait_stamp(traits: &[(TraitKey, TraitValue)]) -> io::Result<OwnedFd> {
+    check_keys(traits)?;
+
+    let trait_set_id = vmlinux_func_id(TRAIT_SET_KFUNC)?;
+    load_socket_filter("trait_stamp", &trait_stamp_insns(traits, trait_set_id))
+}
+
+/// Builds and loads a tc classifier setting the given traits on every
+/// packet, for `attach_tc`.
+pub fn load_trait_stamp_tc(traits: &[(TraitKey, TraitValue)]) -> io::Result<OwnedFd> {
+    check_keys(traits)?;
+
+    let trait_set_id = vmlinux_func_id(TRAIT_SET_KFUNC)?;
+    load_tc_prog(
+        "trait_stamp_tc",
+        &trait_stamp_tc_insns(traits, trait_set_id),
+    )
+}
+
+/// Builds and loads an XDP program writing a fixed trait record into the
+/// metadata area, for `attach_xdp`.
+pub fn load_xdp_meta_stamp(traits: &[(TraitKey, TraitValue)]) -> io::Result<OwnedFd> {
+    check_meta_traits(traits)?;
+
+    load_xdp_prog("xdp_meta_stamp", &xdp_meta_stamp_insns(traits))
+}
+
+/// Builds and loads the tc classifier converting metadata records into
+/// traits. See `MetaBridge`.
+pub fn load_meta_to_traits() -> io::Result<OwnedFd> {
+    let trait_set_id = vmlinux_func_id(TRAIT_SET_KFUNC)?;
+    load_tc_prog("meta_to_traits", &meta_to_traits_insns(trait_set_id))
+}
@@ -0,0 +1,62 @@
+use hyper::service::Service;
+use hyper::Request;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::sockopt::setsockopt;
+use crate::{SynTraits, TcpSaveSynTraits};
+
+/// Accepts TCP connections together with the traits saved from their SYN.
+pub struct SynTraitsAcceptor {
+    listener: TcpListener,
+}
+
+impl SynTraitsAcceptor {
+    /// Enables `TcpSaveSynTraits` on the listener. Connections which
+    /// completed the handshake before that carry no traits.
+    pub fn new(listener: TcpListener) -> io::Result<SynTraitsAcceptor> {
+        setsockopt(&listener, TcpSaveSynTraits, &true)?;
+
+        Ok(SynTraitsAcceptor { listener })
+    }
+
+    pub async fn accept(&self) -> io::Result<(TcpStream, SocketAddr, SynTraits)> {
+        let (stream, addr) = self.listener.accept().await?;
+        let traits = SynTraits::from_socket(&stream)?;
+
+        Ok((stream, addr, traits))
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+/// Service wrapper which inserts connection's `SynTraits` into extensions of
+/// every request served over it.
+#[derive(Clone, Debug)]
+pub struct WithSynTraits<S> {
+    inner: S,
+    traits: SynTraits,
+}
+
+impl<S> WithSynTraits<S> {
+    pub fn new(inner: S, traits: SynTraits) -> WithSynTraits<S> {
+        WithSynTraits { inner, traits }
+    }
+}
+
+impl<S, B> Service<Request<B>> for WithSynTraits<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn call(&self, mut req: Request<B>) -> Self::Future {
+        req.extensions_mut().insert(self.traits.clone());
+        self.inner.call(req)
+    }
+}
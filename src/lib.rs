@@ -1,13 +1,66 @@
-mod sockopt_ext;
+pub mod cbpf;
+pub mod ebpf;
+pub mod sockopt;
 
+mod bpf_sys;
+mod link;
 mod pkt_traits;
+mod probe;
+mod prog_test_run;
+mod reuseport;
+mod saved_syn;
+mod sk_lookup;
 mod so_attach_bpf;
+mod so_attach_filter;
 mod so_pkt_traits;
+mod syn_fingerprint;
+mod syn_info;
+mod tagger;
 mod tcp_syn_headers;
 mod tcp_syn_traits;
+mod tcx;
+mod trait_meta;
+mod trait_policy;
+mod trait_store;
+mod xdp;
 
+#[cfg(feature = "axum")]
+mod axum_ext;
+#[cfg(feature = "hyper")]
+mod hyper_ext;
+#[cfg(feature = "socket2")]
+mod socket2_ext;
+#[cfg(feature = "tonic")]
+mod tonic_ext;
+#[cfg(feature = "tower")]
+mod tower_ext;
+
+pub use link::*;
 pub use pkt_traits::*;
+pub use probe::*;
+pub use prog_test_run::*;
+pub use reuseport::*;
+pub use saved_syn::*;
+pub use sk_lookup::*;
 pub use so_attach_bpf::*;
+pub use so_attach_filter::*;
 pub use so_pkt_traits::*;
+pub use syn_fingerprint::*;
+pub use syn_info::*;
+pub use tagger::*;
 pub use tcp_syn_headers::*;
 pub use tcp_syn_traits::*;
+pub use tcx::*;
+pub use trait_meta::*;
+pub use trait_policy::*;
+pub use trait_store::*;
+pub use xdp::*;
+
+#[cfg(feature = "hyper")]
+pub use hyper_ext::*;
+#[cfg(feature = "socket2")]
+pub use socket2_ext::*;
+#[cfg(feature = "tonic")]
+pub use tonic_ext::*;
+#[cfg(feature = "tower")]
+pub use tower_ext::*;
@@ -0,0 +1,59 @@
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+use std::path::Path;
+
+use crate::bpf_sys;
+use crate::TcDirection;
+
+/// Where a link attaches its program.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum LinkTarget {
+    /// tcx hook of a network device, see `attach_tc`.
+    Tc { ifindex: u32, dir: TcDirection },
+    /// XDP hook of a network device, see `attach_xdp`.
+    Xdp { ifindex: u32 },
+    /// Socket lookup in a network namespace, see `attach_sk_lookup`.
+    SkLookup,
+}
+
+/// Program attached with a BPF link by `attach_tc`, `attach_xdp` or
+/// `attach_sk_lookup`. Detaches it on drop, unless the link is pinned.
+#[derive(Debug)]
+#[must_use = "program is detached when the link is dropped"]
+pub struct BpfLink {
+    link: OwnedFd,
+    target: LinkTarget,
+}
+
+impl BpfLink {
+    pub(crate) fn new(link: OwnedFd, target: LinkTarget) -> BpfLink {
+        BpfLink { link, target }
+    }
+
+    pub fn target(&self) -> LinkTarget {
+        self.target
+    }
+
+    /// Id of the attached program. For tc links, as listed by
+    /// `tc_prog_ids`.
+    pub fn prog_id(&self) -> io::Result<u32> {
+        Ok(bpf_sys::link_info(self.link.as_fd())?.prog_id)
+    }
+
+    /// Pins the link in bpffs. It then stays attached after drop, until the
+    /// pin is removed.
+    pub fn pin<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        bpf_sys::obj_pin(self.link.as_fd(), path.as_ref())
+    }
+
+    /// Detaches the program, also when the link is pinned.
+    pub fn detach(self) -> io::Result<()> {
+        bpf_sys::link_detach(self.link.as_fd())
+    }
+}
+
+impl AsFd for BpfLink {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.link.as_fd()
+    }
+}
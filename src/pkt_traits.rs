@@ -2,6 +2,8 @@
 use std::fmt;
 use std::mem;
 
+use crate::{Key, TraitKey, TraitVal, TraitWidth, MAX_KEY};
+
 use self::Error::*;
 
 #[repr(C)]
@@ -67,6 +69,7 @@ impl TryFrom<&[u8]> for PktTraitsHdr {
 }
 
 
+#[derive(Clone, Debug, PartialEq)]
 pub struct PktTraits {
     data: Vec<u8>,
 }
@@ -119,24 +122,42 @@ impl TryFrom<Vec<u8>> for PktTraits {
     }
 }
 
-type TraitKey = u8;
-
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum TraitValue {
     U16(u16),
     U32(u32),
     U64(u64),
 }
 
-impl PktTraits {
-    const MAX_KEY: TraitKey = (u64::BITS - 1) as u8;
+impl TraitValue {
+    pub fn width(&self) -> TraitWidth {
+        match self {
+            TraitValue::U16(_) => TraitWidth::U16,
+            TraitValue::U32(_) => TraitWidth::U32,
+            TraitValue::U64(_) => TraitWidth::U64,
+        }
+    }
+
+    pub fn to_u64(&self) -> u64 {
+        match *self {
+            TraitValue::U16(v) => v.into(),
+            TraitValue::U32(v) => v.into(),
+            TraitValue::U64(v) => v,
+        }
+    }
+
+    /// Converts to `T` if the widths match.
+    pub fn to_typed<T: TraitVal>(&self) -> Option<T> {
+        (self.width() == T::WIDTH).then(|| T::from_u64(self.to_u64()))
+    }
+}
 
+impl PktTraits {
     pub fn get(&self, key: TraitKey) -> Result<Option<TraitValue>, Error> {
-        if key > Self::MAX_KEY {
+        if key > MAX_KEY {
             return Err(KeyRange(format!(
-                "Key must be in 0..{} range, got {}",
-                Self::MAX_KEY,
-                key
+                "Key must be in 0..={} range, got {}",
+                MAX_KEY, key
             )));
         }
 
@@ -159,6 +180,11 @@ impl PktTraits {
         Ok(Some(val))
     }
 
+    /// Typed lookup. Yields `None` also if the value width doesn't match.
+    pub fn get_typed<T: TraitVal>(&self, key: Key<T>) -> Result<Option<T>, Error> {
+        Ok(self.get(key.key())?.and_then(|v| v.to_typed()))
+    }
+
     fn header(&self) -> PktTraitsHdr {
         let bytes: [u8; 16] = self.data[0..PktTraitsHdr::HEADER_SIZE].try_into().unwrap();
         PktTraitsHdr::from(bytes)
@@ -0,0 +1,250 @@
+//! Probing the running kernel for trait support.
+//!
+//! Trait socket options only exist on kernels from the `dev/skb-traits-uapi`
+//! branch. Upstream has since given some of their numbers to other options
+//! (`SO_RCVPRIORITY` is 82, `TCP_RTO_MAX_MS` is 44, `TCP_RTO_MIN_US` is 45),
+//! so a successful call proves nothing. Each probe checks for behavior only
+//! the trait option has.
+use libc::{c_int, c_void};
+use std::fmt;
+use std::io;
+use std::mem;
+use std::net::{Ipv4Addr, TcpListener, TcpStream, UdpSocket};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::time::Duration;
+
+use crate::ebpf::{self, TRAIT_SET_KFUNC};
+use crate::sockopt::{self, GetSockOpt, SetSockOpt};
+use crate::{
+    SoAttachBpf, TcpSynTraits, TraitValue, SCM_PKT_TRAITS, SO_RCV_PKT_TRAITS, TCP_SAVE_SYN_TRAITS,
+};
+
+/// Trait key the probe stamps on its datagram.
+const PROBE_KEY: u8 = 1;
+
+/// Piece of kernel trait support, as detected by [`probe`].
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Feature {
+    /// `SO_RCV_PKT_TRAITS` socket option.
+    RcvPktTraits,
+    /// Traits delivered in `SO_PKT_TRAITS` control messages.
+    PktTraits,
+    /// `TCP_SAVE_SYN_TRAITS` socket option.
+    TcpSaveSynTraits,
+    /// `TCP_SYN_TRAITS` socket option.
+    TcpSynTraits,
+    /// `bpf_skb_trait_set` kfunc in vmlinux BTF.
+    TraitSetKfunc,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 5] = [
+        Feature::RcvPktTraits,
+        Feature::PktTraits,
+        Feature::TcpSaveSynTraits,
+        Feature::TcpSynTraits,
+        Feature::TraitSetKfunc,
+    ];
+
+    /// Name of the kernel interface, e.g. `SO_RCV_PKT_TRAITS`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Feature::RcvPktTraits => "SO_RCV_PKT_TRAITS",
+            Feature::PktTraits => "SO_PKT_TRAITS",
+            Feature::TcpSaveSynTraits => "TCP_SAVE_SYN_TRAITS",
+            Feature::TcpSynTraits => "TCP_SYN_TRAITS",
+            Feature::TraitSetKfunc => TRAIT_SET_KFUNC,
+        }
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Kernel trait support, as reported by [`probe`].
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Features {
+    pub rcv_pkt_traits: bool,
+    pub pkt_traits: bool,
+    pub tcp_save_syn_traits: bool,
+    pub tcp_syn_traits: bool,
+    pub trait_set_kfunc: bool,
+}
+
+impl Features {
+    pub fn has(&self, feature: Feature) -> bool {
+        match feature {
+            Feature::RcvPktTraits => self.rcv_pkt_traits,
+            Feature::PktTraits => self.pkt_traits,
+            Feature::TcpSaveSynTraits => self.tcp_save_syn_traits,
+            Feature::TcpSynTraits => self.tcp_syn_traits,
+            Feature::TraitSetKfunc => self.trait_set_kfunc,
+        }
+    }
+
+    /// True if the kernel supports all features.
+    pub fn all(&self) -> bool {
+        Feature::ALL.iter().all(|f| self.has(*f))
+    }
+
+    /// Lists features the kernel lacks.
+    pub fn missing(&self) -> Vec<Feature> {
+        Feature::ALL.into_iter().filter(|f| !self.has(*f)).collect()
+    }
+}
+
+/// One feature per line, e.g. `SO_RCV_PKT_TRAITS: yes`.
+impl fmt::Display for Features {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for feature in Feature::ALL {
+            let yes_no = if self.has(feature) { "yes" } else { "no" };
+            writeln!(f, "{}: {}", feature, yes_no)?;
+        }
+        Ok(())
+    }
+}
+
+/// Detects which trait features the running kernel supports.
+///
+/// Probes open sockets on loopback in the network namespace of the calling
+/// thread, so loopback has to be up. Checking for `SO_PKT_TRAITS` control
+/// messages loads a socket filter calling the kfunc, which needs privileges
+/// to load BPF programs; `pkt_traits` is reported false if it fails to load.
+/// Other errors than the kernel not knowing an option are returned.
+pub fn probe() -> io::Result<Features> {
+    let trait_set_kfunc = probe_kfunc()?;
+    let (rcv_pkt_traits, pkt_traits) = probe_udp(trait_set_kfunc)?;
+    let (tcp_save_syn_traits, tcp_syn_traits) = probe_tcp()?;
+
+    Ok(Features {
+        rcv_pkt_traits,
+        pkt_traits,
+        tcp_save_syn_traits,
+        tcp_syn_traits,
+        trait_set_kfunc,
+    })
+}
+
+fn unknown_option(err: &io::Error) -> bool {
+    matches!(
+        err.raw_os_error(),
+        Some(libc::ENOPROTOOPT) | Some(libc::EOPNOTSUPP)
+    )
+}
+
+fn probe_kfunc() -> io::Result<bool> {
+    // Kernels without BTF have no vmlinux file, and no kfuncs either
+    match ebpf::vmlinux_func_id(TRAIT_SET_KFUNC) {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+/// Checks that an option behaves as a boolean flag: off on a new socket,
+/// then on once set.
+fn probe_flag(fd: BorrowedFd<'_>, level: c_int, name: c_int) -> io::Result<bool> {
+    match sockopt::get_int(fd, level, name) {
+        Ok(0) => {}
+        Ok(_) => return Ok(false),
+        Err(err) if unknown_option(&err) => return Ok(false),
+        Err(err) => return Err(err),
+    }
+    match sockopt::set_int(fd, level, name, 1) {
+        Ok(()) => {}
+        Err(err) if unknown_option(&err) || err.raw_os_error() == Some(libc::EINVAL) => {
+            return Ok(false)
+        }
+        Err(err) => return Err(err),
+    }
+
+    Ok(sockopt::get_int(fd, level, name)? == 1)
+}
+
+fn probe_udp(trait_set_kfunc: bool) -> io::Result<(bool, bool)> {
+    let sock = UdpSocket::bind((Ipv4Addr::LOCALHOST, 0))?;
+    sock.set_read_timeout(Some(Duration::from_secs(1)))?;
+
+    if !probe_flag(sock.as_fd(), libc::SOL_SOCKET, SO_RCV_PKT_TRAITS)? {
+        return Ok((false, false));
+    }
+
+    // SO_RCVPRIORITY is a flag too, but reports skb priority in SO_PRIORITY
+    // control messages
+    sockopt::set_int(sock.as_fd(), libc::SOL_SOCKET, libc::SO_PRIORITY, 1)?;
+
+    // Without a stamp nothing sets traits, so none can be seen delivered.
+    // Loading it fails without privileges or if the verifier refuses it.
+    let prog = match trait_set_kfunc {
+        true => ebpf::load_trait_stamp(&[(PROBE_KEY, TraitValue::U32(1))]).ok(),
+        false => None,
+    };
+    if let Some(prog) = &prog {
+        SoAttachBpf::new().set(&sock, &prog.as_fd())?;
+    }
+
+    sock.send_to(b"x", sock.local_addr()?)?;
+    let types = recv_cmsg_types(&sock)?;
+
+    let rcv_pkt_traits = !types.contains(&libc::SO_PRIORITY);
+    let pkt_traits = prog.is_some() && rcv_pkt_traits && types.contains(&SCM_PKT_TRAITS);
+
+    Ok((rcv_pkt_traits, pkt_traits))
+}
+
+/// Receives a datagram and lists the types of `SOL_SOCKET` control messages
+/// it came with.
+fn recv_cmsg_types(sock: &UdpSocket) -> io::Result<Vec<c_int>> {
+    let mut buf = [0u8; 1];
+    let mut iov = libc::iovec {
+        iov_base: buf.as_mut_ptr() as *mut c_void,
+        iov_len: buf.len(),
+    };
+    // u64 elements keep the buffer aligned for cmsghdr
+    let mut cbuf = [0u64; 128];
+    let mut msg: libc::msghdr = unsafe { mem::zeroed() };
+    msg.msg_iov = &mut iov;
+    msg.msg_iovlen = 1;
+    msg.msg_control = cbuf.as_mut_ptr() as *mut c_void;
+    msg.msg_controllen = mem::size_of_val(&cbuf) as _;
+
+    let res = unsafe { libc::recvmsg(sock.as_raw_fd(), &mut msg, 0) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let mut types = vec![];
+    let mut cmsg = unsafe { libc::CMSG_FIRSTHDR(&msg) };
+    while let Some(hdr) = unsafe { cmsg.as_ref() } {
+        if hdr.cmsg_level == libc::SOL_SOCKET {
+            types.push(hdr.cmsg_type);
+        }
+        cmsg = unsafe { libc::CMSG_NXTHDR(&msg, cmsg) };
+    }
+
+    Ok(types)
+}
+
+fn probe_tcp() -> io::Result<(bool, bool)> {
+    let ln = TcpListener::bind((Ipv4Addr::LOCALHOST, 0))?;
+
+    let tcp_save_syn_traits = probe_flag(ln.as_fd(), libc::SOL_TCP, TCP_SAVE_SYN_TRAITS)?;
+    if tcp_save_syn_traits {
+        sockopt::set_int(ln.as_fd(), libc::SOL_TCP, TCP_SAVE_SYN_TRAITS, 0)?;
+    }
+
+    // With nothing saved the kernel reports zero length. TCP_RTO_MIN_US
+    // reports an int, which reads as EMSGSIZE.
+    let _client = TcpStream::connect(ln.local_addr()?)?;
+    let (peer, _) = ln.accept()?;
+    let tcp_syn_traits = match TcpSynTraits(&[PROBE_KEY]).get(&peer) {
+        Ok(_) => true,
+        Err(err) if unknown_option(&err) || err.raw_os_error() == Some(libc::EMSGSIZE) => false,
+        Err(err) => return Err(err),
+    };
+
+    Ok((tcp_save_syn_traits, tcp_syn_traits))
+}
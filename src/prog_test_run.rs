@@ -0,0 +1,139 @@
+//! Running BPF programs against synthetic packets with `BPF_PROG_TEST_RUN`
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::time::Duration;
+
+use crate::bpf_sys::{self, TestRunAttr, BPF_PROG_TYPE_XDP};
+use crate::{decode_trait_meta, TraitKey, TraitValue};
+
+/// Room left for the program to grow the packet or prepend metadata.
+const OUT_HEADROOM: usize = 4096;
+
+/// `struct xdp_md`, the context of XDP programs.
+#[repr(C)]
+#[derive(Default)]
+struct XdpMd {
+    data: u32,
+    data_end: u32,
+    data_meta: u32,
+    ingress_ifindex: u32,
+    rx_queue_index: u32,
+    egress_ifindex: u32,
+}
+
+/// Runs a program in the kernel on a packet supplied from userspace, without
+/// attaching it anywhere. Needs the same privileges as loading the program.
+///
+/// Packets start with an Ethernet header. Socket filters see them from the
+/// network header on, as on a packet socket, whereas on a TCP or UDP socket
+/// they would see them from the transport header on.
+///
+/// Test runs on an skb hand back neither the skb nor its traits, so traits
+/// set by socket filters can't be read back. Check those on a socket with
+/// the filter attached, in `SCM_PKT_TRAITS` control messages.
+#[derive(Clone, Debug)]
+pub struct ProgTestRun<'fd> {
+    prog: BorrowedFd<'fd>,
+    data: Vec<u8>,
+    repeat: u32,
+}
+
+/// Outcome of a test run.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TestRunOutput {
+    /// Program return value, e.g. bytes to keep for socket filters.
+    pub retval: u32,
+    /// Packet as left by the program.
+    pub data: Vec<u8>,
+    /// Metadata area in front of the packet.
+    ///
+    /// Only XDP test runs hand the metadata area back. For programs running
+    /// on an skb, socket filters included, the kernel returns just the
+    /// packet and this is always empty.
+    pub meta: Vec<u8>,
+    /// Average run time per repetition.
+    pub duration: Duration,
+    is_xdp: bool,
+}
+
+impl TestRunOutput {
+    /// Traits an XDP program handed on in a trait record, see
+    /// `encode_trait_meta`. Yields `None` if the metadata area holds no
+    /// record.
+    ///
+    /// Fails with `Unsupported` for programs running on an skb, as the
+    /// kernel doesn't return the traits they set.
+    pub fn traits(&self) -> io::Result<Option<Vec<(TraitKey, TraitValue)>>> {
+        if !self.is_xdp {
+            return Err(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "Test runs on an skb don't return traits",
+            ));
+        }
+
+        Ok(decode_trait_meta(&self.meta))
+    }
+}
+
+impl<'fd> ProgTestRun<'fd> {
+    pub fn new(prog: BorrowedFd<'fd>) -> ProgTestRun<'fd> {
+        ProgTestRun {
+            prog,
+            data: vec![],
+            repeat: 1,
+        }
+    }
+
+    pub fn data(mut self, data: &[u8]) -> ProgTestRun<'fd> {
+        self.data = data.to_vec();
+        self
+    }
+
+    /// Runs the program this many times. Useful for benchmarking.
+    pub fn repeat(mut self, repeat: u32) -> ProgTestRun<'fd> {
+        self.repeat = repeat;
+        self
+    }
+
+    pub fn run(&self) -> io::Result<TestRunOutput> {
+        let is_xdp = bpf_sys::prog_info(self.prog)?.prog_type == BPF_PROG_TYPE_XDP;
+
+        let mut out = vec![0u8; self.data.len() + OUT_HEADROOM];
+        let mut ctx_in = XdpMd {
+            data_end: self.data.len() as u32,
+            ..Default::default()
+        };
+        let mut ctx_out = XdpMd::default();
+
+        let mut attr = TestRunAttr {
+            prog_fd: self.prog.as_raw_fd() as u32,
+            data_size_in: self.data.len() as u32,
+            data_size_out: out.len() as u32,
+            data_in: self.data.as_ptr() as u64,
+            data_out: out.as_mut_ptr() as u64,
+            repeat: self.repeat,
+            ..Default::default()
+        };
+        // XDP test runs report where metadata ends only through the context
+        if is_xdp {
+            attr.ctx_size_in = mem::size_of::<XdpMd>() as u32;
+            attr.ctx_in = &mut ctx_in as *mut XdpMd as u64;
+            attr.ctx_size_out = mem::size_of::<XdpMd>() as u32;
+            attr.ctx_out = &mut ctx_out as *mut XdpMd as u64;
+        }
+        bpf_sys::prog_test_run(&mut attr)?;
+
+        out.truncate(attr.data_size_out as usize);
+        let meta_len = (ctx_out.data - ctx_out.data_meta) as usize;
+        let data = out.split_off(meta_len.min(out.len()));
+
+        Ok(TestRunOutput {
+            retval: attr.retval,
+            data,
+            meta: out,
+            duration: Duration::from_nanos(attr.duration.into()),
+            is_xdp,
+        })
+    }
+}
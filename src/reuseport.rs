@@ -0,0 +1,327 @@
+//! Steering packets within an `SO_REUSEPORT` group with BPF.
+//!
+//! Sockets sharing a port with `SO_REUSEPORT` form a group. An
+//! `sk_reuseport` program attached to any of them picks the socket for
+//! each packet from a `REUSEPORT_SOCKARRAY` map. `bpf/reuseport.bpf.c`
+//! picks it by trait value, so a tc program at ingress decides the shard.
+//! Its `record_shard_trait` notes the value by flow, attach it with
+//! `attach_tc` after the program setting the trait, and attach
+//! `reuseport_by_trait` with [`ReuseportGroup::attach`].
+use libc::{c_int, sockaddr_storage, socklen_t};
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::net::{SocketAddr, TcpListener, UdpSocket};
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+use std::path::Path;
+
+use crate::bpf_sys::{
+    self, check_map, invalid, BPF_MAP_TYPE_REUSEPORT_SOCKARRAY, BPF_PROG_TYPE_SK_REUSEPORT,
+};
+use crate::sockopt::{self, bool_sockopt, nix_getsockopt, nix_setsockopt};
+
+pub use libc::{SO_ATTACH_REUSEPORT_EBPF, SO_COOKIE, SO_DETACH_REUSEPORT_BPF};
+
+/// Name of the socket array in `bpf/reuseport.bpf.c`.
+pub const REUSEPORT_SOCKS_MAP: &str = "reuseport_socks";
+/// Name of the map of slots by flow in `bpf/reuseport.bpf.c`, shared by its
+/// two programs.
+pub const REUSEPORT_FLOWS_MAP: &str = "reuseport_flows";
+
+bool_sockopt!(
+    /// Lets sockets of the same user share a port. Must be set before bind.
+    SoReusePort,
+    libc::SOL_SOCKET,
+    libc::SO_REUSEPORT
+);
+
+/// Attaches an `sk_reuseport` program to the reuseport group of the socket.
+/// The socket must be bound. Fails with `EINVAL` for programs of other
+/// types.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SoAttachReuseportEbpf<'fd>(PhantomData<BorrowedFd<'fd>>);
+
+impl SoAttachReuseportEbpf<'_> {
+    pub const fn new() -> Self {
+        SoAttachReuseportEbpf(PhantomData)
+    }
+}
+
+impl<'fd> sockopt::SetSockOpt for SoAttachReuseportEbpf<'fd> {
+    type Val = BorrowedFd<'fd>;
+
+    fn set<F: AsFd>(&self, fd: &F, prog: &BorrowedFd<'fd>) -> io::Result<()> {
+        sockopt::set_int(
+            fd.as_fd(),
+            libc::SOL_SOCKET,
+            SO_ATTACH_REUSEPORT_EBPF,
+            prog.as_raw_fd(),
+        )
+    }
+}
+
+nix_setsockopt!(SoAttachReuseportEbpf<'_>);
+
+/// Detaches the program from the reuseport group of the socket. Fails with
+/// `ENOENT` if none is attached.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SoDetachReuseportBpf;
+
+impl sockopt::SetSockOpt for SoDetachReuseportBpf {
+    type Val = ();
+
+    fn set<F: AsFd>(&self, fd: &F, _: &()) -> io::Result<()> {
+        // Kernel ignores the value but insists on an int-sized one
+        sockopt::set_int(fd.as_fd(), libc::SOL_SOCKET, SO_DETACH_REUSEPORT_BPF, 0)
+    }
+}
+
+nix_setsockopt!(SoDetachReuseportBpf);
+
+/// Socket cookie, the id BPF programs and socket arrays know a socket by.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SoCookie;
+
+impl sockopt::GetSockOpt for SoCookie {
+    type Val = u64;
+
+    fn get<F: AsFd>(&self, fd: &F) -> io::Result<u64> {
+        let mut cookie = 0u64;
+        let mut len = mem::size_of::<u64>();
+        sockopt::get_raw(
+            fd.as_fd(),
+            libc::SOL_SOCKET,
+            SO_COOKIE,
+            (&mut cookie as *mut u64).cast(),
+            &mut len,
+        )?;
+
+        Ok(cookie)
+    }
+}
+
+nix_getsockopt!(SoCookie);
+
+/// `REUSEPORT_SOCKARRAY` map, socket slots indexed from zero.
+#[derive(Debug)]
+pub struct ReuseportSockArray {
+    map: OwnedFd,
+    max_entries: u32,
+}
+
+impl ReuseportSockArray {
+    /// Takes the socket array of a loaded object. The file descriptor is
+    /// duplicated.
+    pub fn new(map: BorrowedFd<'_>) -> io::Result<ReuseportSockArray> {
+        // 8-byte values so that lookups yield socket cookies
+        let info = check_map(
+            map,
+            REUSEPORT_SOCKS_MAP,
+            BPF_MAP_TYPE_REUSEPORT_SOCKARRAY,
+            mem::size_of::<u32>(),
+            mem::size_of::<u64>(),
+        )?;
+
+        Ok(ReuseportSockArray {
+            map: map.try_clone_to_owned()?,
+            max_entries: info.max_entries,
+        })
+    }
+
+    /// Creates an empty array, to be reused when loading the program.
+    pub fn create(max_entries: u32) -> io::Result<ReuseportSockArray> {
+        // 8-byte values so that lookups yield socket cookies
+        let map = bpf_sys::map_create(
+            BPF_MAP_TYPE_REUSEPORT_SOCKARRAY,
+            mem::size_of::<u32>(),
+            mem::size_of::<u64>(),
+            max_entries,
+            0,
+        )?;
+
+        Ok(ReuseportSockArray { map, max_entries })
+    }
+
+    /// Opens an array pinned in bpffs.
+    pub fn open_pinned<P: AsRef<Path>>(path: P) -> io::Result<ReuseportSockArray> {
+        let map = bpf_sys::obj_get(path.as_ref())?;
+
+        ReuseportSockArray::new(map.as_fd())
+    }
+
+    pub fn map(&self) -> BorrowedFd<'_> {
+        self.map.as_fd()
+    }
+
+    pub fn max_entries(&self) -> u32 {
+        self.max_entries
+    }
+
+    /// Puts a socket into a slot, replacing any previous one. Sockets must
+    /// be bound with `SO_REUSEPORT`, and listening if TCP. All sockets in
+    /// the array have to belong to the same reuseport group.
+    pub fn insert<S: AsFd>(&self, slot: u32, sock: &S) -> io::Result<()> {
+        let fd = sock.as_fd().as_raw_fd() as u64;
+
+        bpf_sys::map_update(self.map.as_fd(), &slot, &fd)
+    }
+
+    /// Empties a slot. Yields `false` if it was empty already.
+    pub fn remove(&self, slot: u32) -> io::Result<bool> {
+        bpf_sys::map_delete(self.map.as_fd(), &slot)
+    }
+
+    /// Cookie of the socket in a slot, see [`SoCookie`].
+    pub fn cookie(&self, slot: u32) -> io::Result<Option<u64>> {
+        let mut cookie = 0u64;
+
+        match bpf_sys::map_lookup(self.map.as_fd(), &slot, &mut cookie)? {
+            true => Ok(Some(cookie)),
+            false => Ok(None),
+        }
+    }
+}
+
+/// Sockets bound to one address with `SO_REUSEPORT`, one per slot of a
+/// socket array. The socket in slot N is `sockets()[N]`.
+#[derive(Debug)]
+pub struct ReuseportGroup<S> {
+    socks: Vec<S>,
+    array: ReuseportSockArray,
+}
+
+impl ReuseportGroup<UdpSocket> {
+    /// Binds a UDP socket for every slot of the array and puts it there.
+    /// Port zero picks a free port shared by the whole group.
+    pub fn bind_udp(
+        addr: SocketAddr,
+        array: ReuseportSockArray,
+    ) -> io::Result<ReuseportGroup<UdpSocket>> {
+        ReuseportGroup::bind(addr, array, libc::SOCK_DGRAM, UdpSocket::from)
+    }
+}
+
+impl ReuseportGroup<TcpListener> {
+    /// Same as [`ReuseportGroup::bind_udp`] but for TCP listeners.
+    pub fn bind_tcp(
+        addr: SocketAddr,
+        array: ReuseportSockArray,
+    ) -> io::Result<ReuseportGroup<TcpListener>> {
+        ReuseportGroup::bind(addr, array, libc::SOCK_STREAM, TcpListener::from)
+    }
+}
+
+impl<S: AsFd> ReuseportGroup<S> {
+    fn bind(
+        mut addr: SocketAddr,
+        array: ReuseportSockArray,
+        ty: c_int,
+        wrap: fn(OwnedFd) -> S,
+    ) -> io::Result<ReuseportGroup<S>> {
+        if array.max_entries() == 0 {
+            return Err(invalid("Socket array has no slots".to_string()));
+        }
+
+        let mut socks = Vec::with_capacity(array.max_entries() as usize);
+        for slot in 0..array.max_entries() {
+            let sock = reuseport_socket(addr, ty)?;
+            // Rest of the group joins on the port the first one got
+            addr = local_addr(sock.as_fd())?;
+            array.insert(slot, &sock)?;
+            socks.push(wrap(sock));
+        }
+
+        Ok(ReuseportGroup { socks, array })
+    }
+
+    pub fn sockets(&self) -> &[S] {
+        &self.socks
+    }
+
+    pub fn array(&self) -> &ReuseportSockArray {
+        &self.array
+    }
+
+    /// Address the group is bound to.
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        local_addr(self.socks[0].as_fd())
+    }
+
+    /// Attaches a socket selector to the group, replacing the current one.
+    /// Fails with `InvalidInput` if it's not an `sk_reuseport` program.
+    pub fn attach<P: AsFd>(&self, prog: P) -> io::Result<()> {
+        let info = bpf_sys::prog_info(prog.as_fd())?;
+        if info.prog_type != BPF_PROG_TYPE_SK_REUSEPORT {
+            return Err(invalid(format!(
+                "BPF program type {} can't select reuseport sockets, expected sk_reuseport ({})",
+                info.prog_type, BPF_PROG_TYPE_SK_REUSEPORT
+            )));
+        }
+
+        sockopt::setsockopt(&self.socks[0], SoAttachReuseportEbpf::new(), &prog.as_fd())
+    }
+
+    /// Goes back to the kernel picking sockets by flow hash.
+    pub fn detach(&self) -> io::Result<()> {
+        sockopt::setsockopt(&self.socks[0], SoDetachReuseportBpf, &())
+    }
+}
+
+fn reuseport_socket(addr: SocketAddr, ty: c_int) -> io::Result<OwnedFd> {
+    let family = match addr {
+        SocketAddr::V4(_) => libc::AF_INET,
+        SocketAddr::V6(_) => libc::AF_INET6,
+    };
+    let fd = unsafe { libc::socket(family, ty | libc::SOCK_CLOEXEC, 0) };
+    if fd == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+
+    sockopt::setsockopt(&sock, SoReusePort, &true)?;
+
+    let (sa, len) = to_sockaddr(addr);
+    let res = unsafe { libc::bind(fd, (&sa as *const sockaddr_storage).cast(), len) };
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    if ty == libc::SOCK_STREAM && unsafe { libc::listen(fd, libc::SOMAXCONN) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(sock)
+}
+
+fn to_sockaddr(addr: SocketAddr) -> (sockaddr_storage, socklen_t) {
+    let mut ss: sockaddr_storage = unsafe { mem::zeroed() };
+
+    let len = match addr {
+        SocketAddr::V4(a) => {
+            let sin =
+                unsafe { &mut *(&mut ss as *mut sockaddr_storage).cast::<libc::sockaddr_in>() };
+            sin.sin_family = libc::AF_INET as libc::sa_family_t;
+            sin.sin_port = a.port().to_be();
+            sin.sin_addr.s_addr = u32::from_ne_bytes(a.ip().octets());
+            mem::size_of::<libc::sockaddr_in>()
+        }
+        SocketAddr::V6(a) => {
+            let sin6 =
+                unsafe { &mut *(&mut ss as *mut sockaddr_storage).cast::<libc::sockaddr_in6>() };
+            sin6.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            sin6.sin6_port = a.port().to_be();
+            sin6.sin6_addr.s6_addr = a.ip().octets();
+            sin6.sin6_flowinfo = a.flowinfo();
+            sin6.sin6_scope_id = a.scope_id();
+            mem::size_of::<libc::sockaddr_in6>()
+        }
+    };
+
+    (ss, len as socklen_t)
+}
+
+fn local_addr(fd: BorrowedFd<'_>) -> io::Result<SocketAddr> {
+    // Borrow the fd as a std socket just to ask for its address
+    let sock = mem::ManuallyDrop::new(unsafe { UdpSocket::from_raw_fd(fd.as_raw_fd()) });
+
+    sock.local_addr()
+}
@@ -0,0 +1,521 @@
+/// Decoder for IP and TCP headers returned by `TcpSavedSyn`
+use std::fmt;
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use self::SavedSynError::*;
+
+const ETH_HDR_LEN: usize = 14;
+const ETH_P_IP: u16 = 0x0800;
+const ETH_P_IPV6: u16 = 0x86dd;
+const ETH_P_8021Q: u16 = 0x8100;
+const ETH_P_8021AD: u16 = 0x88a8;
+const VLAN_TAG_LEN: usize = 4;
+const IPPROTO_TCP: u8 = 6;
+const IPV4_MIN_HDR_LEN: usize = 20;
+const IPV6_HDR_LEN: usize = 40;
+const TCP_MIN_HDR_LEN: usize = 20;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SavedSynError {
+    /// Buffer ends before the named header or field does.
+    Truncated {
+        what: &'static str,
+        need: usize,
+        have: usize,
+    },
+    UnsupportedIpVersion(u8),
+    /// Link-layer header doesn't carry IPv4 or IPv6.
+    UnsupportedEtherType(u16),
+    /// Header length field is smaller than the fixed part of the header.
+    BadHeaderLength {
+        what: &'static str,
+        len: usize,
+    },
+    /// Transport protocol following the IP headers is not TCP.
+    NotTcp(u8),
+    /// TCP option length is invalid for its kind or overruns the header.
+    BadOptionLength {
+        kind: u8,
+        len: usize,
+    },
+    /// Bytes left over after the TCP header.
+    TrailingBytes(usize),
+}
+
+impl std::error::Error for SavedSynError {}
+
+impl fmt::Display for SavedSynError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Truncated { what, need, have } => {
+                write!(f, "Truncated {}: need {} bytes, have {}", what, need, have)
+            }
+            UnsupportedIpVersion(v) => write!(f, "Unsupported IP version {}", v),
+            UnsupportedEtherType(t) => write!(f, "Unsupported EtherType {:#06x}", t),
+            BadHeaderLength { what, len } => write!(f, "Bad {} length {}", what, len),
+            NotTcp(proto) => write!(f, "Expected TCP, got protocol {}", proto),
+            BadOptionLength { kind, len } => {
+                write!(f, "Bad length {} for TCP option kind {}", len, kind)
+            }
+            TrailingBytes(n) => write!(f, "{} bytes past the TCP header", n),
+        }
+    }
+}
+
+/// 802.1Q or 802.1ad tag.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct VlanTag {
+    pub tpid: u16,
+    pub tci: u16,
+}
+
+impl VlanTag {
+    pub fn vid(&self) -> u16 {
+        self.tci & 0x0fff
+    }
+
+    pub fn pcp(&self) -> u8 {
+        (self.tci >> 13) as u8
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct EthHeader {
+    pub dst: [u8; 6],
+    pub src: [u8; 6],
+    /// VLAN tags, outermost first.
+    pub vlans: Vec<VlanTag>,
+    pub ethertype: u16,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ipv4Header {
+    pub tos: u8,
+    pub total_len: u16,
+    pub id: u16,
+    /// Flags in the top 3 bits, fragment offset in the rest.
+    pub flags_frag_off: u16,
+    pub ttl: u8,
+    pub protocol: u8,
+    pub checksum: u16,
+    pub src: Ipv4Addr,
+    pub dst: Ipv4Addr,
+    pub options: Vec<u8>,
+}
+
+impl Ipv4Header {
+    const DF: u16 = 0x4000;
+
+    pub fn dont_fragment(&self) -> bool {
+        self.flags_frag_off & Self::DF != 0
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ipv6ExtHeader {
+    /// Type of this header, as given by the preceding next header field.
+    pub kind: u8,
+    /// Header contents past the next header field.
+    pub data: Vec<u8>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Ipv6Header {
+    pub traffic_class: u8,
+    pub flow_label: u32,
+    pub payload_len: u16,
+    pub next_header: u8,
+    pub hop_limit: u8,
+    pub src: Ipv6Addr,
+    pub dst: Ipv6Addr,
+    pub ext_headers: Vec<Ipv6ExtHeader>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IpHeader {
+    V4(Ipv4Header),
+    V6(Ipv6Header),
+}
+
+impl IpHeader {
+    /// TTL or hop limit.
+    pub fn ttl(&self) -> u8 {
+        match self {
+            IpHeader::V4(h) => h.ttl,
+            IpHeader::V6(h) => h.hop_limit,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TcpHeader {
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub seq: u32,
+    pub ack: u32,
+    /// Header length in bytes, including options.
+    pub header_len: usize,
+    /// Flags, including the reserved bits, in the low 12 bits.
+    pub flags: u16,
+    pub window: u16,
+    pub checksum: u16,
+    pub urgent_ptr: u16,
+}
+
+impl TcpHeader {
+    pub const FIN: u16 = 0x001;
+    pub const SYN: u16 = 0x002;
+    pub const RST: u16 = 0x004;
+    pub const PSH: u16 = 0x008;
+    pub const ACK: u16 = 0x010;
+    pub const URG: u16 = 0x020;
+    pub const ECE: u16 = 0x040;
+    pub const CWR: u16 = 0x080;
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TcpOption {
+    Eol,
+    Nop,
+    Mss(u16),
+    WindowScale(u8),
+    SackPermitted,
+    Sack(Vec<(u32, u32)>),
+    Timestamps {
+        val: u32,
+        ecr: u32,
+    },
+    /// TCP Fast Open cookie, empty when the client requests one.
+    FastOpen(Vec<u8>),
+    /// Fast Open cookie in the experimental option, kind 254 with magic
+    /// 0xf989, as sent by older stacks.
+    FastOpenExp(Vec<u8>),
+    Unknown {
+        kind: u8,
+        data: Vec<u8>,
+    },
+}
+
+impl TcpOption {
+    pub fn kind(&self) -> u8 {
+        match self {
+            TcpOption::Eol => 0,
+            TcpOption::Nop => 1,
+            TcpOption::Mss(_) => 2,
+            TcpOption::WindowScale(_) => 3,
+            TcpOption::SackPermitted => 4,
+            TcpOption::Sack(_) => 5,
+            TcpOption::Timestamps { .. } => 8,
+            TcpOption::FastOpen(_) => 34,
+            TcpOption::FastOpenExp(_) => 254,
+            TcpOption::Unknown { kind, .. } => *kind,
+        }
+    }
+}
+
+/// Headers of a saved SYN packet, decoded.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SavedSyn {
+    /// Present only when saved with `SaveSyn::WithMac`.
+    pub eth: Option<EthHeader>,
+    pub ip: IpHeader,
+    pub tcp: TcpHeader,
+    /// TCP options in the order they appear on the wire.
+    pub options: Vec<TcpOption>,
+}
+
+impl SavedSyn {
+    /// Parses headers saved with `SaveSyn::NetworkAndTransport`.
+    pub fn parse(buf: &[u8]) -> Result<SavedSyn, SavedSynError> {
+        let version = buf.first().ok_or(Truncated {
+            what: "IP header",
+            need: 1,
+            have: 0,
+        })? >> 4;
+
+        let (ip, proto, off) = match version {
+            4 => parse_ipv4(buf)?,
+            6 => parse_ipv6(buf)?,
+            v => return Err(UnsupportedIpVersion(v)),
+        };
+        if proto != IPPROTO_TCP {
+            return Err(NotTcp(proto));
+        }
+
+        let (tcp, options) = parse_tcp(&buf[off..])?;
+        let end = off + tcp.header_len;
+        if end != buf.len() {
+            return Err(TrailingBytes(buf.len() - end));
+        }
+
+        Ok(SavedSyn {
+            eth: None,
+            ip,
+            tcp,
+            options,
+        })
+    }
+
+    /// Parses headers saved with `SaveSyn::WithMac`, where an Ethernet header
+    /// precedes the IP header.
+    pub fn parse_with_mac(buf: &[u8]) -> Result<SavedSyn, SavedSynError> {
+        let (eth, off) = parse_eth(buf)?;
+
+        let want = match eth.ethertype {
+            ETH_P_IP => 4,
+            ETH_P_IPV6 => 6,
+            t => return Err(UnsupportedEtherType(t)),
+        };
+        let got = buf.get(off).map_or(want, |b| b >> 4);
+        if got != want {
+            return Err(UnsupportedIpVersion(got));
+        }
+
+        let syn = SavedSyn::parse(&buf[off..])?;
+        Ok(SavedSyn {
+            eth: Some(eth),
+            ..syn
+        })
+    }
+
+    pub fn mss(&self) -> Option<u16> {
+        self.options.iter().find_map(|o| match o {
+            TcpOption::Mss(mss) => Some(*mss),
+            _ => None,
+        })
+    }
+
+    pub fn window_scale(&self) -> Option<u8> {
+        self.options.iter().find_map(|o| match o {
+            TcpOption::WindowScale(ws) => Some(*ws),
+            _ => None,
+        })
+    }
+
+    pub fn sack_permitted(&self) -> bool {
+        self.options.contains(&TcpOption::SackPermitted)
+    }
+
+    pub fn timestamps(&self) -> Option<(u32, u32)> {
+        self.options.iter().find_map(|o| match o {
+            TcpOption::Timestamps { val, ecr } => Some((*val, *ecr)),
+            _ => None,
+        })
+    }
+
+    pub fn fast_open_cookie(&self) -> Option<&[u8]> {
+        self.options.iter().find_map(|o| match o {
+            TcpOption::FastOpen(cookie) | TcpOption::FastOpenExp(cookie) => Some(&cookie[..]),
+            _ => None,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for SavedSyn {
+    type Error = SavedSynError;
+
+    fn try_from(buf: &[u8]) -> Result<SavedSyn, Self::Error> {
+        SavedSyn::parse(buf)
+    }
+}
+
+fn need(what: &'static str, buf: &[u8], need: usize) -> Result<(), SavedSynError> {
+    if buf.len() < need {
+        return Err(Truncated {
+            what,
+            need,
+            have: buf.len(),
+        });
+    }
+    Ok(())
+}
+
+fn be16(buf: &[u8], off: usize) -> u16 {
+    u16::from_be_bytes([buf[off], buf[off + 1]])
+}
+
+fn be32(buf: &[u8], off: usize) -> u32 {
+    u32::from_be_bytes(buf[off..off + 4].try_into().unwrap())
+}
+
+fn parse_eth(buf: &[u8]) -> Result<(EthHeader, usize), SavedSynError> {
+    need("Ethernet header", buf, ETH_HDR_LEN)?;
+
+    let mut h = EthHeader {
+        dst: buf[0..6].try_into().unwrap(),
+        src: buf[6..12].try_into().unwrap(),
+        vlans: vec![],
+        ethertype: be16(buf, 12),
+    };
+
+    let mut off = ETH_HDR_LEN;
+    while matches!(h.ethertype, ETH_P_8021Q | ETH_P_8021AD) {
+        need("VLAN tag", buf, off + VLAN_TAG_LEN)?;
+
+        h.vlans.push(VlanTag {
+            tpid: h.ethertype,
+            tci: be16(buf, off),
+        });
+        h.ethertype = be16(buf, off + 2);
+        off += VLAN_TAG_LEN;
+    }
+
+    Ok((h, off))
+}
+
+fn parse_ipv4(buf: &[u8]) -> Result<(IpHeader, u8, usize), SavedSynError> {
+    need("IPv4 header", buf, IPV4_MIN_HDR_LEN)?;
+
+    let hdr_len = ((buf[0] & 0x0f) as usize) * 4;
+    if hdr_len < IPV4_MIN_HDR_LEN {
+        return Err(BadHeaderLength {
+            what: "IPv4 header",
+            len: hdr_len,
+        });
+    }
+    need("IPv4 options", buf, hdr_len)?;
+
+    let h = Ipv4Header {
+        tos: buf[1],
+        total_len: be16(buf, 2),
+        id: be16(buf, 4),
+        flags_frag_off: be16(buf, 6),
+        ttl: buf[8],
+        protocol: buf[9],
+        checksum: be16(buf, 10),
+        src: Ipv4Addr::from(be32(buf, 12)),
+        dst: Ipv4Addr::from(be32(buf, 16)),
+        options: buf[IPV4_MIN_HDR_LEN..hdr_len].to_vec(),
+    };
+    let proto = h.protocol;
+
+    Ok((IpHeader::V4(h), proto, hdr_len))
+}
+
+fn is_ipv6_ext_header(kind: u8) -> bool {
+    // Hop-by-Hop, Routing, Fragment, Auth, Destination Options, Mobility,
+    // HIP, Shim6
+    matches!(kind, 0 | 43 | 44 | 51 | 60 | 135 | 139 | 140)
+}
+
+fn parse_ipv6(buf: &[u8]) -> Result<(IpHeader, u8, usize), SavedSynError> {
+    need("IPv6 header", buf, IPV6_HDR_LEN)?;
+
+    let vtcfl = be32(buf, 0);
+    let mut h = Ipv6Header {
+        traffic_class: (vtcfl >> 20) as u8,
+        flow_label: vtcfl & 0x000f_ffff,
+        payload_len: be16(buf, 4),
+        next_header: buf[6],
+        hop_limit: buf[7],
+        src: Ipv6Addr::from(<[u8; 16]>::try_from(&buf[8..24]).unwrap()),
+        dst: Ipv6Addr::from(<[u8; 16]>::try_from(&buf[24..40]).unwrap()),
+        ext_headers: vec![],
+    };
+
+    let mut kind = h.next_header;
+    let mut off = IPV6_HDR_LEN;
+    while is_ipv6_ext_header(kind) {
+        let ext = &buf[off..];
+        need("IPv6 extension header", ext, 2)?;
+
+        let len = match kind {
+            44 => 8,
+            51 => (ext[1] as usize + 2) * 4,
+            _ => (ext[1] as usize + 1) * 8,
+        };
+        need("IPv6 extension header", ext, len)?;
+
+        h.ext_headers.push(Ipv6ExtHeader {
+            kind,
+            data: ext[1..len].to_vec(),
+        });
+        kind = ext[0];
+        off += len;
+    }
+
+    Ok((IpHeader::V6(h), kind, off))
+}
+
+fn parse_tcp(buf: &[u8]) -> Result<(TcpHeader, Vec<TcpOption>), SavedSynError> {
+    need("TCP header", buf, TCP_MIN_HDR_LEN)?;
+
+    let hdr_len = ((buf[12] >> 4) as usize) * 4;
+    if hdr_len < TCP_MIN_HDR_LEN {
+        return Err(BadHeaderLength {
+            what: "TCP header",
+            len: hdr_len,
+        });
+    }
+    need("TCP options", buf, hdr_len)?;
+
+    let h = TcpHeader {
+        src_port: be16(buf, 0),
+        dst_port: be16(buf, 2),
+        seq: be32(buf, 4),
+        ack: be32(buf, 8),
+        header_len: hdr_len,
+        flags: be16(buf, 12) & 0x0fff,
+        window: be16(buf, 14),
+        checksum: be16(buf, 16),
+        urgent_ptr: be16(buf, 18),
+    };
+    let opts = parse_tcp_options(&buf[TCP_MIN_HDR_LEN..hdr_len])?;
+
+    Ok((h, opts))
+}
+
+/// Experimental TCP Fast Open option (RFC 7413) uses kind 254 with this magic.
+const TFO_EXP_MAGIC: u16 = 0xf989;
+
+fn parse_tcp_options(mut buf: &[u8]) -> Result<Vec<TcpOption>, SavedSynError> {
+    let mut opts = vec![];
+
+    while let Some(&kind) = buf.first() {
+        match kind {
+            0 => {
+                // Rest of the option space is padding
+                opts.push(TcpOption::Eol);
+                break;
+            }
+            1 => {
+                opts.push(TcpOption::Nop);
+                buf = &buf[1..];
+                continue;
+            }
+            _ => {}
+        }
+
+        let len = *buf.get(1).ok_or(BadOptionLength { kind, len: 1 })? as usize;
+        if len < 2 || len > buf.len() {
+            return Err(BadOptionLength { kind, len });
+        }
+        let data = &buf[2..len];
+
+        let opt = match (kind, data.len()) {
+            (2, 2) => TcpOption::Mss(be16(data, 0)),
+            (3, 1) => TcpOption::WindowScale(data[0]),
+            (4, 0) => TcpOption::SackPermitted,
+            (5, n) if n % 8 == 0 => {
+                TcpOption::Sack(data.chunks(8).map(|b| (be32(b, 0), be32(b, 4))).collect())
+            }
+            (8, 8) => TcpOption::Timestamps {
+                val: be32(data, 0),
+                ecr: be32(data, 4),
+            },
+            (34, _) => TcpOption::FastOpen(data.to_vec()),
+            (254, n) if n >= 2 && be16(data, 0) == TFO_EXP_MAGIC => {
+                TcpOption::FastOpenExp(data[2..].to_vec())
+            }
+            (2..=5 | 8, _) => return Err(BadOptionLength { kind, len }),
+            _ => TcpOption::Unknown {
+                kind,
+                data: data.to_vec(),
+            },
+        };
+        opts.push(opt);
+        buf = &buf[len..];
+    }
+
+    Ok(opts)
+}
@@ -0,0 +1,197 @@
+//! Dispatching incoming connections to listening sockets by trait with
+//! `sk_lookup` programs.
+//!
+//! An `sk_lookup` program runs before the regular socket lookup for every
+//! packet looking for a socket in a network namespace, and may pick any
+//! socket from a `SOCKMAP`, regardless of its address and port. With
+//! `bpf/sk_lookup.bpf.c` the pick is made by trait value, through routes
+//! from trait values to socket map slots kept in a hash map.
+//!
+//! `sk_lookup` programs see no skb, so they can't read traits themselves. A
+//! tc program from the same object, attached at ingress after whatever sets
+//! the trait, notes trait values by flow in a map for the lookup to find.
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+use std::path::Path;
+
+use crate::bpf_sys::{
+    self, check_map, invalid, BPF_MAP_TYPE_HASH, BPF_MAP_TYPE_SOCKMAP, BPF_PROG_TYPE_SK_LOOKUP,
+    BPF_SK_LOOKUP,
+};
+use crate::{BpfLink, LinkTarget};
+
+/// Name of the socket map in `bpf/sk_lookup.bpf.c`.
+pub const DISPATCH_SOCKS_MAP: &str = "dispatch_socks";
+/// Name of the route map in `bpf/sk_lookup.bpf.c`.
+pub const DISPATCH_ROUTES_MAP: &str = "dispatch_routes";
+/// Name of the map of trait values by flow in `bpf/sk_lookup.bpf.c`, shared
+/// by its two programs.
+pub const DISPATCH_FLOWS_MAP: &str = "dispatch_flows";
+/// Most routes maps made by `TraitDispatch::create` hold.
+pub const MAX_DISPATCH_ROUTES: u32 = 1024;
+
+/// Manages where an `sk_lookup` dispatcher sends connections.
+///
+/// Target sockets sit in slots of a socket map. Routes map trait values to
+/// slots, so that several values can share a target. Lookups for packets
+/// without a routed trait value proceed as usual.
+///
+/// Load `bpf/sk_lookup.bpf.o` and hand its maps to [`TraitDispatch::new`],
+/// or create the maps up front with [`TraitDispatch::create`] and have the
+/// loader reuse them. The flow map is left to the loader. Attach
+/// `record_dispatch_trait` with `attach_tc` and `dispatch_by_trait` with
+/// [`attach_sk_lookup`]. Changes take effect for the next lookup.
+#[derive(Debug)]
+pub struct TraitDispatch {
+    socks: OwnedFd,
+    routes: OwnedFd,
+}
+
+impl TraitDispatch {
+    /// Takes the maps of a loaded dispatcher object. File descriptors are
+    /// duplicated.
+    pub fn new(socks: BorrowedFd<'_>, routes: BorrowedFd<'_>) -> io::Result<TraitDispatch> {
+        // 8-byte values so that lookups yield socket cookies
+        check_map(
+            socks,
+            DISPATCH_SOCKS_MAP,
+            BPF_MAP_TYPE_SOCKMAP,
+            mem::size_of::<u32>(),
+            mem::size_of::<u64>(),
+        )?;
+        check_map(
+            routes,
+            DISPATCH_ROUTES_MAP,
+            BPF_MAP_TYPE_HASH,
+            mem::size_of::<u32>(),
+            mem::size_of::<u32>(),
+        )?;
+
+        Ok(TraitDispatch {
+            socks: socks.try_clone_to_owned()?,
+            routes: routes.try_clone_to_owned()?,
+        })
+    }
+
+    /// Creates empty maps with room for this many target sockets.
+    pub fn create(max_targets: u32) -> io::Result<TraitDispatch> {
+        let socks = bpf_sys::map_create(
+            BPF_MAP_TYPE_SOCKMAP,
+            mem::size_of::<u32>(),
+            mem::size_of::<u64>(),
+            max_targets,
+            0,
+        )?;
+        let routes = bpf_sys::map_create(
+            BPF_MAP_TYPE_HASH,
+            mem::size_of::<u32>(),
+            mem::size_of::<u32>(),
+            MAX_DISPATCH_ROUTES,
+            0,
+        )?;
+
+        Ok(TraitDispatch { socks, routes })
+    }
+
+    /// Opens maps pinned in bpffs under their own names in `dir`.
+    pub fn open_pinned<P: AsRef<Path>>(dir: P) -> io::Result<TraitDispatch> {
+        let socks = bpf_sys::obj_get(&dir.as_ref().join(DISPATCH_SOCKS_MAP))?;
+        let routes = bpf_sys::obj_get(&dir.as_ref().join(DISPATCH_ROUTES_MAP))?;
+
+        TraitDispatch::new(socks.as_fd(), routes.as_fd())
+    }
+
+    pub fn socks_map(&self) -> BorrowedFd<'_> {
+        self.socks.as_fd()
+    }
+
+    pub fn routes_map(&self) -> BorrowedFd<'_> {
+        self.routes.as_fd()
+    }
+
+    /// Puts a target socket into a slot, replacing any previous one. TCP
+    /// sockets must be listening, UDP ones bound and not connected.
+    pub fn set_target<S: AsFd>(&self, slot: u32, sock: &S) -> io::Result<()> {
+        let fd = sock.as_fd().as_raw_fd() as u64;
+
+        bpf_sys::map_update(self.socks.as_fd(), &slot, &fd)
+    }
+
+    /// Empties a slot. Routes to it are left in place and match nothing.
+    /// Yields `false` if it was empty already.
+    pub fn remove_target(&self, slot: u32) -> io::Result<bool> {
+        // Socket maps fail deletes of empty slots with EINVAL, not ENOENT
+        if self.target_cookie(slot)?.is_none() {
+            return Ok(false);
+        }
+
+        bpf_sys::map_delete(self.socks.as_fd(), &slot)
+    }
+
+    /// Cookie of the socket in a slot, see `SoCookie`.
+    pub fn target_cookie(&self, slot: u32) -> io::Result<Option<u64>> {
+        let mut cookie = 0u64;
+
+        match bpf_sys::map_lookup(self.socks.as_fd(), &slot, &mut cookie)? {
+            true => Ok(Some(cookie)),
+            false => Ok(None),
+        }
+    }
+
+    /// Sends connections carrying the trait value to the socket in a slot.
+    /// Replaces an existing route for the value.
+    pub fn add_route(&self, value: u32, slot: u32) -> io::Result<()> {
+        bpf_sys::map_update(self.routes.as_fd(), &value, &slot)
+    }
+
+    /// Yields `false` if there was no route for the value.
+    pub fn remove_route(&self, value: u32) -> io::Result<bool> {
+        bpf_sys::map_delete(self.routes.as_fd(), &value)
+    }
+
+    /// Slot connections carrying the trait value go to, if any.
+    pub fn route(&self, value: u32) -> io::Result<Option<u32>> {
+        let mut slot = 0u32;
+
+        match bpf_sys::map_lookup(self.routes.as_fd(), &value, &mut slot)? {
+            true => Ok(Some(slot)),
+            false => Ok(None),
+        }
+    }
+
+    /// Lists routes as (trait value, slot) pairs, in no particular order.
+    pub fn list_routes(&self) -> io::Result<Vec<(u32, u32)>> {
+        let mut routes = vec![];
+
+        for value in bpf_sys::map_keys::<u32>(self.routes.as_fd())? {
+            if let Some(slot) = self.route(value)? {
+                routes.push((value, slot));
+            }
+        }
+
+        Ok(routes)
+    }
+}
+
+/// Attaches an `sk_lookup` program to the network namespace of the calling
+/// thread with a link. Several programs can be attached at once; they run
+/// in order until one picks a socket.
+///
+/// The program stays attached until the returned link is dropped, or
+/// longer if pinned.
+pub fn attach_sk_lookup<P: AsFd>(prog: P) -> io::Result<BpfLink> {
+    let info = bpf_sys::prog_info(prog.as_fd())?;
+    if info.prog_type != BPF_PROG_TYPE_SK_LOOKUP {
+        return Err(invalid(format!(
+            "BPF program type {} can't be attached to socket lookup, expected sk_lookup ({})",
+            info.prog_type, BPF_PROG_TYPE_SK_LOOKUP
+        )));
+    }
+
+    let netns = File::open("/proc/thread-self/ns/net")?;
+    let link = bpf_sys::link_create(prog.as_fd(), netns.as_raw_fd() as u32, BPF_SK_LOOKUP)?;
+
+    Ok(BpfLink::new(link, LinkTarget::SkLookup))
+}
@@ -1,10 +1,151 @@
-use nix::{self, sockopt_impl, setsockopt_impl};
-use nix::libc;
+use std::io;
+use std::marker::PhantomData;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+use std::path::Path;
 
-sockopt_impl!(
-    SoAttachBpf,
-    SetOnly,
+use crate::bpf_sys::{self, BPF_PROG_TYPE_SOCKET_FILTER};
+use crate::sockopt::{self, bool_sockopt, nix_setsockopt};
+
+/// Attaches a BPF program as the socket filter. The kernel rejects programs
+/// of the wrong type with a bare `EINVAL`. Check the program with
+/// `SocketFilterProg` first for a descriptive error.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SoAttachBpf<'fd>(PhantomData<BorrowedFd<'fd>>);
+
+impl SoAttachBpf<'_> {
+    pub const fn new() -> Self {
+        SoAttachBpf(PhantomData)
+    }
+}
+
+impl<'fd> sockopt::SetSockOpt for SoAttachBpf<'fd> {
+    type Val = BorrowedFd<'fd>;
+
+    fn set<F: AsFd>(&self, fd: &F, prog: &BorrowedFd<'fd>) -> io::Result<()> {
+        sockopt::set_int(
+            fd.as_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_ATTACH_BPF,
+            prog.as_raw_fd(),
+        )
+    }
+}
+
+nix_setsockopt!(SoAttachBpf<'_>);
+
+/// BPF program checked to be of a type which can be attached to a socket.
+#[derive(Clone, Copy, Debug)]
+pub struct SocketFilterProg<'fd>(BorrowedFd<'fd>);
+
+impl<'fd> SocketFilterProg<'fd> {
+    /// Queries the program type with `BPF_OBJ_GET_INFO_BY_FD`. Fails with
+    /// `InvalidInput` if it's not a socket filter or not a program at all.
+    pub fn new(prog: BorrowedFd<'fd>) -> io::Result<SocketFilterProg<'fd>> {
+        let info = bpf_sys::prog_info(prog)?;
+        if info.prog_type != BPF_PROG_TYPE_SOCKET_FILTER {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "BPF program type {} can't be attached to a socket, expected socket filter ({})",
+                    info.prog_type, BPF_PROG_TYPE_SOCKET_FILTER
+                ),
+            ));
+        }
+
+        Ok(SocketFilterProg(prog))
+    }
+}
+
+impl AsFd for SocketFilterProg<'_> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0
+    }
+}
+
+/// Removes the socket filter, be it a cBPF or an eBPF one. Fails with
+/// `ENOENT` if none is attached.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SoDetachBpf;
+
+impl sockopt::SetSockOpt for SoDetachBpf {
+    type Val = ();
+
+    fn set<F: AsFd>(&self, fd: &F, _: &()) -> io::Result<()> {
+        // Kernel ignores the value but insists on an int-sized one
+        sockopt::set_int(fd.as_fd(), libc::SOL_SOCKET, libc::SO_DETACH_BPF, 0)
+    }
+}
+
+nix_setsockopt!(SoDetachBpf);
+
+bool_sockopt!(
+    /// Once set, the socket filter can be neither replaced nor detached, and
+    /// the lock can't be released.
+    SoLockFilter,
     libc::SOL_SOCKET,
-    libc::SO_ATTACH_BPF,
-    libc::c_int
+    libc::SO_LOCK_FILTER
 );
+
+/// Attaches a BPF program to the socket as its filter. The filter stays
+/// attached until the returned guard is dropped.
+pub fn attach_bpf<S: AsFd, P: AsFd>(sock: &S, prog: P) -> io::Result<AttachedFilter<'_>> {
+    let sock = sock.as_fd();
+    sockopt::setsockopt(&sock, SoAttachBpf::new(), &prog.as_fd())?;
+
+    Ok(AttachedFilter::new(sock))
+}
+
+/// Attaches a socket filter program pinned in bpffs by another process.
+/// Fails with `InvalidInput` if the pinned object is not a socket filter.
+pub fn attach_pinned<S: AsFd, P: AsRef<Path>>(sock: &S, path: P) -> io::Result<AttachedFilter<'_>> {
+    let prog = bpf_sys::obj_get(path.as_ref())?;
+    let prog = SocketFilterProg::new(prog.as_fd())?;
+
+    // Socket holds its own reference to the program once attached
+    attach_bpf(sock, prog)
+}
+
+/// Socket filter attached with `attach_bpf` or `attach_filter`. Detaches it
+/// on drop, ignoring errors.
+#[derive(Debug)]
+#[must_use = "filter is detached when the guard is dropped"]
+pub struct AttachedFilter<'a> {
+    sock: BorrowedFd<'a>,
+}
+
+impl<'a> AttachedFilter<'a> {
+    pub(crate) fn new(sock: BorrowedFd<'a>) -> AttachedFilter<'a> {
+        AttachedFilter { sock }
+    }
+
+    /// Swaps in another program in place of the attached one. Packets are
+    /// never left unfiltered in between.
+    pub fn replace<P: AsFd>(&self, prog: P) -> io::Result<()> {
+        sockopt::setsockopt(&self.sock, SoAttachBpf::new(), &prog.as_fd())
+    }
+
+    /// Prevents the filter from being replaced or detached, also by the
+    /// guard itself.
+    pub fn lock(&self) -> io::Result<()> {
+        sockopt::setsockopt(&self.sock, SoLockFilter, &true)
+    }
+
+    /// Detaches the filter, reporting failure unlike drop.
+    pub fn detach(self) -> io::Result<()> {
+        let res = sockopt::setsockopt(&self.sock, SoDetachBpf, &());
+        mem::forget(self);
+        res
+    }
+
+    /// Leaves the filter attached for the lifetime of the socket.
+    pub fn keep(self) {
+        mem::forget(self);
+    }
+}
+
+impl Drop for AttachedFilter<'_> {
+    fn drop(&mut self) {
+        let _ = sockopt::setsockopt(&self.sock, SoDetachBpf, &());
+    }
+}
@@ -0,0 +1,130 @@
+use libc::c_ushort;
+use std::io;
+use std::os::fd::AsFd;
+use std::ptr;
+
+use crate::sockopt::{self, nix_getsockopt, nix_setsockopt};
+use crate::AttachedFilter;
+
+pub use libc::{SO_ATTACH_FILTER, SO_GET_FILTER};
+
+/// Classic BPF instruction, `struct sock_filter`. Build programs with
+/// `cbpf::FilterBuilder`.
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct SockFilter {
+    pub code: u16,
+    pub jt: u8,
+    pub jf: u8,
+    pub k: u32,
+}
+
+impl SockFilter {
+    /// Same as the `BPF_STMT` macro.
+    pub const fn stmt(code: u16, k: u32) -> SockFilter {
+        SockFilter {
+            code,
+            jt: 0,
+            jf: 0,
+            k,
+        }
+    }
+
+    /// Same as the `BPF_JUMP` macro.
+    pub const fn jump(code: u16, k: u32, jt: u8, jf: u8) -> SockFilter {
+        SockFilter { code, jt, jf, k }
+    }
+}
+
+#[repr(C)]
+struct SockFprog {
+    len: c_ushort,
+    filter: *const SockFilter,
+}
+
+/// Attaches a classic BPF program as the socket filter. Unlike
+/// `SoAttachBpf`, needs no privileges.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SoAttachFilter;
+
+impl sockopt::SetSockOpt for SoAttachFilter {
+    type Val = [SockFilter];
+
+    fn set<F: AsFd>(&self, fd: &F, prog: &[SockFilter]) -> io::Result<()> {
+        let fprog = SockFprog {
+            len: prog
+                .len()
+                .try_into()
+                .map_err(|_| io::Error::from_raw_os_error(libc::EINVAL))?,
+            filter: prog.as_ptr(),
+        };
+
+        sockopt::set_raw(
+            fd.as_fd(),
+            libc::SOL_SOCKET,
+            SO_ATTACH_FILTER,
+            (&fprog as *const SockFprog).cast(),
+            std::mem::size_of::<SockFprog>(),
+        )
+    }
+}
+
+nix_setsockopt!(SoAttachFilter);
+
+/// Reads back the attached classic BPF program. Empty if there is no filter.
+/// Fails with `EACCES` for eBPF filters, which can't be dumped this way.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct SoGetFilter;
+
+impl sockopt::GetSockOpt for SoGetFilter {
+    type Val = Vec<SockFilter>;
+
+    fn get<F: AsFd>(&self, fd: &F) -> io::Result<Vec<SockFilter>> {
+        // Length is counted in instructions, not bytes. Zero asks for the
+        // count alone.
+        loop {
+            let mut n = 0;
+            sockopt::get_raw(
+                fd.as_fd(),
+                libc::SOL_SOCKET,
+                SO_GET_FILTER,
+                ptr::null_mut(),
+                &mut n,
+            )?;
+
+            let mut prog = vec![SockFilter::default(); n];
+            let mut len = n;
+            let res = sockopt::get_raw(
+                fd.as_fd(),
+                libc::SOL_SOCKET,
+                SO_GET_FILTER,
+                prog.as_mut_ptr().cast(),
+                &mut len,
+            );
+
+            match res {
+                Ok(()) => {
+                    prog.truncate(len);
+                    return Ok(prog);
+                }
+                // Replaced with a longer program in the meantime
+                Err(err) if n != 0 && err.raw_os_error() == Some(libc::EINVAL) => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+nix_getsockopt!(SoGetFilter);
+
+/// Attaches a classic BPF program to the socket as its filter. The filter
+/// stays attached until the returned guard is dropped.
+pub fn attach_filter<'a, S: AsFd>(
+    sock: &'a S,
+    prog: &[SockFilter],
+) -> io::Result<AttachedFilter<'a>> {
+    let sock = sock.as_fd();
+    sockopt::setsockopt(&sock, SoAttachFilter, prog)?;
+
+    Ok(AttachedFilter::new(sock))
+}
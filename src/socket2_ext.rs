@@ -0,0 +1,50 @@
+use socket2::Socket;
+use std::io;
+use std::os::fd::AsFd;
+
+use crate::sockopt::{getsockopt, setsockopt};
+use crate::{
+    PktTrait, RcvPktTraits, SoAttachBpf, TcpSaveSynTraits, TcpSynTraits, TcpSynTraitsSet, TraitKey,
+};
+
+/// Trait socket options for `socket2::Socket`, to be set before bind or
+/// connect.
+pub trait SockTraitsExt {
+    fn set_save_syn_traits(&self, enable: bool) -> io::Result<()>;
+    fn save_syn_traits(&self) -> io::Result<bool>;
+    fn set_syn_traits(&self, traits: &[PktTrait]) -> io::Result<()>;
+    fn syn_traits(&self, keys: &[TraitKey]) -> io::Result<Vec<PktTrait>>;
+    fn set_recv_pkt_traits(&self, enable: bool) -> io::Result<()>;
+    fn recv_pkt_traits(&self) -> io::Result<bool>;
+    fn attach_bpf<P: AsFd>(&self, prog: P) -> io::Result<()>;
+}
+
+impl SockTraitsExt for Socket {
+    fn set_save_syn_traits(&self, enable: bool) -> io::Result<()> {
+        setsockopt(self, TcpSaveSynTraits, &enable)
+    }
+
+    fn save_syn_traits(&self) -> io::Result<bool> {
+        getsockopt(self, TcpSaveSynTraits)
+    }
+
+    fn set_syn_traits(&self, traits: &[PktTrait]) -> io::Result<()> {
+        setsockopt(self, TcpSynTraitsSet::default(), &traits)
+    }
+
+    fn syn_traits(&self, keys: &[TraitKey]) -> io::Result<Vec<PktTrait>> {
+        getsockopt(self, TcpSynTraits(keys))
+    }
+
+    fn set_recv_pkt_traits(&self, enable: bool) -> io::Result<()> {
+        setsockopt(self, RcvPktTraits, &enable)
+    }
+
+    fn recv_pkt_traits(&self) -> io::Result<bool> {
+        getsockopt(self, RcvPktTraits)
+    }
+
+    fn attach_bpf<P: AsFd>(&self, prog: P) -> io::Result<()> {
+        setsockopt(self, SoAttachBpf::new(), &prog.as_fd())
+    }
+}
@@ -0,0 +1,142 @@
+//! Socket options on plain `libc` calls, with std types in the API.
+//!
+//! With the `nix` feature enabled, the same option types also implement
+//! `nix::sys::socket::{GetSockOpt, SetSockOpt}`.
+use libc::{c_int, c_void, socklen_t};
+use std::io;
+use std::mem;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd};
+
+/// Socket option which can be read.
+pub trait GetSockOpt: Copy {
+    type Val;
+
+    fn get<F: AsFd>(&self, fd: &F) -> io::Result<Self::Val>;
+}
+
+/// Socket option which can be set.
+pub trait SetSockOpt: Clone {
+    type Val: ?Sized;
+
+    fn set<F: AsFd>(&self, fd: &F, val: &Self::Val) -> io::Result<()>;
+}
+
+pub fn getsockopt<F: AsFd, O: GetSockOpt>(fd: &F, opt: O) -> io::Result<O::Val> {
+    opt.get(fd)
+}
+
+pub fn setsockopt<F: AsFd, O: SetSockOpt>(fd: &F, opt: O, val: &O::Val) -> io::Result<()> {
+    opt.set(fd, val)
+}
+
+/// Calls getsockopt(2). On return `len` holds the length reported by the
+/// kernel, also when the call fails.
+pub(crate) fn get_raw(
+    fd: BorrowedFd<'_>,
+    level: c_int,
+    name: c_int,
+    buf: *mut c_void,
+    len: &mut usize,
+) -> io::Result<()> {
+    let mut optlen = *len as socklen_t;
+    let res = unsafe { libc::getsockopt(fd.as_raw_fd(), level, name, buf, &mut optlen) };
+    *len = optlen as usize;
+
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Calls setsockopt(2).
+pub(crate) fn set_raw(
+    fd: BorrowedFd<'_>,
+    level: c_int,
+    name: c_int,
+    buf: *const c_void,
+    len: usize,
+) -> io::Result<()> {
+    let res = unsafe { libc::setsockopt(fd.as_raw_fd(), level, name, buf, len as socklen_t) };
+
+    if res == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+pub(crate) fn get_int(fd: BorrowedFd<'_>, level: c_int, name: c_int) -> io::Result<c_int> {
+    let mut val: c_int = 0;
+    let ptr = (&mut val as *mut c_int).cast();
+
+    get_raw(fd, level, name, ptr, &mut mem::size_of::<c_int>())?;
+    Ok(val)
+}
+
+pub(crate) fn set_int(fd: BorrowedFd<'_>, level: c_int, name: c_int, val: c_int) -> io::Result<()> {
+    let ptr = (&val as *const c_int).cast();
+
+    set_raw(fd, level, name, ptr, mem::size_of::<c_int>())
+}
+
+#[cfg(feature = "nix")]
+pub(crate) fn errno(err: io::Error) -> nix::errno::Errno {
+    nix::errno::Errno::from_raw(err.raw_os_error().unwrap_or(0))
+}
+
+/// Defines a boolean socket option, stored by the kernel as `int`.
+macro_rules! bool_sockopt {
+    ($(#[$attr:meta])* $name:ident, $level:expr, $opt:expr) => {
+        $(#[$attr])*
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+        pub struct $name;
+
+        impl $crate::sockopt::GetSockOpt for $name {
+            type Val = bool;
+
+            fn get<F: std::os::fd::AsFd>(&self, fd: &F) -> std::io::Result<bool> {
+                $crate::sockopt::get_int(fd.as_fd(), $level, $opt).map(|v| v != 0)
+            }
+        }
+
+        impl $crate::sockopt::SetSockOpt for $name {
+            type Val = bool;
+
+            fn set<F: std::os::fd::AsFd>(&self, fd: &F, val: &bool) -> std::io::Result<()> {
+                $crate::sockopt::set_int(fd.as_fd(), $level, $opt, *val as libc::c_int)
+            }
+        }
+
+        $crate::sockopt::nix_getsockopt!($name);
+        $crate::sockopt::nix_setsockopt!($name);
+    };
+}
+
+/// Implements `nix::sys::socket::GetSockOpt` on top of `GetSockOpt`.
+macro_rules! nix_getsockopt {
+    ($name:ty) => {
+        #[cfg(feature = "nix")]
+        impl nix::sys::socket::GetSockOpt for $name {
+            type Val = <Self as $crate::sockopt::GetSockOpt>::Val;
+
+            fn get<F: std::os::fd::AsFd>(&self, fd: &F) -> nix::Result<Self::Val> {
+                $crate::sockopt::GetSockOpt::get(self, fd).map_err($crate::sockopt::errno)
+            }
+        }
+    };
+}
+
+/// Implements `nix::sys::socket::SetSockOpt` on top of `SetSockOpt`.
+macro_rules! nix_setsockopt {
+    ($name:ty) => {
+        #[cfg(feature = "nix")]
+        impl nix::sys::socket::SetSockOpt for $name {
+            type Val = <Self as $crate::sockopt::SetSockOpt>::Val;
+
+            fn set<F: std::os::fd::AsFd>(&self, fd: &F, val: &Self::Val) -> nix::Result<()> {
+                $crate::sockopt::SetSockOpt::set(self, fd, val).map_err($crate::sockopt::errno)
+            }
+        }
+    };
+}
+
+pub(crate) use {bool_sockopt, nix_getsockopt, nix_setsockopt};
@@ -0,0 +1,721 @@
+/// Passive TCP fingerprinting of saved SYN headers
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::{IpHeader, SavedSyn, TcpHeader, TcpOption};
+
+use self::SignatureError::*;
+
+/// Largest hop count between the initial and the observed TTL we accept.
+const MAX_DIST: u8 = 35;
+
+const IPV4_MTU_OVERHEAD: u16 = 40;
+const IPV6_MTU_OVERHEAD: u16 = 60;
+const IPV4_RF: u16 = 0x8000;
+const MAX_WSCALE: u8 = 14;
+
+/// Set of header oddities, named as in p0f.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct Quirks(u32);
+
+impl Quirks {
+    pub const DF: Quirks = Quirks(1 << 0);
+    pub const NONZERO_ID: Quirks = Quirks(1 << 1);
+    pub const ZERO_ID: Quirks = Quirks(1 << 2);
+    pub const ECN: Quirks = Quirks(1 << 3);
+    pub const NONZERO_RF: Quirks = Quirks(1 << 4);
+    pub const FLOW: Quirks = Quirks(1 << 5);
+    pub const ZERO_SEQ: Quirks = Quirks(1 << 6);
+    pub const NONZERO_ACK: Quirks = Quirks(1 << 7);
+    pub const ZERO_ACK: Quirks = Quirks(1 << 8);
+    pub const NONZERO_URG: Quirks = Quirks(1 << 9);
+    pub const URG: Quirks = Quirks(1 << 10);
+    pub const PUSH: Quirks = Quirks(1 << 11);
+    pub const ZERO_TS1: Quirks = Quirks(1 << 12);
+    pub const NONZERO_TS2: Quirks = Quirks(1 << 13);
+    /// Never set on a `SynFingerprint`, since `SavedSyn` drops bytes past EOL.
+    pub const OPT_PAST_EOL: Quirks = Quirks(1 << 14);
+    pub const EXCESSIVE_WS: Quirks = Quirks(1 << 15);
+    /// Never set on a `SynFingerprint`, since `SavedSyn` rejects bad options.
+    pub const BAD_OPT: Quirks = Quirks(1 << 16);
+
+    const NAMES: [(Quirks, &'static str); 17] = [
+        (Quirks::DF, "df"),
+        (Quirks::NONZERO_ID, "id+"),
+        (Quirks::ZERO_ID, "id-"),
+        (Quirks::ECN, "ecn"),
+        (Quirks::NONZERO_RF, "0+"),
+        (Quirks::FLOW, "flow"),
+        (Quirks::ZERO_SEQ, "seq-"),
+        (Quirks::NONZERO_ACK, "ack+"),
+        (Quirks::ZERO_ACK, "ack-"),
+        (Quirks::NONZERO_URG, "uptr+"),
+        (Quirks::URG, "urgf+"),
+        (Quirks::PUSH, "pushf+"),
+        (Quirks::ZERO_TS1, "ts1-"),
+        (Quirks::NONZERO_TS2, "ts2+"),
+        (Quirks::OPT_PAST_EOL, "opt+"),
+        (Quirks::EXCESSIVE_WS, "exws"),
+        (Quirks::BAD_OPT, "bad"),
+    ];
+
+    pub const fn empty() -> Quirks {
+        Quirks(0)
+    }
+
+    pub const fn bits(&self) -> u32 {
+        self.0
+    }
+
+    pub fn contains(&self, other: Quirks) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub fn insert(&mut self, other: Quirks) {
+        self.0 |= other.0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+}
+
+impl std::ops::BitOr for Quirks {
+    type Output = Quirks;
+
+    fn bitor(self, rhs: Quirks) -> Quirks {
+        Quirks(self.0 | rhs.0)
+    }
+}
+
+impl fmt::Display for Quirks {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut sep = "";
+        for (q, name) in Quirks::NAMES {
+            if self.contains(q) {
+                write!(f, "{}{}", sep, name)?;
+                sep = ",";
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for Quirks {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<Quirks, SignatureError> {
+        let mut quirks = Quirks::empty();
+        for name in s.split(',').filter(|n| !n.is_empty()) {
+            let (q, _) = Quirks::NAMES
+                .iter()
+                .find(|(_, n)| *n == name)
+                .ok_or_else(|| bad_field("quirks", name))?;
+            quirks.insert(*q);
+        }
+        Ok(quirks)
+    }
+}
+
+/// TCP option as it appears in the option layout of a signature.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum OptionLayout {
+    /// End of options, followed by given number of padding bytes.
+    Eol(usize),
+    Nop,
+    Mss,
+    WindowScale,
+    SackPermitted,
+    Sack,
+    Timestamps,
+    Other(u8),
+}
+
+impl fmt::Display for OptionLayout {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            OptionLayout::Eol(pad) => write!(f, "eol+{}", pad),
+            OptionLayout::Nop => write!(f, "nop"),
+            OptionLayout::Mss => write!(f, "mss"),
+            OptionLayout::WindowScale => write!(f, "ws"),
+            OptionLayout::SackPermitted => write!(f, "sok"),
+            OptionLayout::Sack => write!(f, "sack"),
+            OptionLayout::Timestamps => write!(f, "ts"),
+            OptionLayout::Other(kind) => write!(f, "?{}", kind),
+        }
+    }
+}
+
+impl FromStr for OptionLayout {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<OptionLayout, SignatureError> {
+        let opt = match s {
+            "nop" => OptionLayout::Nop,
+            "mss" => OptionLayout::Mss,
+            "ws" => OptionLayout::WindowScale,
+            "sok" => OptionLayout::SackPermitted,
+            "sack" => OptionLayout::Sack,
+            "ts" => OptionLayout::Timestamps,
+            _ => {
+                if let Some(pad) = s.strip_prefix("eol+") {
+                    OptionLayout::Eol(num("olayout", pad)?)
+                } else if let Some(kind) = s.strip_prefix('?') {
+                    OptionLayout::Other(num("olayout", kind)?)
+                } else {
+                    return Err(bad_field("olayout", s));
+                }
+            }
+        };
+        Ok(opt)
+    }
+}
+
+/// Characteristics of a SYN which identify the sender's TCP/IP stack.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SynFingerprint {
+    pub ip_version: u8,
+    /// TTL or hop limit as received.
+    pub ttl: u8,
+    pub ip_opt_len: usize,
+    pub mss: Option<u16>,
+    pub window: u16,
+    pub window_scale: Option<u8>,
+    pub layout: Vec<OptionLayout>,
+    pub quirks: Quirks,
+}
+
+impl SynFingerprint {
+    pub fn new(syn: &SavedSyn) -> SynFingerprint {
+        let mut quirks = Quirks::empty();
+
+        let (ip_version, ip_opt_len) = match &syn.ip {
+            IpHeader::V4(h) => {
+                if h.dont_fragment() {
+                    quirks.insert(Quirks::DF);
+                    if h.id != 0 {
+                        quirks.insert(Quirks::NONZERO_ID);
+                    }
+                } else if h.id == 0 {
+                    quirks.insert(Quirks::ZERO_ID);
+                }
+                if h.flags_frag_off & IPV4_RF != 0 {
+                    quirks.insert(Quirks::NONZERO_RF);
+                }
+                if h.tos & 0x03 != 0 {
+                    quirks.insert(Quirks::ECN);
+                }
+                (4, h.options.len())
+            }
+            IpHeader::V6(h) => {
+                if h.flow_label != 0 {
+                    quirks.insert(Quirks::FLOW);
+                }
+                if h.traffic_class & 0x03 != 0 {
+                    quirks.insert(Quirks::ECN);
+                }
+                (6, 0)
+            }
+        };
+
+        let tcp = &syn.tcp;
+        if tcp.flags & (TcpHeader::ECE | TcpHeader::CWR) != 0 {
+            quirks.insert(Quirks::ECN);
+        }
+        if tcp.seq == 0 {
+            quirks.insert(Quirks::ZERO_SEQ);
+        }
+        if tcp.flags & TcpHeader::ACK != 0 {
+            if tcp.ack == 0 {
+                quirks.insert(Quirks::ZERO_ACK);
+            }
+        } else if tcp.ack != 0 {
+            quirks.insert(Quirks::NONZERO_ACK);
+        }
+        if tcp.flags & TcpHeader::URG != 0 {
+            quirks.insert(Quirks::URG);
+        } else if tcp.urgent_ptr != 0 {
+            quirks.insert(Quirks::NONZERO_URG);
+        }
+        if tcp.flags & TcpHeader::PSH != 0 {
+            quirks.insert(Quirks::PUSH);
+        }
+        if let Some((val, ecr)) = syn.timestamps() {
+            if val == 0 {
+                quirks.insert(Quirks::ZERO_TS1);
+            }
+            if ecr != 0 {
+                quirks.insert(Quirks::NONZERO_TS2);
+            }
+        }
+        if syn.window_scale().is_some_and(|ws| ws > MAX_WSCALE) {
+            quirks.insert(Quirks::EXCESSIVE_WS);
+        }
+
+        SynFingerprint {
+            ip_version,
+            ttl: syn.ip.ttl(),
+            ip_opt_len,
+            mss: syn.mss(),
+            window: tcp.window,
+            window_scale: syn.window_scale(),
+            layout: option_layout(syn),
+            quirks,
+        }
+    }
+
+    /// Initial TTL the sender most likely used.
+    pub fn initial_ttl(&self) -> u8 {
+        match self.ttl {
+            0..=32 => 32,
+            33..=64 => 64,
+            65..=128 => 128,
+            _ => 255,
+        }
+    }
+
+    /// Link MTU implied by the MSS.
+    pub fn mtu(&self) -> Option<u16> {
+        let overhead = match self.ip_version {
+            4 => IPV4_MTU_OVERHEAD,
+            _ => IPV6_MTU_OVERHEAD,
+        };
+        self.mss.map(|mss| mss.saturating_add(overhead))
+    }
+
+    /// JA4T fingerprint: window, option kinds, MSS and window scale.
+    pub fn ja4t(&self) -> String {
+        let kinds: Vec<String> = self
+            .layout
+            .iter()
+            .map(|o| match o {
+                OptionLayout::Eol(_) => 0,
+                OptionLayout::Nop => 1,
+                OptionLayout::Mss => 2,
+                OptionLayout::WindowScale => 3,
+                OptionLayout::SackPermitted => 4,
+                OptionLayout::Sack => 5,
+                OptionLayout::Timestamps => 8,
+                OptionLayout::Other(kind) => *kind,
+            })
+            .map(|k| k.to_string())
+            .collect();
+
+        format!(
+            "{}_{}_{}_{}",
+            self.window,
+            kinds.join("-"),
+            self.mss.unwrap_or(0),
+            self.window_scale.unwrap_or(0)
+        )
+    }
+}
+
+impl From<&SavedSyn> for SynFingerprint {
+    fn from(syn: &SavedSyn) -> SynFingerprint {
+        SynFingerprint::new(syn)
+    }
+}
+
+/// Formats as a p0f raw signature.
+impl fmt::Display for SynFingerprint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let ittl = self.initial_ttl();
+        write!(
+            f,
+            "{}:{}+{}:{}:",
+            self.ip_version,
+            ittl,
+            ittl - self.ttl,
+            self.ip_opt_len
+        )?;
+        match self.mss {
+            Some(mss) => write!(f, "{}:", mss)?,
+            None => write!(f, "*:")?,
+        }
+        match self.mss {
+            Some(mss) if mss != 0 && self.window.is_multiple_of(mss) => {
+                write!(f, "mss*{}", self.window / mss)?
+            }
+            _ => write!(f, "{}", self.window)?,
+        }
+        match self.window_scale {
+            Some(ws) => write!(f, ",{}:", ws)?,
+            None => write!(f, ",*:")?,
+        }
+        write_layout(f, &self.layout)?;
+        write!(f, ":{}:0", self.quirks)
+    }
+}
+
+/// Window size pattern of a signature.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum WindowMatch {
+    Any,
+    Exact(u16),
+    /// Multiple of the MSS.
+    Mss(u16),
+    /// Multiple of the MTU.
+    Mtu(u16),
+    /// Any multiple of the given value.
+    Modulo(u16),
+}
+
+impl WindowMatch {
+    pub fn matches(&self, fp: &SynFingerprint) -> bool {
+        let win = fp.window as u32;
+        match *self {
+            WindowMatch::Any => true,
+            WindowMatch::Exact(w) => fp.window == w,
+            WindowMatch::Mss(n) => fp.mss.is_some_and(|mss| mss as u32 * n as u32 == win),
+            WindowMatch::Mtu(n) => fp.mtu().is_some_and(|mtu| mtu as u32 * n as u32 == win),
+            WindowMatch::Modulo(n) => n != 0 && win.is_multiple_of(n as u32),
+        }
+    }
+}
+
+impl fmt::Display for WindowMatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            WindowMatch::Any => write!(f, "*"),
+            WindowMatch::Exact(w) => write!(f, "{}", w),
+            WindowMatch::Mss(n) => write!(f, "mss*{}", n),
+            WindowMatch::Mtu(n) => write!(f, "mtu*{}", n),
+            WindowMatch::Modulo(n) => write!(f, "%{}", n),
+        }
+    }
+}
+
+impl FromStr for WindowMatch {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<WindowMatch, SignatureError> {
+        let win = if s == "*" {
+            WindowMatch::Any
+        } else if let Some(n) = s.strip_prefix("mss*") {
+            WindowMatch::Mss(num("wsize", n)?)
+        } else if let Some(n) = s.strip_prefix("mtu*") {
+            WindowMatch::Mtu(num("wsize", n)?)
+        } else if let Some(n) = s.strip_prefix('%') {
+            WindowMatch::Modulo(num("wsize", n)?)
+        } else {
+            WindowMatch::Exact(num("wsize", s)?)
+        };
+        Ok(win)
+    }
+}
+
+/// Pattern a `SynFingerprint` can be matched against. Parses from and
+/// formats to p0f v3 syntax:
+///
+/// ```text
+/// ver:ittl:olen:mss:wsize,scale:olayout:quirks:pclass
+/// ```
+#[derive(Clone, Debug, PartialEq)]
+pub struct SynSignature {
+    /// IP version, any when `None`.
+    pub ip_version: Option<u8>,
+    pub initial_ttl: u8,
+    /// Accept any TTL up to `initial_ttl`, not just ones within `MAX_DIST`
+    /// hops of it.
+    pub fuzzy_ttl: bool,
+    pub ip_opt_len: usize,
+    /// MSS, any when `None`.
+    pub mss: Option<u16>,
+    pub window: WindowMatch,
+    /// Window scale, any when `None`.
+    pub window_scale: Option<u8>,
+    pub layout: Vec<OptionLayout>,
+    pub quirks: Quirks,
+    /// Whether the SYN carries a payload, any when `None`.
+    pub payload: Option<bool>,
+}
+
+impl SynSignature {
+    pub fn matches(&self, fp: &SynFingerprint) -> bool {
+        if self.ip_version.is_some_and(|v| v != fp.ip_version) {
+            return false;
+        }
+        if fp.ttl > self.initial_ttl {
+            return false;
+        }
+        if !self.fuzzy_ttl && self.initial_ttl - fp.ttl > MAX_DIST {
+            return false;
+        }
+        if self.mss.is_some_and(|mss| Some(mss) != fp.mss) {
+            return false;
+        }
+        if self
+            .window_scale
+            .is_some_and(|ws| Some(ws) != fp.window_scale)
+        {
+            return false;
+        }
+        // Saved SYN headers never include the payload
+        if self.payload == Some(true) {
+            return false;
+        }
+
+        self.ip_opt_len == fp.ip_opt_len
+            && self.window.matches(fp)
+            && self.layout == fp.layout
+            && self.quirks == fp.quirks
+    }
+}
+
+impl fmt::Display for SynSignature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self.ip_version {
+            Some(v) => write!(f, "{}:", v)?,
+            None => write!(f, "*:")?,
+        }
+        write!(f, "{}", self.initial_ttl)?;
+        if self.fuzzy_ttl {
+            write!(f, "-")?;
+        }
+        write!(f, ":{}:", self.ip_opt_len)?;
+        match self.mss {
+            Some(mss) => write!(f, "{}:", mss)?,
+            None => write!(f, "*:")?,
+        }
+        write!(f, "{},", self.window)?;
+        match self.window_scale {
+            Some(ws) => write!(f, "{}:", ws)?,
+            None => write!(f, "*:")?,
+        }
+        write_layout(f, &self.layout)?;
+        write!(f, ":{}:", self.quirks)?;
+        match self.payload {
+            Some(true) => write!(f, "+"),
+            Some(false) => write!(f, "0"),
+            None => write!(f, "*"),
+        }
+    }
+}
+
+impl FromStr for SynSignature {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<SynSignature, SignatureError> {
+        let fields: Vec<&str> = s.trim().split(':').collect();
+        let [ver, ittl, olen, mss, win, layout, quirks, pclass] = fields[..] else {
+            return Err(FieldCount(fields.len()));
+        };
+
+        let ip_version = match ver {
+            "*" => None,
+            "4" | "6" => Some(num("ver", ver)?),
+            _ => return Err(bad_field("ver", ver)),
+        };
+
+        // Observed signatures carry the hop distance as "ittl+dist"
+        let (ittl, fuzzy_ttl) = match ittl.strip_suffix('-') {
+            Some(t) => (t, true),
+            None => (ittl.split('+').next().unwrap_or(ittl), false),
+        };
+
+        let (wsize, scale) = win.split_once(',').ok_or_else(|| bad_field("wsize", win))?;
+
+        let layout = layout
+            .split(',')
+            .filter(|o| !o.is_empty())
+            .map(str::parse)
+            .collect::<Result<_, _>>()?;
+
+        let payload = match pclass {
+            "0" => Some(false),
+            "+" => Some(true),
+            "*" => None,
+            _ => return Err(bad_field("pclass", pclass)),
+        };
+
+        Ok(SynSignature {
+            ip_version,
+            initial_ttl: num("ittl", ittl)?,
+            fuzzy_ttl,
+            ip_opt_len: num("olen", olen)?,
+            mss: any_or_num("mss", mss)?,
+            window: wsize.parse()?,
+            window_scale: any_or_num("scale", scale)?,
+            layout,
+            quirks: quirks.parse()?,
+            payload,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum SignatureError {
+    /// Signature doesn't have the 8 colon separated fields.
+    FieldCount(usize),
+    /// Value of the named field can't be parsed.
+    BadField { field: &'static str, value: String },
+    /// `sig` appears before any `label` in the database.
+    MissingLabel,
+    /// Error on the given line of the database.
+    Line(usize, Box<SignatureError>),
+}
+
+impl std::error::Error for SignatureError {}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            FieldCount(n) => write!(f, "Expected 8 signature fields, got {}", n),
+            BadField { field, value } => write!(f, "Bad {} value '{}'", field, value),
+            MissingLabel => write!(f, "Signature without a label"),
+            Line(n, err) => write!(f, "Line {}: {}", n, err),
+        }
+    }
+}
+
+/// Labeled SYN signatures, as found in the `[tcp:request]` section of a
+/// p0f database. Other sections are skipped.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SignatureDb {
+    entries: Vec<(String, SynSignature)>,
+}
+
+impl SignatureDb {
+    pub fn new() -> SignatureDb {
+        SignatureDb::default()
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<SignatureDb> {
+        fs::read_to_string(path)?
+            .parse()
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn add(&mut self, label: impl Into<String>, sig: SynSignature) {
+        self.entries.push((label.into(), sig));
+    }
+
+    /// Label of the first signature matching the fingerprint.
+    pub fn lookup(&self, fp: &SynFingerprint) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(_, sig)| sig.matches(fp))
+            .map(|(label, _)| label.as_str())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &SynSignature)> {
+        self.entries
+            .iter()
+            .map(|(label, sig)| (label.as_str(), sig))
+    }
+}
+
+impl FromStr for SignatureDb {
+    type Err = SignatureError;
+
+    fn from_str(s: &str) -> Result<SignatureDb, SignatureError> {
+        let mut db = SignatureDb::new();
+        let mut in_section = false;
+        let mut label = None;
+
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with(';') {
+                continue;
+            }
+            if let Some(section) = line.strip_prefix('[') {
+                in_section = section.trim_end_matches(']') == "tcp:request";
+                label = None;
+                continue;
+            }
+            if !in_section {
+                continue;
+            }
+
+            let at_line = |err| Line(n + 1, Box::new(err));
+            match line.split_once('=').map(|(k, v)| (k.trim(), v.trim())) {
+                Some(("label", v)) => label = Some(v.to_string()),
+                Some(("sig", v)) => {
+                    let sig = v.parse().map_err(at_line)?;
+                    let label = label.clone().ok_or(MissingLabel).map_err(at_line)?;
+                    db.add(label, sig);
+                }
+                _ => {}
+            }
+        }
+
+        Ok(db)
+    }
+}
+
+fn bad_field(field: &'static str, value: &str) -> SignatureError {
+    BadField {
+        field,
+        value: value.to_string(),
+    }
+}
+
+fn num<T: FromStr>(field: &'static str, s: &str) -> Result<T, SignatureError> {
+    s.parse().map_err(|_| bad_field(field, s))
+}
+
+fn any_or_num<T: FromStr>(field: &'static str, s: &str) -> Result<Option<T>, SignatureError> {
+    match s {
+        "*" => Ok(None),
+        _ => num(field, s).map(Some),
+    }
+}
+
+fn write_layout(f: &mut fmt::Formatter, layout: &[OptionLayout]) -> Result<(), fmt::Error> {
+    let mut sep = "";
+    for opt in layout {
+        write!(f, "{}{}", sep, opt)?;
+        sep = ",";
+    }
+    Ok(())
+}
+
+/// Bytes the option takes on the wire.
+fn option_len(opt: &TcpOption) -> usize {
+    match opt {
+        TcpOption::Eol | TcpOption::Nop => 1,
+        TcpOption::Mss(_) => 4,
+        TcpOption::WindowScale(_) => 3,
+        TcpOption::SackPermitted => 2,
+        TcpOption::Sack(blocks) => 2 + blocks.len() * 8,
+        TcpOption::Timestamps { .. } => 10,
+        TcpOption::FastOpen(cookie) => 2 + cookie.len(),
+        // Kind, length and magic
+        TcpOption::FastOpenExp(cookie) => 4 + cookie.len(),
+        TcpOption::Unknown { data, .. } => 2 + data.len(),
+    }
+}
+
+fn option_layout(syn: &SavedSyn) -> Vec<OptionLayout> {
+    let opt_space = syn.tcp.header_len.saturating_sub(20);
+    let mut used = 0;
+
+    syn.options
+        .iter()
+        .map(|opt| {
+            used += option_len(opt);
+            match opt {
+                TcpOption::Eol => OptionLayout::Eol(opt_space.saturating_sub(used)),
+                TcpOption::Nop => OptionLayout::Nop,
+                TcpOption::Mss(_) => OptionLayout::Mss,
+                TcpOption::WindowScale(_) => OptionLayout::WindowScale,
+                TcpOption::SackPermitted => OptionLayout::SackPermitted,
+                TcpOption::Sack(_) => OptionLayout::Sack,
+                TcpOption::Timestamps { .. } => OptionLayout::Timestamps,
+                other => OptionLayout::Other(other.kind()),
+            }
+        })
+        .collect()
+}
@@ -0,0 +1,61 @@
+use std::io;
+use std::os::fd::AsFd;
+
+use crate::sockopt::{getsockopt, setsockopt};
+use crate::{
+    SaveSyn, SavedSyn, SavedSynError, SynTraits, TcpSaveSyn, TcpSaveSynTraits, TcpSavedSyn,
+    TcpSynTraits, TraitKey,
+};
+
+/// Headers and traits saved from the SYN of an accepted connection.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SynInfo {
+    /// Mode the headers were saved in, inherited from the listener.
+    pub mode: SaveSyn,
+    /// Raw headers as returned by `TcpSavedSyn`. Empty if none were saved.
+    pub headers: Vec<u8>,
+    pub traits: SynTraits,
+}
+
+impl SynInfo {
+    /// Decodes the saved headers according to `mode`. Returns `None` if no
+    /// headers were saved.
+    pub fn saved_syn(&self) -> Option<Result<SavedSyn, SavedSynError>> {
+        if self.headers.is_empty() {
+            return None;
+        }
+
+        match self.mode {
+            SaveSyn::WithMac => Some(SavedSyn::parse_with_mac(&self.headers)),
+            _ => Some(SavedSyn::parse(&self.headers)),
+        }
+    }
+}
+
+/// Enables saving of both SYN headers, in the given mode, and SYN traits on
+/// a listener. Leaves header saving off if traits can't be enabled.
+pub fn enable_syn_info<F: AsFd>(listener: &F, mode: SaveSyn) -> io::Result<()> {
+    setsockopt(listener, TcpSaveSyn, &mode)?;
+
+    if let Err(err) = setsockopt(listener, TcpSaveSynTraits, &true) {
+        let _ = setsockopt(listener, TcpSaveSyn, &SaveSyn::Off);
+        return Err(err);
+    }
+    Ok(())
+}
+
+/// Reads saved SYN headers together with the traits under `keys` from an
+/// accepted connection. Both are empty unless enabled on the listener with
+/// `enable_syn_info`.
+pub fn syn_info<F: AsFd>(stream: &F, keys: &[TraitKey]) -> io::Result<SynInfo> {
+    let mode = getsockopt(stream, TcpSaveSyn)?;
+    let traits = getsockopt(stream, TcpSynTraits(keys))?.into();
+    // Reading saved headers releases them, so do it last
+    let headers = getsockopt(stream, TcpSavedSyn)?;
+
+    Ok(SynInfo {
+        mode,
+        headers,
+        traits,
+    })
+}
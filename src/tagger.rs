@@ -0,0 +1,323 @@
+//! Control plane for the bundled trait tagger program, `bpf/tagger.bpf.c`
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use std::os::fd::{AsFd, BorrowedFd, OwnedFd};
+use std::path::Path;
+
+use crate::bpf_sys::{
+    self, check_map, invalid, BPF_F_NO_PREALLOC, BPF_MAP_TYPE_HASH, BPF_MAP_TYPE_LPM_TRIE,
+};
+use crate::{PktTrait, MAX_KEY};
+
+/// Most traits a single rule can set.
+pub const MAX_RULE_TRAITS: usize = 8;
+/// Capacity of each rule map.
+pub const MAX_TAG_RULES: u32 = 1024;
+
+/// Names of the rule maps in the tagger object.
+pub const TAGGER_FLOWS_MAP: &str = "tagger_flows";
+pub const TAGGER_PREFIXES_MAP: &str = "tagger_prefixes";
+
+/// Packets a tagger rule applies to. A packet matching a flow rule is tagged
+/// by it alone, otherwise the longest matching prefix rule applies.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TagMatch {
+    /// Exact 5-tuple. Ports are matched only for TCP and UDP and must be
+    /// zero for other protocols. IPv6 extension headers are not walked, so
+    /// `proto` is the next header of the fixed header.
+    Flow {
+        proto: u8,
+        src: SocketAddr,
+        dst: SocketAddr,
+    },
+    /// Source address prefix. IPv4 prefixes are stored IPv4-mapped, so
+    /// IPv6 rules for `::ffff:0:0/96` and longer read back as IPv4.
+    SrcPrefix { addr: IpAddr, len: u8 },
+}
+
+/// `struct flow_key`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct FlowKey {
+    saddr: [u8; 16],
+    daddr: [u8; 16],
+    sport: u16,
+    dport: u16,
+    proto: u8,
+    _pad: [u8; 3],
+}
+
+/// `struct prefix_key`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct PrefixKey {
+    prefixlen: u32,
+    saddr: [u8; 16],
+}
+
+/// `struct tag_rule`
+#[repr(C)]
+#[derive(Clone, Copy, Debug, Default)]
+struct TagRule {
+    cnt: u32,
+    _pad: u32,
+    traits: [PktTrait; MAX_RULE_TRAITS],
+}
+
+fn mapped(addr: IpAddr) -> [u8; 16] {
+    match addr {
+        IpAddr::V4(addr) => addr.to_ipv6_mapped().octets(),
+        IpAddr::V6(addr) => addr.octets(),
+    }
+}
+
+fn unmapped(addr: [u8; 16]) -> IpAddr {
+    let addr = Ipv6Addr::from(addr);
+    match addr.to_ipv4_mapped() {
+        Some(addr) => IpAddr::V4(addr),
+        None => IpAddr::V6(addr),
+    }
+}
+
+impl FlowKey {
+    fn new(proto: u8, src: SocketAddr, dst: SocketAddr) -> io::Result<FlowKey> {
+        if src.is_ipv4() != dst.is_ipv4() {
+            return Err(invalid(format!(
+                "Flow addresses {} and {} are of different families",
+                src, dst
+            )));
+        }
+        let has_ports = matches!(proto as i32, libc::IPPROTO_TCP | libc::IPPROTO_UDP);
+        if !has_ports && (src.port() != 0 || dst.port() != 0) {
+            return Err(invalid(format!(
+                "Ports can't be matched for protocol {}",
+                proto
+            )));
+        }
+
+        Ok(FlowKey {
+            saddr: mapped(src.ip()),
+            daddr: mapped(dst.ip()),
+            sport: src.port().to_be(),
+            dport: dst.port().to_be(),
+            proto,
+            ..Default::default()
+        })
+    }
+
+    fn to_match(self) -> TagMatch {
+        let sockaddr = |addr, port: u16| match unmapped(addr) {
+            IpAddr::V4(addr) => SocketAddr::V4(SocketAddrV4::new(addr, u16::from_be(port))),
+            IpAddr::V6(addr) => SocketAddr::V6(SocketAddrV6::new(addr, u16::from_be(port), 0, 0)),
+        };
+
+        TagMatch::Flow {
+            proto: self.proto,
+            src: sockaddr(self.saddr, self.sport),
+            dst: sockaddr(self.daddr, self.dport),
+        }
+    }
+}
+
+impl PrefixKey {
+    fn new(addr: IpAddr, len: u8) -> io::Result<PrefixKey> {
+        let (prefixlen, max) = match addr {
+            IpAddr::V4(_) => (u32::from(len) + 96, 32),
+            IpAddr::V6(_) => (u32::from(len), 128),
+        };
+        if len > max {
+            return Err(invalid(format!(
+                "Prefix length {} out of range for {}",
+                len, addr
+            )));
+        }
+
+        // Mask host bits so that equal prefixes make equal keys
+        let mask = u128::MAX.checked_shl(128 - prefixlen).unwrap_or(0);
+        let saddr = (u128::from_be_bytes(mapped(addr)) & mask).to_be_bytes();
+
+        Ok(PrefixKey { prefixlen, saddr })
+    }
+
+    fn to_match(self) -> TagMatch {
+        match unmapped(self.saddr) {
+            IpAddr::V4(addr) if self.prefixlen >= 96 => TagMatch::SrcPrefix {
+                addr: IpAddr::V4(addr),
+                len: (self.prefixlen - 96) as u8,
+            },
+            // Prefix shorter than the mapped range, e.g. ::/0
+            IpAddr::V4(_) => TagMatch::SrcPrefix {
+                addr: IpAddr::V6(Ipv6Addr::from(self.saddr)),
+                len: self.prefixlen as u8,
+            },
+            addr => TagMatch::SrcPrefix {
+                addr,
+                len: self.prefixlen as u8,
+            },
+        }
+    }
+}
+
+impl TagRule {
+    fn new(traits: &[PktTrait]) -> io::Result<TagRule> {
+        if traits.is_empty() || traits.len() > MAX_RULE_TRAITS {
+            return Err(invalid(format!(
+                "Rule must set 1..={} traits, got {}",
+                MAX_RULE_TRAITS,
+                traits.len()
+            )));
+        }
+
+        let mut rule = TagRule {
+            cnt: traits.len() as u32,
+            ..Default::default()
+        };
+        for (dst, src) in rule.traits.iter_mut().zip(traits) {
+            if src.key > MAX_KEY || src.value().is_none() {
+                return Err(invalid(format!(
+                    "Trait {} has bad key or value length {}",
+                    src.key, src.len
+                )));
+            }
+            *dst = PktTrait {
+                key: src.key,
+                len: src.len,
+                val: src.val,
+                ..Default::default()
+            };
+        }
+
+        Ok(rule)
+    }
+
+    fn traits(&self) -> Vec<PktTrait> {
+        let cnt = (self.cnt as usize).min(MAX_RULE_TRAITS);
+        self.traits[..cnt].to_vec()
+    }
+}
+
+/// Manages the rules of a trait tagger at runtime.
+///
+/// The tagger program comes in two flavours sharing the rule maps,
+/// `tagger_sk` for socket filters and `tagger_tc` for tc hooks. Load
+/// `bpf/tagger.bpf.o` with a loader of choice, then hand its maps to
+/// [`Tagger::new`]. Alternatively create the maps up front with
+/// [`Tagger::create`] and have the loader reuse them.
+///
+/// Rule changes take effect for the next packet.
+#[derive(Debug)]
+pub struct Tagger {
+    flows: OwnedFd,
+    prefixes: OwnedFd,
+}
+
+impl Tagger {
+    /// Takes the rule maps of a loaded tagger object. File descriptors are
+    /// duplicated.
+    pub fn new(flows: BorrowedFd<'_>, prefixes: BorrowedFd<'_>) -> io::Result<Tagger> {
+        check_map(
+            flows,
+            TAGGER_FLOWS_MAP,
+            BPF_MAP_TYPE_HASH,
+            mem::size_of::<FlowKey>(),
+            mem::size_of::<TagRule>(),
+        )?;
+        check_map(
+            prefixes,
+            TAGGER_PREFIXES_MAP,
+            BPF_MAP_TYPE_LPM_TRIE,
+            mem::size_of::<PrefixKey>(),
+            mem::size_of::<TagRule>(),
+        )?;
+
+        Ok(Tagger {
+            flows: flows.try_clone_to_owned()?,
+            prefixes: prefixes.try_clone_to_owned()?,
+        })
+    }
+
+    /// Creates empty rule maps, to be reused when loading the program.
+    pub fn create() -> io::Result<Tagger> {
+        let flows = bpf_sys::map_create(
+            BPF_MAP_TYPE_HASH,
+            mem::size_of::<FlowKey>(),
+            mem::size_of::<TagRule>(),
+            MAX_TAG_RULES,
+            0,
+        )?;
+        let prefixes = bpf_sys::map_create(
+            BPF_MAP_TYPE_LPM_TRIE,
+            mem::size_of::<PrefixKey>(),
+            mem::size_of::<TagRule>(),
+            MAX_TAG_RULES,
+            BPF_F_NO_PREALLOC,
+        )?;
+
+        Ok(Tagger { flows, prefixes })
+    }
+
+    /// Opens rule maps pinned in bpffs under their own names in `dir`.
+    pub fn open_pinned<P: AsRef<Path>>(dir: P) -> io::Result<Tagger> {
+        let flows = bpf_sys::obj_get(&dir.as_ref().join(TAGGER_FLOWS_MAP))?;
+        let prefixes = bpf_sys::obj_get(&dir.as_ref().join(TAGGER_PREFIXES_MAP))?;
+
+        Tagger::new(flows.as_fd(), prefixes.as_fd())
+    }
+
+    pub fn flows_map(&self) -> BorrowedFd<'_> {
+        self.flows.as_fd()
+    }
+
+    pub fn prefixes_map(&self) -> BorrowedFd<'_> {
+        self.prefixes.as_fd()
+    }
+
+    /// Adds a rule, or replaces the traits of an existing one. Traits must
+    /// carry values, as made with `PktTrait::from((key, val))`.
+    pub fn add_rule(&self, on: &TagMatch, traits: &[PktTrait]) -> io::Result<()> {
+        let rule = TagRule::new(traits)?;
+
+        match *on {
+            TagMatch::Flow { proto, src, dst } => {
+                bpf_sys::map_update(self.flows.as_fd(), &FlowKey::new(proto, src, dst)?, &rule)
+            }
+            TagMatch::SrcPrefix { addr, len } => {
+                bpf_sys::map_update(self.prefixes.as_fd(), &PrefixKey::new(addr, len)?, &rule)
+            }
+        }
+    }
+
+    /// Removes a rule. Yields `false` if there was no such rule.
+    pub fn remove_rule(&self, on: &TagMatch) -> io::Result<bool> {
+        match *on {
+            TagMatch::Flow { proto, src, dst } => {
+                bpf_sys::map_delete(self.flows.as_fd(), &FlowKey::new(proto, src, dst)?)
+            }
+            TagMatch::SrcPrefix { addr, len } => {
+                bpf_sys::map_delete(self.prefixes.as_fd(), &PrefixKey::new(addr, len)?)
+            }
+        }
+    }
+
+    /// Lists flow rules followed by prefix rules. Rules changed while
+    /// listing may or may not show up.
+    pub fn list_rules(&self) -> io::Result<Vec<(TagMatch, Vec<PktTrait>)>> {
+        let mut rules = vec![];
+
+        for key in bpf_sys::map_keys::<FlowKey>(self.flows.as_fd())? {
+            let mut rule = TagRule::default();
+            if bpf_sys::map_lookup(self.flows.as_fd(), &key, &mut rule)? {
+                rules.push((key.to_match(), rule.traits()));
+            }
+        }
+        for key in bpf_sys::map_keys::<PrefixKey>(self.prefixes.as_fd())? {
+            let mut rule = TagRule::default();
+            if bpf_sys::map_lookup(self.prefixes.as_fd(), &key, &mut rule)? {
+                rules.push((key.to_match(), rule.traits()));
+            }
+        }
+
+        Ok(rules)
+    }
+}
@@ -1,37 +1,105 @@
-use nix::libc;
-use nix::sys::socket::sockopt;
-use nix::{getsockopt_impl, setsockopt_impl};
+use std::io;
+use std::os::fd::AsFd;
 
-use crate::sockopt_ext;
+use crate::sockopt::{self, nix_getsockopt, nix_setsockopt};
+
+/// Which headers of the SYN the kernel saves.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub enum SaveSyn {
+    #[default]
+    Off,
+    /// IP and TCP headers.
+    NetworkAndTransport,
+    /// Link-layer header followed by IP and TCP headers. Parse with
+    /// `SavedSyn::parse_with_mac`.
+    WithMac,
+}
+
+impl TryFrom<libc::c_int> for SaveSyn {
+    type Error = io::Error;
+
+    fn try_from(val: libc::c_int) -> Result<SaveSyn, Self::Error> {
+        match val {
+            0 => Ok(SaveSyn::Off),
+            1 => Ok(SaveSyn::NetworkAndTransport),
+            2 => Ok(SaveSyn::WithMac),
+            _ => Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("Unknown TCP_SAVE_SYN mode {}", val),
+            )),
+        }
+    }
+}
+
+impl From<SaveSyn> for libc::c_int {
+    fn from(mode: SaveSyn) -> Self {
+        match mode {
+            SaveSyn::Off => 0,
+            SaveSyn::NetworkAndTransport => 1,
+            SaveSyn::WithMac => 2,
+        }
+    }
+}
 
-#[allow(dead_code)]
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct TcpSaveSyn;
 
-getsockopt_impl!(
-    TcpSaveSyn,
-    libc::SOL_TCP,
-    libc::TCP_SAVE_SYN,
-    bool,
-    sockopt::GetBool
-);
-
-setsockopt_impl!(
-    TcpSaveSyn,
-    libc::SOL_TCP,
-    libc::TCP_SAVE_SYN,
-    bool,
-    sockopt::SetBool
-);
-
-#[allow(dead_code)]
+impl sockopt::GetSockOpt for TcpSaveSyn {
+    type Val = SaveSyn;
+
+    fn get<F: AsFd>(&self, fd: &F) -> io::Result<SaveSyn> {
+        sockopt::get_int(fd.as_fd(), libc::SOL_TCP, libc::TCP_SAVE_SYN)?.try_into()
+    }
+}
+
+impl sockopt::SetSockOpt for TcpSaveSyn {
+    type Val = SaveSyn;
+
+    fn set<F: AsFd>(&self, fd: &F, val: &SaveSyn) -> io::Result<()> {
+        sockopt::set_int(fd.as_fd(), libc::SOL_TCP, libc::TCP_SAVE_SYN, (*val).into())
+    }
+}
+
+nix_getsockopt!(TcpSaveSyn);
+nix_setsockopt!(TcpSaveSyn);
+
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
 pub struct TcpSavedSyn;
 
-getsockopt_impl!(
-    TcpSavedSyn,
-    libc::SOL_TCP,
-    libc::TCP_SAVED_SYN,
-    Vec<u8>,
-    sockopt_ext::GetBytes<[u8; 64]>
-);
+impl TcpSavedSyn {
+    /// Fits an IPv4 SYN with typical options. Kernel reports the required
+    /// length when the buffer is too short, in which case we retry.
+    const INITIAL_LEN: usize = 64;
+}
+
+impl sockopt::GetSockOpt for TcpSavedSyn {
+    type Val = Vec<u8>;
+
+    fn get<F: AsFd>(&self, fd: &F) -> io::Result<Vec<u8>> {
+        let mut buf = vec![0u8; Self::INITIAL_LEN];
+
+        loop {
+            let mut len = buf.len();
+            let res = sockopt::get_raw(
+                fd.as_fd(),
+                libc::SOL_TCP,
+                libc::TCP_SAVED_SYN,
+                buf.as_mut_ptr().cast(),
+                &mut len,
+            );
+
+            match res {
+                Ok(()) => {
+                    buf.truncate(len);
+                    return Ok(buf);
+                }
+                Err(err) if err.raw_os_error() == Some(libc::EINVAL) && len > buf.len() => {
+                    buf.resize(len, 0);
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+nix_getsockopt!(TcpSavedSyn);
@@ -1,14 +1,15 @@
-use nix::errno::Errno;
-use nix::libc::{self, c_int, c_void, socklen_t};
-use nix::sys::socket::sockopt;
-use nix::{getsockopt_impl, setsockopt_impl};
+use libc::{c_int, c_void};
+use std::io;
 use std::mem;
-use std::os::fd::{AsFd, AsRawFd};
+use std::os::fd::AsFd;
+
+use crate::sockopt::{self, bool_sockopt, nix_getsockopt, GetSockOpt};
+use crate::TraitValue;
 
 pub const TCP_SAVE_SYN_TRAITS: c_int = 44;
 pub const TCP_SYN_TRAITS: c_int = 45;
 
-pub type TraitKey = u8;
+pub use skb_traits_common::{Key, TraitKey, TraitVal, TraitWidth, MAX_KEY};
 
 macro_rules! bits_to_bytes {
     ($bits:expr) => { $bits / 8 };
@@ -25,6 +26,18 @@ pub struct PktTrait {
     pub val: [u64; 2],
 }
 
+impl PktTrait {
+    /// Returns the trait value, or `None` if the trait is absent.
+    pub fn value(&self) -> Option<TraitValue> {
+        match self.len {
+            2 => Some(TraitValue::U16(self.val[0] as u16)),
+            4 => Some(TraitValue::U32(self.val[0] as u32)),
+            8 => Some(TraitValue::U64(self.val[0])),
+            _ => None,
+        }
+    }
+}
+
 impl From<TraitKey> for PktTrait {
     fn from(key: TraitKey) -> Self {
         PktTrait {
@@ -67,33 +80,15 @@ impl From<(TraitKey, u64)> for PktTrait {
     }
 }
 
-#[allow(dead_code)]
-#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
-pub struct TcpSaveSynTraits;
-
-getsockopt_impl!(
-    TcpSaveSynTraits,
-    libc::SOL_TCP,
-    TCP_SAVE_SYN_TRAITS,
-    bool,
-    sockopt::GetBool
-);
-
-setsockopt_impl!(
-    TcpSaveSynTraits,
-    libc::SOL_TCP,
-    TCP_SAVE_SYN_TRAITS,
-    bool,
-    sockopt::SetBool
-);
+bool_sockopt!(TcpSaveSynTraits, libc::SOL_TCP, TCP_SAVE_SYN_TRAITS);
 
 #[derive(Clone, Copy)]
 pub struct TcpSynTraits<'a>(pub &'a [TraitKey]);
 
-impl nix::sys::socket::GetSockOpt for TcpSynTraits<'_> {
+impl sockopt::GetSockOpt for TcpSynTraits<'_> {
     type Val = Vec<PktTrait>;
 
-    fn get<F: AsFd>(&self, fd: &F) -> nix::Result<Vec<PktTrait>> {
+    fn get<F: AsFd>(&self, fd: &F) -> io::Result<Vec<PktTrait>> {
         let n = self.0.len();
         let sz = n * mem::size_of::<PktTrait>();
         let mut traits: Vec<PktTrait> = Vec::with_capacity(n);
@@ -106,31 +101,75 @@ impl nix::sys::socket::GetSockOpt for TcpSynTraits<'_> {
         }
 
         let ffi_ptr = traits.as_mut_ptr() as *mut c_void;
-        let mut ffi_len = sz as socklen_t;
-        let res = unsafe {
-            libc::getsockopt(
-                fd.as_fd().as_raw_fd(),
-                libc::SOL_TCP,
-                TCP_SYN_TRAITS,
-                ffi_ptr,
-                &mut ffi_len,
-            )
-        };
-
-        if let Err(err) = Errno::result(res) {
-            if err != Errno::EIO {
+        let mut ffi_len = sz;
+        let res = sockopt::get_raw(
+            fd.as_fd(),
+            libc::SOL_TCP,
+            TCP_SYN_TRAITS,
+            ffi_ptr,
+            &mut ffi_len,
+        );
+
+        if let Err(err) = res {
+            if err.raw_os_error() != Some(libc::EIO) {
                 return Err(err);
             }
         }
 
-        match ffi_len as usize {
+        match ffi_len {
             0 => Ok(vec![]),
             len if len == sz => Ok(traits),
-            _ => Err(Errno::EMSGSIZE),
+            _ => Err(io::Error::from_raw_os_error(libc::EMSGSIZE)),
         }
     }
 }
 
+nix_getsockopt!(TcpSynTraits<'_>);
+
+/// All traits present on the SYN of an accepted connection.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SynTraits(Vec<PktTrait>);
+
+impl SynTraits {
+    /// Reads back all traits saved from the SYN. Requires `TcpSaveSynTraits`
+    /// to have been enabled on the listener. Yields an empty set otherwise.
+    pub fn from_socket<F: AsFd>(fd: &F) -> io::Result<SynTraits> {
+        let keys: Vec<TraitKey> = (0..=MAX_KEY).collect();
+        let traits = TcpSynTraits(&keys).get(fd)?;
+
+        Ok(traits.into())
+    }
+
+    pub fn get(&self, key: TraitKey) -> Option<TraitValue> {
+        self.0.iter().find(|t| t.key == key).and_then(PktTrait::value)
+    }
+
+    /// Typed lookup. Yields `None` also if the value width doesn't match.
+    pub fn get_typed<T: TraitVal>(&self, key: Key<T>) -> Option<T> {
+        self.get(key.key()).and_then(|v| v.to_typed())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &PktTrait> {
+        self.0.iter()
+    }
+}
+
+impl AsRef<[PktTrait]> for SynTraits {
+    fn as_ref(&self) -> &[PktTrait] {
+        &self.0
+    }
+}
+
+impl From<Vec<PktTrait>> for SynTraits {
+    fn from(traits: Vec<PktTrait>) -> Self {
+        SynTraits(traits.into_iter().filter(|t| t.len != 0).collect())
+    }
+}
+
 // TODO: Merge it with `TcpSynTraits`
 #[derive(Clone, Debug)]
 pub struct TcpSynTraitsSet<T>(::std::marker::PhantomData<T>);
@@ -141,6 +180,26 @@ impl<T> Default for TcpSynTraitsSet<T> {
     }
 }
 
+impl<T> sockopt::SetSockOpt for TcpSynTraitsSet<T>
+where
+    T: AsRef<[PktTrait]> + Clone,
+{
+    type Val = T;
+
+    fn set<F: AsFd>(&self, fd: &F, val: &T) -> io::Result<()> {
+        let traits = val.as_ref();
+
+        sockopt::set_raw(
+            fd.as_fd(),
+            libc::SOL_TCP,
+            TCP_SYN_TRAITS,
+            traits.as_ptr().cast(),
+            mem::size_of_val(traits),
+        )
+    }
+}
+
+#[cfg(feature = "nix")]
 impl<T> nix::sys::socket::SetSockOpt for TcpSynTraitsSet<T>
 where
     T: AsRef<[PktTrait]> + Clone,
@@ -148,15 +207,6 @@ where
     type Val = T;
 
     fn set<F: AsFd>(&self, fd: &F, val: &T) -> nix::Result<()> {
-        let res = unsafe {
-            libc::setsockopt(
-                fd.as_fd().as_raw_fd(),
-                libc::SOL_TCP,
-                TCP_SYN_TRAITS,
-                val.as_ref().as_ptr().cast(),
-                mem::size_of_val(val) as libc::socklen_t,
-            )
-        };
-        Errno::result(res).map(drop)
+        sockopt::SetSockOpt::set(self, fd, val).map_err(sockopt::errno)
     }
 }
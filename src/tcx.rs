@@ -0,0 +1,81 @@
+use std::ffi::CString;
+use std::io;
+use std::os::fd::{AsFd, BorrowedFd};
+
+use crate::bpf_sys::{self, BPF_PROG_TYPE_SCHED_CLS, BPF_TCX_EGRESS, BPF_TCX_INGRESS};
+use crate::{BpfLink, LinkTarget};
+
+/// Traffic direction on a network device.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum TcDirection {
+    Ingress,
+    Egress,
+}
+
+impl TcDirection {
+    fn attach_type(self) -> u32 {
+        match self {
+            TcDirection::Ingress => BPF_TCX_INGRESS,
+            TcDirection::Egress => BPF_TCX_EGRESS,
+        }
+    }
+}
+
+/// BPF program checked to be of a type which can be attached to tc hooks.
+#[derive(Clone, Copy, Debug)]
+pub struct TcProg<'fd>(BorrowedFd<'fd>);
+
+impl<'fd> TcProg<'fd> {
+    /// Fails with `InvalidInput` if the program is not a tc classifier
+    /// (`SEC("tc")`) or not a program at all.
+    pub fn new(prog: BorrowedFd<'fd>) -> io::Result<TcProg<'fd>> {
+        let info = bpf_sys::prog_info(prog)?;
+        if info.prog_type != BPF_PROG_TYPE_SCHED_CLS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "BPF program type {} can't be attached to tc, expected classifier ({})",
+                    info.prog_type, BPF_PROG_TYPE_SCHED_CLS
+                ),
+            ));
+        }
+
+        Ok(TcProg(prog))
+    }
+}
+
+impl AsFd for TcProg<'_> {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.0
+    }
+}
+
+/// Looks up a network device index by name.
+pub fn if_index(name: &str) -> io::Result<u32> {
+    let name =
+        CString::new(name).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+
+    match unsafe { libc::if_nametoindex(name.as_ptr()) } {
+        0 => Err(io::Error::last_os_error()),
+        ifindex => Ok(ifindex),
+    }
+}
+
+/// Attaches a tc program to a network device with a tcx link, which needs
+/// Linux 6.6 or later. Programs attached at ingress run before socket
+/// lookup, so traits they set reach every socket on the host.
+///
+/// The program stays attached until the returned link is dropped, or
+/// longer if pinned. Programs attached by others are left in place.
+pub fn attach_tc<P: AsFd>(prog: P, ifindex: u32, dir: TcDirection) -> io::Result<BpfLink> {
+    let prog = TcProg::new(prog.as_fd())?;
+    let link = bpf_sys::link_create(prog.as_fd(), ifindex, dir.attach_type())?;
+
+    Ok(BpfLink::new(link, LinkTarget::Tc { ifindex, dir }))
+}
+
+/// Lists ids of programs attached to a network device with tcx, in the
+/// order they run.
+pub fn tc_prog_ids(ifindex: u32, dir: TcDirection) -> io::Result<Vec<u32>> {
+    bpf_sys::prog_query(ifindex, dir.attach_type())
+}
@@ -0,0 +1,108 @@
+use std::io;
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_stream::Stream;
+use tonic::transport::server::{Connected, TcpConnectInfo};
+
+use crate::sockopt::setsockopt;
+use crate::{SynTraits, TcpSaveSynTraits};
+
+/// Stream of incoming connections for `Server::serve_with_incoming`. Each
+/// connection carries the traits saved from its SYN.
+pub struct SynTraitsIncoming {
+    listener: TcpListener,
+}
+
+impl SynTraitsIncoming {
+    /// Enables `TcpSaveSynTraits` on the listener.
+    pub fn new(listener: TcpListener) -> io::Result<SynTraitsIncoming> {
+        setsockopt(&listener, TcpSaveSynTraits, &true)?;
+
+        Ok(SynTraitsIncoming { listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+}
+
+impl Stream for SynTraitsIncoming {
+    type Item = io::Result<SynTraitsStream>;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        match self.listener.poll_accept(cx) {
+            Poll::Ready(Ok((stream, _))) => Poll::Ready(Some(SynTraitsStream::new(stream))),
+            Poll::Ready(Err(err)) => Poll::Ready(Some(Err(err))),
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+/// Accepted TCP stream together with the traits saved from its SYN.
+#[derive(Debug)]
+pub struct SynTraitsStream {
+    inner: TcpStream,
+    traits: SynTraits,
+}
+
+impl SynTraitsStream {
+    pub fn new(inner: TcpStream) -> io::Result<SynTraitsStream> {
+        let traits = SynTraits::from_socket(&inner)?;
+
+        Ok(SynTraitsStream { inner, traits })
+    }
+
+    pub fn syn_traits(&self) -> &SynTraits {
+        &self.traits
+    }
+}
+
+/// Connection info available to interceptors and handlers through
+/// `Request::extensions()`.
+#[derive(Clone, Debug)]
+pub struct SynTraitsConnectInfo {
+    pub tcp: TcpConnectInfo,
+    pub traits: SynTraits,
+}
+
+impl Connected for SynTraitsStream {
+    type ConnectInfo = SynTraitsConnectInfo;
+
+    fn connect_info(&self) -> Self::ConnectInfo {
+        SynTraitsConnectInfo {
+            tcp: self.inner.connect_info(),
+            traits: self.traits.clone(),
+        }
+    }
+}
+
+impl AsyncRead for SynTraitsStream {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_read(cx, buf)
+    }
+}
+
+impl AsyncWrite for SynTraitsStream {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
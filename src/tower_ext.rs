@@ -0,0 +1,109 @@
+use http::{Extensions, Request};
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{SynTraits, TraitAction, TraitPolicy};
+
+type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Applies a `TraitPolicy` to requests based on the SYN traits of the
+/// connection they arrived on.
+///
+/// Traits are looked up in request extensions, where `WithSynTraits`, axum's
+/// `ConnectInfo<SynTraits>`, or tonic's `SynTraitsConnectInfo` put them.
+/// Requests without traits are evaluated against an empty set.
+#[derive(Clone, Debug)]
+pub struct TraitPolicyLayer {
+    policy: Arc<TraitPolicy>,
+}
+
+impl TraitPolicyLayer {
+    pub fn new(policy: TraitPolicy) -> TraitPolicyLayer {
+        TraitPolicyLayer {
+            policy: Arc::new(policy),
+        }
+    }
+}
+
+impl<S> Layer<S> for TraitPolicyLayer {
+    type Service = TraitPolicyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        TraitPolicyService {
+            inner,
+            policy: self.policy.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct TraitPolicyService<S> {
+    inner: S,
+    policy: Arc<TraitPolicy>,
+}
+
+/// Route selected by the policy, inserted into request extensions.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraitRoute(pub String);
+
+/// Error returned for requests denied by the policy.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PolicyDenied;
+
+impl std::error::Error for PolicyDenied {}
+
+impl fmt::Display for PolicyDenied {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(f, "Request denied by trait policy")
+    }
+}
+
+fn syn_traits(ext: &Extensions) -> Option<&SynTraits> {
+    if let Some(traits) = ext.get::<SynTraits>() {
+        return Some(traits);
+    }
+    #[cfg(feature = "axum")]
+    if let Some(info) = ext.get::<axum::extract::ConnectInfo<SynTraits>>() {
+        return Some(&info.0);
+    }
+    #[cfg(feature = "tonic")]
+    if let Some(info) = ext.get::<crate::SynTraitsConnectInfo>() {
+        return Some(&info.traits);
+    }
+    None
+}
+
+impl<S, B> Service<Request<B>> for TraitPolicyService<S>
+where
+    S: Service<Request<B>>,
+    S::Error: Into<BoxError>,
+    S::Future: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = Pin<Box<dyn Future<Output = Result<S::Response, BoxError>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        let traits = syn_traits(req.extensions()).map_or(&[][..], |t| t.as_ref());
+
+        match self.policy.evaluate(traits) {
+            TraitAction::Allow => {}
+            TraitAction::Deny => return Box::pin(async { Err(PolicyDenied.into()) }),
+            TraitAction::Route(route) => {
+                req.extensions_mut().insert(TraitRoute(route.clone()));
+            }
+        }
+
+        let fut = self.inner.call(req);
+        Box::pin(async move { fut.await.map_err(Into::into) })
+    }
+}
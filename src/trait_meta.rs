@@ -0,0 +1,121 @@
+//! Compact trait record handed from XDP to tc in the metadata area.
+//!
+//! XDP programs can't set skb traits, as there is no skb yet. Instead they
+//! write a record in front of the packet with `bpf_xdp_adjust_meta`, and a
+//! tc program at ingress turns it into traits. The C side of the format is
+//! in `bpf/trait_meta.h`.
+//!
+//! Record layout, in host byte order, starting at `data_meta`:
+//!
+//! ```text
+//! u16 magic  u8 cnt  u8 reserved
+//! cnt times: u8 key  u8 len  u16 reserved  u32 val_lo  u32 val_hi
+//! ```
+use std::io;
+use std::os::fd::{AsFd, OwnedFd};
+
+use crate::{attach_tc, ebpf, BpfLink, TcDirection, TraitKey, TraitValue};
+
+pub const TRAIT_META_MAGIC: u16 = 0x5452;
+/// Most traits a record can carry. `bpf_xdp_adjust_meta` grants at most 32
+/// bytes, room for the header and two entries.
+pub const MAX_META_TRAITS: usize = 2;
+
+pub(crate) const META_HDR_LEN: usize = 4;
+pub(crate) const META_ENTRY_LEN: usize = 12;
+
+pub(crate) fn check_meta_traits(traits: &[(TraitKey, TraitValue)]) -> io::Result<()> {
+    if traits.len() > MAX_META_TRAITS {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Record can carry up to {} traits, got {}",
+                MAX_META_TRAITS,
+                traits.len()
+            ),
+        ));
+    }
+
+    ebpf::check_keys(traits)
+}
+
+/// Builds a record, as an XDP program would write it.
+pub fn encode_trait_meta(traits: &[(TraitKey, TraitValue)]) -> io::Result<Vec<u8>> {
+    check_meta_traits(traits)?;
+
+    let mut meta = Vec::with_capacity(META_HDR_LEN + traits.len() * META_ENTRY_LEN);
+    meta.extend(TRAIT_META_MAGIC.to_ne_bytes());
+    meta.extend([traits.len() as u8, 0]);
+    for (key, val) in traits {
+        let len = val.width().bytes() as u8;
+        let val = val.to_u64();
+
+        meta.extend([*key, len, 0, 0]);
+        meta.extend((val as u32).to_ne_bytes());
+        meta.extend(((val >> 32) as u32).to_ne_bytes());
+    }
+
+    Ok(meta)
+}
+
+/// Reads a record from the start of the metadata area. Yields `None` if
+/// there is no record. Entries of unknown length are skipped, same as the
+/// tc side does.
+pub fn decode_trait_meta(meta: &[u8]) -> Option<Vec<(TraitKey, TraitValue)>> {
+    let hdr = meta.get(..META_HDR_LEN)?;
+    if u16::from_ne_bytes([hdr[0], hdr[1]]) != TRAIT_META_MAGIC {
+        return None;
+    }
+    let cnt = (hdr[2] as usize).min(MAX_META_TRAITS);
+    let entries = meta.get(META_HDR_LEN..META_HDR_LEN + cnt * META_ENTRY_LEN)?;
+
+    let traits = entries
+        .chunks(META_ENTRY_LEN)
+        .filter_map(|ent| {
+            let lo = u32::from_ne_bytes([ent[4], ent[5], ent[6], ent[7]]);
+            let hi = u32::from_ne_bytes([ent[8], ent[9], ent[10], ent[11]]);
+            let val = (u64::from(hi) << 32) | u64::from(lo);
+
+            let val = match ent[1] {
+                2 => TraitValue::U16(val as u16),
+                4 => TraitValue::U32(val as u32),
+                8 => TraitValue::U64(val),
+                _ => return None,
+            };
+            Some((ent[0], val))
+        })
+        .collect();
+
+    Some(traits)
+}
+
+/// tc program converting metadata records into skb traits, attached at
+/// ingress of a network device. Pair it with an XDP program on the same
+/// device which writes the records, e.g. one built with `bpf/trait_meta.h`.
+///
+/// Packets without a record pass untouched. Detached on drop.
+#[derive(Debug)]
+pub struct MetaBridge {
+    link: BpfLink,
+    _prog: OwnedFd,
+}
+
+impl MetaBridge {
+    /// Loads the converter and attaches it to the device. Needs a kernel
+    /// with trait kfuncs and tcx links.
+    pub fn attach(ifindex: u32) -> io::Result<MetaBridge> {
+        let prog = ebpf::load_meta_to_traits()?;
+        let link = attach_tc(prog.as_fd(), ifindex, TcDirection::Ingress)?;
+
+        Ok(MetaBridge { link, _prog: prog })
+    }
+
+    pub fn link(&self) -> &BpfLink {
+        &self.link
+    }
+
+    /// Detaches the converter, reporting failure unlike drop.
+    pub fn detach(self) -> io::Result<()> {
+        self.link.detach()
+    }
+}
@@ -0,0 +1,75 @@
+/// Declarative rules over packet traits
+use crate::{PktTrait, TraitKey};
+
+/// Condition over keys and values of a trait set. Traits of zero length are
+/// treated as absent.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraitMatch {
+    Present(TraitKey),
+    Absent(TraitKey),
+    OneOf(TraitKey, Vec<u64>),
+    Not(Box<TraitMatch>),
+    All(Vec<TraitMatch>),
+    Any(Vec<TraitMatch>),
+}
+
+impl TraitMatch {
+    pub fn matches(&self, traits: &[PktTrait]) -> bool {
+        match self {
+            TraitMatch::Present(key) => lookup(traits, *key).is_some(),
+            TraitMatch::Absent(key) => lookup(traits, *key).is_none(),
+            TraitMatch::OneOf(key, vals) => lookup(traits, *key).is_some_and(|v| vals.contains(&v)),
+            TraitMatch::Not(m) => !m.matches(traits),
+            TraitMatch::All(ms) => ms.iter().all(|m| m.matches(traits)),
+            TraitMatch::Any(ms) => ms.iter().any(|m| m.matches(traits)),
+        }
+    }
+}
+
+fn lookup(traits: &[PktTrait], key: TraitKey) -> Option<u64> {
+    traits
+        .iter()
+        .find(|t| t.key == key && t.len != 0)
+        .map(|t| t.val[0])
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TraitAction {
+    Allow,
+    Deny,
+    Route(String),
+}
+
+/// Ordered list of rules. First rule which matches decides the action. When
+/// none does, the default action applies.
+#[derive(Clone, Debug)]
+pub struct TraitPolicy {
+    rules: Vec<(TraitMatch, TraitAction)>,
+    default: TraitAction,
+}
+
+impl TraitPolicy {
+    pub fn new(default: TraitAction) -> TraitPolicy {
+        TraitPolicy {
+            rules: vec![],
+            default,
+        }
+    }
+
+    pub fn rule(mut self, m: TraitMatch, action: TraitAction) -> TraitPolicy {
+        self.rules.push((m, action));
+        self
+    }
+
+    /// Denies trait sets which don't satisfy the condition.
+    pub fn require(self, m: TraitMatch) -> TraitPolicy {
+        self.rule(TraitMatch::Not(Box::new(m)), TraitAction::Deny)
+    }
+
+    pub fn evaluate(&self, traits: &[PktTrait]) -> &TraitAction {
+        self.rules
+            .iter()
+            .find(|(m, _)| m.matches(traits))
+            .map_or(&self.default, |(_, action)| action)
+    }
+}
@@ -0,0 +1,261 @@
+//! Userspace model of the kernel's per-packet trait store.
+//!
+//! `TraitStore` does to a set of traits what the trait kfuncs and the
+//! `TCP_SYN_TRAITS` socket option do to a packet's, down to the errno, so
+//! that logic built on them can be tested without the patched kernel. It
+//! serializes to the formats the kernel hands to userspace: the
+//! `SO_PKT_TRAITS` control message blob and `PktTrait` arrays.
+use libc::c_int;
+use std::collections::BTreeMap;
+use std::io;
+use std::mem;
+
+use crate::{Key, PktTrait, PktTraits, TraitKey, TraitVal, TraitValue, MAX_KEY};
+
+/// Size of the bitmap header in front of trait values.
+const HEADER_SIZE: usize = 2 * mem::size_of::<u64>();
+
+/// Room the kernel has for a packet's traits, header included. They live
+/// in the skb metadata area, whose length is kept in a byte and must be a
+/// multiple of four.
+pub const KERNEL_TRAITS_SIZE: usize = 252;
+
+/// Size of a store with every key set to a 64-bit value.
+pub const MAX_TRAITS_SIZE: usize = HEADER_SIZE + (MAX_KEY as usize + 1) * mem::size_of::<u64>();
+
+fn errno(err: c_int) -> io::Error {
+    io::Error::from_raw_os_error(err)
+}
+
+fn check_key(key: TraitKey) -> io::Result<()> {
+    match key > MAX_KEY {
+        true => Err(errno(libc::EINVAL)),
+        false => Ok(()),
+    }
+}
+
+/// Flags for `TraitStore::set`. Unlike the kernel's `TraitFlags`, which has
+/// none yet, these let tests model sets that must not replace a value.
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct StoreFlags(u64);
+
+impl StoreFlags {
+    /// Fail with `EEXIST` instead of replacing a value already set.
+    pub const NO_OVERWRITE: StoreFlags = StoreFlags(1);
+
+    pub const fn empty() -> StoreFlags {
+        StoreFlags(0)
+    }
+
+    pub const fn contains(&self, other: StoreFlags) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    pub const fn from_bits(bits: u64) -> StoreFlags {
+        StoreFlags(bits)
+    }
+
+    pub const fn bits(&self) -> u64 {
+        self.0
+    }
+}
+
+/// Traits of one packet, with the kernel's rules for changing them.
+///
+/// The capacity counts the 16-byte header and the values, as the kernel's
+/// store does. Sets which would outgrow it fail with `ENOSPC`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraitStore {
+    traits: BTreeMap<TraitKey, TraitValue>,
+    capacity: usize,
+}
+
+impl Default for TraitStore {
+    fn default() -> Self {
+        TraitStore::new()
+    }
+}
+
+impl TraitStore {
+    /// Creates an empty store as big as the kernel's, see
+    /// [`KERNEL_TRAITS_SIZE`].
+    pub fn new() -> TraitStore {
+        TraitStore::with_capacity(KERNEL_TRAITS_SIZE)
+    }
+
+    /// Creates an empty store holding at most `capacity` bytes, header
+    /// included. [`MAX_TRAITS_SIZE`] makes one which never runs out of
+    /// space.
+    pub fn with_capacity(capacity: usize) -> TraitStore {
+        TraitStore {
+            traits: BTreeMap::new(),
+            capacity,
+        }
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// Bytes taken up, header included. Same as the length of `to_blob`.
+    pub fn size(&self) -> usize {
+        let values: usize = self.traits.values().map(|v| v.width().bytes()).sum();
+
+        HEADER_SIZE + values
+    }
+
+    pub fn len(&self) -> usize {
+        self.traits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.traits.is_empty()
+    }
+
+    /// Sets a trait, as `bpf_skb_trait_set` does.
+    ///
+    /// Fails with `EINVAL` for keys out of range or unknown flags, `EBUSY`
+    /// if the key holds a value of another width, `EEXIST` if it holds any
+    /// value and `NO_OVERWRITE` is given, and `ENOSPC` if a new value
+    /// doesn't fit.
+    pub fn set(&mut self, key: TraitKey, val: TraitValue, flags: StoreFlags) -> io::Result<()> {
+        check_key(key)?;
+        if flags.bits() & !StoreFlags::NO_OVERWRITE.bits() != 0 {
+            return Err(errno(libc::EINVAL));
+        }
+
+        match self.traits.get(&key) {
+            Some(_) if flags.contains(StoreFlags::NO_OVERWRITE) => return Err(errno(libc::EEXIST)),
+            Some(old) if old.width() != val.width() => return Err(errno(libc::EBUSY)),
+            Some(_) => {}
+            None if self.size() + val.width().bytes() > self.capacity => {
+                return Err(errno(libc::ENOSPC))
+            }
+            None => {}
+        }
+
+        self.traits.insert(key, val);
+        Ok(())
+    }
+
+    /// Reads a trait. Fails with `EINVAL` for keys out of range.
+    pub fn get(&self, key: TraitKey) -> io::Result<Option<TraitValue>> {
+        check_key(key)?;
+
+        Ok(self.traits.get(&key).copied())
+    }
+
+    /// Reads a trait into a value of the key's type, as `bpf_skb_trait_get`
+    /// does. Narrower values are zero-extended. Wider ones fail with
+    /// `ENOSPC`.
+    pub fn get_typed<T: TraitVal>(&self, key: Key<T>) -> io::Result<Option<T>> {
+        match self.get(key.key())? {
+            Some(v) if v.width().bytes() > T::WIDTH.bytes() => Err(errno(libc::ENOSPC)),
+            Some(v) => Ok(Some(T::from_u64(v.to_u64()))),
+            None => Ok(None),
+        }
+    }
+
+    /// Removes a trait, as `bpf_skb_trait_del` does. Yields `false` if it
+    /// was not set.
+    pub fn delete(&mut self, key: TraitKey) -> io::Result<bool> {
+        check_key(key)?;
+
+        Ok(self.traits.remove(&key).is_some())
+    }
+
+    /// Iterates over traits in key order.
+    pub fn iter(&self) -> impl Iterator<Item = (TraitKey, TraitValue)> + '_ {
+        self.traits.iter().map(|(k, v)| (*k, *v))
+    }
+
+    /// Sets traits from a `PktTrait` array, as setting `TCP_SYN_TRAITS` on
+    /// a socket does. Either all traits are set or none.
+    ///
+    /// Fails with `EINVAL` for an empty array and for lengths other than 0,
+    /// 2, 4 or 8. Entries of zero length are skipped. Otherwise fails as
+    /// `set` does.
+    pub fn set_traits(&mut self, traits: &[PktTrait]) -> io::Result<()> {
+        if traits.is_empty() {
+            return Err(errno(libc::EINVAL));
+        }
+
+        let mut next = self.clone();
+        for t in traits {
+            check_key(t.key)?;
+            if t.len == 0 {
+                continue;
+            }
+            let val = t.value().ok_or_else(|| errno(libc::EINVAL))?;
+            next.set(t.key, val, StoreFlags::empty())?;
+        }
+
+        *self = next;
+        Ok(())
+    }
+
+    /// Reads traits into a `PktTrait` array, as getting `TCP_SYN_TRAITS`
+    /// does. Absent traits have zero length.
+    pub fn get_traits(&self, keys: &[TraitKey]) -> io::Result<Vec<PktTrait>> {
+        keys.iter()
+            .map(|&key| {
+                Ok(match self.get(key)? {
+                    Some(TraitValue::U16(v)) => (key, v).into(),
+                    Some(TraitValue::U32(v)) => (key, v).into(),
+                    Some(TraitValue::U64(v)) => (key, v).into(),
+                    None => key.into(),
+                })
+            })
+            .collect()
+    }
+
+    /// Serializes to the blob carried by `SO_PKT_TRAITS` control messages:
+    /// two key bitmaps encoding value widths, then values in key order.
+    pub fn to_blob(&self) -> Vec<u8> {
+        let (mut high, mut low) = (0u64, 0u64);
+        let mut values = vec![];
+
+        for (key, val) in self.iter() {
+            let bit = 1u64 << key;
+            match val {
+                TraitValue::U16(v) => {
+                    low |= bit;
+                    values.extend_from_slice(&v.to_ne_bytes());
+                }
+                TraitValue::U32(v) => {
+                    high |= bit;
+                    values.extend_from_slice(&v.to_ne_bytes());
+                }
+                TraitValue::U64(v) => {
+                    high |= bit;
+                    low |= bit;
+                    values.extend_from_slice(&v.to_ne_bytes());
+                }
+            }
+        }
+
+        let mut blob = Vec::with_capacity(HEADER_SIZE + values.len());
+        blob.extend_from_slice(&high.to_ne_bytes());
+        blob.extend_from_slice(&low.to_ne_bytes());
+        blob.extend_from_slice(&values);
+        blob
+    }
+
+    /// Traits as a socket receives them.
+    pub fn to_pkt_traits(&self) -> PktTraits {
+        PktTraits::try_from(self.to_blob()).expect("blob size matches header")
+    }
+}
+
+/// Copies received traits into a store with room for every key.
+impl From<&PktTraits> for TraitStore {
+    fn from(traits: &PktTraits) -> Self {
+        let mut store = TraitStore::new();
+        for key in 0..=MAX_KEY {
+            if let Ok(Some(val)) = traits.get(key) {
+                store.traits.insert(key, val);
+            }
+        }
+        store
+    }
+}
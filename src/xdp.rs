@@ -0,0 +1,28 @@
+use std::io;
+use std::os::fd::AsFd;
+
+use crate::bpf_sys::{self, BPF_PROG_TYPE_XDP, BPF_XDP};
+use crate::{BpfLink, LinkTarget};
+
+/// Attaches an XDP program to a network device with a link. The kernel
+/// picks native mode if the driver supports it, generic otherwise. Fails
+/// with `EBUSY` if a program was attached without a link, e.g. with
+/// `ip link set dev DEV xdp`.
+///
+/// The program stays attached until the returned link is dropped, or
+/// longer if pinned.
+pub fn attach_xdp<P: AsFd>(prog: P, ifindex: u32) -> io::Result<BpfLink> {
+    let info = bpf_sys::prog_info(prog.as_fd())?;
+    if info.prog_type != BPF_PROG_TYPE_XDP {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "BPF program type {} can't be attached to XDP, expected XDP ({})",
+                info.prog_type, BPF_PROG_TYPE_XDP
+            ),
+        ));
+    }
+    let link = bpf_sys::link_create(prog.as_fd(), ifindex, BPF_XDP)?;
+
+    Ok(BpfLink::new(link, LinkTarget::Xdp { ifindex }))
+}
@@ -1,10 +1,78 @@
 use libbpf_rs::{Object, ObjectBuilder, Program};
-use nix::sys::socket::SockaddrLike;
+use nix::cmsg_space;
+use nix::sys::socket::{
+    recvmsg, socket, AddressFamily, ControlMessageOwned, MsgFlags, SockFlag, SockProtocol,
+    SockType, SockaddrLike,
+};
+use skb_traits::{probe, Feature, Features, PktTraits, SCM_PKT_TRAITS};
 use std::error::Error;
-use std::os::fd::AsRawFd;
+use std::ffi::CString;
+use std::io::{IoSliceMut, Write};
+use std::net::{Ipv4Addr, UdpSocket};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+pub(crate) mod netns;
+pub(crate) mod packet;
+pub(crate) mod trait_sets;
+
+pub(crate) use netns::*;
+pub(crate) use packet::*;
 
 pub(crate) type TestResult = Result<(), Box<dyn Error>>;
 
+pub(crate) const LOOPBACK_V4: (Ipv4Addr, u16) = (Ipv4Addr::LOCALHOST, 0);
+
+/// Kernel features, probed once per test run in a namespace of its own.
+pub(crate) fn features() -> &'static Features {
+    static FEATURES: OnceLock<Features> = OnceLock::new();
+
+    FEATURES.get_or_init(|| {
+        Netns::create()
+            .and_then(|ns| ns.run(probe)?)
+            .expect("probing kernel features")
+    })
+}
+
+/// Tells why a test needing these features can't run, if it can't.
+pub(crate) fn skip_reason(needed: &[Feature]) -> Option<String> {
+    let missing: Vec<&str> = needed
+        .iter()
+        .filter(|f| !features().has(**f))
+        .map(|f| f.name())
+        .collect();
+
+    match missing.is_empty() {
+        true => None,
+        false => Some(format!("kernel lacks {}", missing.join(", "))),
+    }
+}
+
+/// Notes a skipped test on stderr. Writes to the stream directly, as the
+/// test harness captures `eprintln!` output of passing tests. Test threads
+/// are named after their tests.
+pub(crate) fn note_skipped(reason: &str) {
+    let thread = std::thread::current();
+    let test = thread.name().unwrap_or("test");
+    let _ = writeln!(std::io::stderr(), "{} skipped: {}", test, reason);
+}
+
+/// Returns early from a test, which then passes, unless the kernel has all
+/// the listed features.
+macro_rules! skip_unless {
+    ($($feature:ident),+ $(,)?) => {
+        let needed = [$(skb_traits::Feature::$feature),+];
+        if let Some(reason) = $crate::common::skip_reason(&needed) {
+            $crate::common::note_skipped(&reason);
+            return Ok(());
+        }
+    };
+}
+
+pub(crate) use skip_unless;
+
 #[allow(dead_code)]
 pub(crate) fn load_bpf() -> Result<Object, Box<dyn Error>> {
     let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/bpf/set_trait.bpf.o");
@@ -32,3 +100,95 @@ impl GetProg for Object {
 pub(crate) fn connect<F: AsRawFd, A: SockaddrLike>(fd: &F, addr: &A) -> nix::Result<()> {
     nix::sys::socket::connect(fd.as_raw_fd(), addr)
 }
+
+pub(crate) fn tcp_socket_v4() -> nix::Result<OwnedFd> {
+    socket(
+        AddressFamily::Inet,
+        SockType::Stream,
+        SockFlag::empty(),
+        SockProtocol::Tcp,
+    )
+}
+
+/// Sends a datagram to the socket itself and returns the traits it arrived
+/// with. Needs `RcvPktTraits` enabled.
+#[allow(dead_code)]
+pub(crate) fn send_and_recv_traits(
+    s: &UdpSocket,
+) -> Result<Option<PktTraits>, Box<dyn std::error::Error>> {
+    s.send_to(b"x", s.local_addr()?)?;
+    recv_traits(s)
+}
+
+/// Receives a datagram and returns the traits it arrived with.
+#[allow(dead_code)]
+pub(crate) fn recv_traits(s: &UdpSocket) -> Result<Option<PktTraits>, Box<dyn std::error::Error>> {
+    let mut buf = [0u8; 1];
+    let mut iov = [IoSliceMut::new(&mut buf)];
+    let mut cbuf = cmsg_space!([u8; 16 + 8 * 64]);
+
+    let msg = recvmsg::<()>(s.as_raw_fd(), &mut iov, Some(&mut cbuf), MsgFlags::empty())?;
+    for cm in msg.cmsgs()? {
+        if let ControlMessageOwned::Unknown(cm) = cm {
+            if cm.cmsg_header.cmsg_level == libc::SOL_SOCKET
+                && cm.cmsg_header.cmsg_type == SCM_PKT_TRAITS
+            {
+                return Ok(Some(PktTraits::try_from(cm.data_bytes)?));
+            }
+        }
+    }
+
+    Ok(None)
+}
+
+/// BPF object pinned in bpffs. Unpinned on drop.
+#[allow(dead_code)]
+pub(crate) struct Pinned(PathBuf);
+
+impl Pinned {
+    #[allow(dead_code)]
+    pub(crate) fn path(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for Pinned {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.0);
+    }
+}
+
+/// Pins a BPF object under /sys/fs/bpf, as the agent loading programs in
+/// production would.
+#[allow(dead_code)]
+pub(crate) fn pin_bpf<F: AsFd>(fd: &F, name: &str) -> Result<Pinned, Box<dyn Error>> {
+    #[repr(C, align(8))]
+    struct ObjPinAttr {
+        pathname: u64,
+        bpf_fd: u32,
+        file_flags: u32,
+    }
+    const BPF_OBJ_PIN: libc::c_int = 6;
+
+    let path = PathBuf::from(format!("/sys/fs/bpf/{}_{}", name, std::process::id()));
+    let cpath = CString::new(path.as_os_str().as_bytes())?;
+    let mut attr = ObjPinAttr {
+        pathname: cpath.as_ptr() as u64,
+        bpf_fd: fd.as_fd().as_raw_fd() as u32,
+        file_flags: 0,
+    };
+
+    let res = unsafe {
+        libc::syscall(
+            libc::SYS_bpf,
+            BPF_OBJ_PIN,
+            &mut attr as *mut ObjPinAttr,
+            std::mem::size_of::<ObjPinAttr>(),
+        )
+    };
+    if res == -1 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(Pinned(path))
+}
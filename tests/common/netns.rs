@@ -0,0 +1,389 @@
+//! Per-test network namespaces, so that tests neither touch host network
+//! state nor step on each other when run in parallel.
+//!
+//! `TestNetns` moves the calling test thread into a fresh namespace with
+//! loopback up, optionally with a pass-all XDP program on it, which
+//! trait-setting tc programs need to see packets. `Veth` adds a veth pair with the
+//! far end in a namespace of its own. Everything goes away with the
+//! namespaces.
+#![allow(dead_code)]
+
+use std::fs::File;
+use std::io;
+use std::net::Ipv4Addr;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, OwnedFd};
+
+use skb_traits::ebpf::{load_xdp_prog, BpfInsn, R0, XDP_PASS};
+use skb_traits::{attach_xdp, if_index, BpfLink};
+
+pub(crate) const VETH0: &str = "veth0";
+pub(crate) const VETH1: &str = "veth1";
+pub(crate) const VETH0_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 1);
+pub(crate) const VETH1_IP: Ipv4Addr = Ipv4Addr::new(10, 0, 0, 2);
+pub(crate) const VETH0_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x01];
+pub(crate) const VETH1_MAC: [u8; 6] = [0x02, 0, 0, 0, 0, 0x02];
+
+fn current_netns() -> io::Result<OwnedFd> {
+    Ok(File::open("/proc/thread-self/ns/net")?.into())
+}
+
+fn setns(ns: BorrowedFd<'_>) -> io::Result<()> {
+    match unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) } {
+        0 => Ok(()),
+        _ => Err(io::Error::last_os_error()),
+    }
+}
+
+/// Same as tests/bpf/xdp_pass.bpf.c, built without clang.
+fn xdp_pass() -> io::Result<OwnedFd> {
+    load_xdp_prog(
+        "xdp_pass",
+        &[BpfInsn::mov64_imm(R0, XDP_PASS), BpfInsn::exit()],
+    )
+}
+
+fn attach_xdp_pass(ifname: &str) -> io::Result<BpfLink> {
+    let prog = xdp_pass()?;
+    // Link holds on to the program
+    attach_xdp(prog.as_fd(), if_index(ifname)?)
+}
+
+/// Network namespace with loopback up. Lives as long as the handle, or any
+/// socket or device in it.
+#[derive(Debug)]
+pub(crate) struct Netns {
+    fd: OwnedFd,
+}
+
+impl Netns {
+    /// Creates a namespace without entering it.
+    pub(crate) fn create() -> io::Result<Netns> {
+        let orig = current_netns()?;
+        if unsafe { libc::unshare(libc::CLONE_NEWNET) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        // Go back to the original namespace whatever fails
+        let res = current_netns().and_then(|fd| {
+            if_index("lo").and_then(rtnl::link_up)?;
+            Ok(Netns { fd })
+        });
+        setns(orig.as_fd())?;
+
+        res
+    }
+
+    /// Runs `f` inside the namespace. Sockets created meanwhile stay in it.
+    pub(crate) fn run<T>(&self, f: impl FnOnce() -> T) -> io::Result<T> {
+        let orig = current_netns()?;
+        setns(self.fd.as_fd())?;
+        let res = f();
+        setns(orig.as_fd())?;
+
+        Ok(res)
+    }
+}
+
+impl AsFd for Netns {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+/// Test thread moved into a fresh network namespace. Moves back on drop.
+#[must_use = "test leaves the namespace when the guard is dropped"]
+pub(crate) struct TestNetns {
+    orig: OwnedFd,
+    ns: Netns,
+    _lo_xdp: Option<BpfLink>,
+}
+
+impl TestNetns {
+    pub(crate) fn new() -> io::Result<TestNetns> {
+        let orig = current_netns()?;
+        let ns = Netns::create()?;
+        setns(ns.as_fd())?;
+
+        Ok(TestNetns {
+            orig,
+            ns,
+            _lo_xdp: None,
+        })
+    }
+
+    /// Also attaches a pass-all XDP program to loopback, which runs in
+    /// generic mode there.
+    pub(crate) fn with_lo_xdp() -> io::Result<TestNetns> {
+        let mut ns = TestNetns::new()?;
+        ns._lo_xdp = Some(attach_xdp_pass("lo")?);
+
+        Ok(ns)
+    }
+
+    pub(crate) fn netns(&self) -> &Netns {
+        &self.ns
+    }
+}
+
+impl Drop for TestNetns {
+    fn drop(&mut self) {
+        // Thread may run other tests next
+        setns(self.orig.as_fd()).expect("can't leave test netns");
+    }
+}
+
+/// Veth pair with `VETH0` in the test namespace and `VETH1` in a peer
+/// namespace, addressed from 10.0.0.0/24. Traffic sent from the peer
+/// enters the test namespace at `VETH0`. Both ends run a pass-all XDP
+/// program, so that packets take the XDP path on receive.
+pub(crate) struct Veth {
+    peer: Netns,
+    xdp: [Option<BpfLink>; 2],
+}
+
+impl Veth {
+    pub(crate) fn new(ns: &TestNetns) -> io::Result<Veth> {
+        let peer = Netns::create()?;
+
+        let xdp0 = ns.netns().run(|| -> io::Result<BpfLink> {
+            rtnl::add_veth((VETH0, VETH0_MAC), (VETH1, VETH1_MAC), peer.as_fd())?;
+            let veth0 = if_index(VETH0)?;
+            rtnl::add_addr_v4(veth0, VETH0_IP, 24)?;
+            rtnl::link_up(veth0)?;
+            attach_xdp_pass(VETH0)
+        })??;
+
+        let xdp1 = peer.run(|| -> io::Result<BpfLink> {
+            let veth1 = if_index(VETH1)?;
+            rtnl::add_addr_v4(veth1, VETH1_IP, 24)?;
+            rtnl::link_up(veth1)?;
+            attach_xdp_pass(VETH1)
+        })??;
+
+        Ok(Veth {
+            peer,
+            xdp: [Some(xdp0), Some(xdp1)],
+        })
+    }
+
+    /// Namespace holding `VETH1`. Create sockets sending over the pair
+    /// with [`Netns::run`].
+    pub(crate) fn peer(&self) -> &Netns {
+        &self.peer
+    }
+
+    /// Detaches the pass-all program from `VETH0`, making room for one
+    /// under test.
+    pub(crate) fn detach_veth0_xdp(&mut self) -> io::Result<()> {
+        match self.xdp[0].take() {
+            Some(link) => link.detach(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Just enough rtnetlink to set up links and addresses.
+mod rtnl {
+    use std::io;
+    use std::mem;
+    use std::net::Ipv4Addr;
+    use std::os::fd::{AsRawFd, BorrowedFd, FromRawFd, OwnedFd};
+
+    const RTM_NEWLINK: u16 = 16;
+    const RTM_NEWADDR: u16 = 20;
+    const NLMSG_ERROR: u16 = 2;
+
+    const NLM_F_REQUEST: u16 = 0x1;
+    const NLM_F_ACK: u16 = 0x4;
+    const NLM_F_EXCL: u16 = 0x200;
+    const NLM_F_CREATE: u16 = 0x400;
+
+    const IFLA_ADDRESS: u16 = 1;
+    const IFLA_IFNAME: u16 = 3;
+    const IFLA_LINKINFO: u16 = 18;
+    const IFLA_NET_NS_FD: u16 = 28;
+    const IFLA_INFO_KIND: u16 = 1;
+    const IFLA_INFO_DATA: u16 = 2;
+    const VETH_INFO_PEER: u16 = 1;
+
+    const IFA_ADDRESS: u16 = 1;
+    const IFA_LOCAL: u16 = 2;
+
+    const NLA_F_NESTED: u16 = 0x8000;
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct IfInfoMsg {
+        family: u8,
+        _pad: u8,
+        ifi_type: u16,
+        index: i32,
+        flags: u32,
+        change: u32,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy, Default)]
+    struct IfAddrMsg {
+        family: u8,
+        prefixlen: u8,
+        flags: u8,
+        scope: u8,
+        index: u32,
+    }
+
+    /// Netlink request under construction, starting with `struct nlmsghdr`.
+    struct Msg {
+        buf: Vec<u8>,
+        nests: Vec<usize>,
+    }
+
+    impl Msg {
+        fn new(ty: u16, flags: u16) -> Msg {
+            let mut msg = Msg {
+                buf: vec![],
+                nests: vec![],
+            };
+            msg.buf.extend(0u32.to_ne_bytes()); // length, filled in by send
+            msg.buf.extend(ty.to_ne_bytes());
+            msg.buf
+                .extend((flags | NLM_F_REQUEST | NLM_F_ACK).to_ne_bytes());
+            msg.buf.extend(1u32.to_ne_bytes()); // sequence number
+            msg.buf.extend(0u32.to_ne_bytes()); // port id, kernel is 0
+
+            msg
+        }
+
+        /// Appends a plain struct, padded to 4 bytes.
+        fn put<T: Copy>(&mut self, val: &T) {
+            let bytes = unsafe {
+                std::slice::from_raw_parts((val as *const T).cast::<u8>(), mem::size_of::<T>())
+            };
+            self.put_bytes(bytes);
+        }
+
+        fn put_bytes(&mut self, bytes: &[u8]) {
+            self.buf.extend_from_slice(bytes);
+            self.buf.resize(self.buf.len().next_multiple_of(4), 0);
+        }
+
+        fn attr(&mut self, ty: u16, data: &[u8]) {
+            self.buf.extend(((4 + data.len()) as u16).to_ne_bytes());
+            self.buf.extend(ty.to_ne_bytes());
+            self.put_bytes(data);
+        }
+
+        fn nest_start(&mut self, ty: u16) {
+            self.nests.push(self.buf.len());
+            self.attr(ty | NLA_F_NESTED, &[]);
+        }
+
+        fn nest_end(&mut self) {
+            let start = self.nests.pop().expect("no open nest");
+            let len = (self.buf.len() - start) as u16;
+            self.buf[start..start + 2].copy_from_slice(&len.to_ne_bytes());
+        }
+
+        fn send(mut self) -> io::Result<()> {
+            let len = self.buf.len() as u32;
+            self.buf[..4].copy_from_slice(&len.to_ne_bytes());
+
+            let fd = unsafe {
+                libc::socket(
+                    libc::AF_NETLINK,
+                    libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                    libc::NETLINK_ROUTE,
+                )
+            };
+            if fd == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            let sock = unsafe { OwnedFd::from_raw_fd(fd) };
+
+            let res = unsafe {
+                libc::send(
+                    sock.as_raw_fd(),
+                    self.buf.as_ptr().cast(),
+                    self.buf.len(),
+                    0,
+                )
+            };
+            if res == -1 {
+                return Err(io::Error::last_os_error());
+            }
+
+            // Ack is an error message with error 0
+            let mut ack = [0u8; 4096];
+            let n = unsafe { libc::recv(sock.as_raw_fd(), ack.as_mut_ptr().cast(), ack.len(), 0) };
+            if n == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            if n < 20 || u16::from_ne_bytes([ack[4], ack[5]]) != NLMSG_ERROR {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "unexpected netlink reply",
+                ));
+            }
+            match i32::from_ne_bytes([ack[16], ack[17], ack[18], ack[19]]) {
+                0 => Ok(()),
+                err => Err(io::Error::from_raw_os_error(-err)),
+            }
+        }
+    }
+
+    fn ifname(name: &str) -> Vec<u8> {
+        let mut buf = name.as_bytes().to_vec();
+        buf.push(0);
+        buf
+    }
+
+    pub(super) fn link_up(ifindex: u32) -> io::Result<()> {
+        let mut msg = Msg::new(RTM_NEWLINK, 0);
+        msg.put(&IfInfoMsg {
+            index: ifindex as i32,
+            flags: libc::IFF_UP as u32,
+            change: libc::IFF_UP as u32,
+            ..Default::default()
+        });
+
+        msg.send()
+    }
+
+    /// Creates a veth pair, moving the peer end into `peer_ns`.
+    pub(super) fn add_veth(
+        (name, mac): (&str, [u8; 6]),
+        (peer, peer_mac): (&str, [u8; 6]),
+        peer_ns: BorrowedFd<'_>,
+    ) -> io::Result<()> {
+        let mut msg = Msg::new(RTM_NEWLINK, NLM_F_CREATE | NLM_F_EXCL);
+        msg.put(&IfInfoMsg::default());
+        msg.attr(IFLA_IFNAME, &ifname(name));
+        msg.attr(IFLA_ADDRESS, &mac);
+        msg.nest_start(IFLA_LINKINFO);
+        msg.attr(IFLA_INFO_KIND, b"veth");
+        msg.nest_start(IFLA_INFO_DATA);
+        msg.nest_start(VETH_INFO_PEER);
+        msg.put(&IfInfoMsg::default());
+        msg.attr(IFLA_IFNAME, &ifname(peer));
+        msg.attr(IFLA_ADDRESS, &peer_mac);
+        msg.attr(IFLA_NET_NS_FD, &(peer_ns.as_raw_fd() as u32).to_ne_bytes());
+        msg.nest_end();
+        msg.nest_end();
+        msg.nest_end();
+
+        msg.send()
+    }
+
+    pub(super) fn add_addr_v4(ifindex: u32, addr: Ipv4Addr, prefixlen: u8) -> io::Result<()> {
+        let mut msg = Msg::new(RTM_NEWADDR, NLM_F_CREATE | NLM_F_EXCL);
+        msg.put(&IfAddrMsg {
+            family: libc::AF_INET as u8,
+            prefixlen,
+            index: ifindex,
+            ..Default::default()
+        });
+        msg.attr(IFA_LOCAL, &addr.octets());
+        msg.attr(IFA_ADDRESS, &addr.octets());
+
+        msg.send()
+    }
+}
@@ -0,0 +1,46 @@
+//! Synthetic packets for running programs with `ProgTestRun`.
+#![allow(dead_code)]
+
+use std::net::Ipv4Addr;
+
+/// Builds an Ethernet frame carrying an IPv4 UDP datagram, with valid IP
+/// checksum and no UDP checksum.
+pub(crate) fn udp_packet_v4(src: (Ipv4Addr, u16), dst: (Ipv4Addr, u16), payload: &[u8]) -> Vec<u8> {
+    let udp_len = 8 + payload.len();
+    let ip_len = 20 + udp_len;
+
+    let mut pkt = Vec::with_capacity(14 + ip_len);
+    // Ethernet
+    pkt.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x01]);
+    pkt.extend_from_slice(&[0x02, 0, 0, 0, 0, 0x02]);
+    pkt.extend_from_slice(&0x0800u16.to_be_bytes());
+    // IPv4
+    let ip = pkt.len();
+    pkt.extend_from_slice(&[0x45, 0x00]);
+    pkt.extend_from_slice(&(ip_len as u16).to_be_bytes());
+    pkt.extend_from_slice(&[0x00, 0x00, 0x40, 0x00, 64, libc::IPPROTO_UDP as u8]);
+    pkt.extend_from_slice(&[0x00, 0x00]);
+    pkt.extend_from_slice(&src.0.octets());
+    pkt.extend_from_slice(&dst.0.octets());
+    let csum = ipv4_checksum(&pkt[ip..ip + 20]);
+    pkt[ip + 10..ip + 12].copy_from_slice(&csum.to_be_bytes());
+    // UDP
+    pkt.extend_from_slice(&src.1.to_be_bytes());
+    pkt.extend_from_slice(&dst.1.to_be_bytes());
+    pkt.extend_from_slice(&(udp_len as u16).to_be_bytes());
+    pkt.extend_from_slice(&[0x00, 0x00]);
+    pkt.extend_from_slice(payload);
+
+    pkt
+}
+
+fn ipv4_checksum(hdr: &[u8]) -> u16 {
+    let mut sum: u32 = hdr
+        .chunks(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
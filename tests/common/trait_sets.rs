@@ -0,0 +1,86 @@
+//! Trait set tests written once and run against both `TraitStore` and a
+//! socket setting `TCP_SYN_TRAITS`, so that the model can't drift from the
+//! kernel.
+#![allow(dead_code)]
+
+use std::io;
+use std::ops::Range;
+use std::os::fd::AsFd;
+
+use skb_traits::sockopt::{getsockopt, setsockopt};
+use skb_traits::{
+    PktTrait, TcpSynTraits, TcpSynTraitsSet, TraitKey, TraitStore, TraitValue, KERNEL_TRAITS_SIZE,
+};
+
+/// Whatever takes traits the way `TCP_SYN_TRAITS` does.
+pub(crate) trait SetTraits {
+    fn set_traits(&mut self, traits: &[PktTrait]) -> io::Result<()>;
+    fn get_traits(&self, keys: &[TraitKey]) -> io::Result<Vec<PktTrait>>;
+}
+
+impl SetTraits for TraitStore {
+    fn set_traits(&mut self, traits: &[PktTrait]) -> io::Result<()> {
+        TraitStore::set_traits(self, traits)
+    }
+
+    fn get_traits(&self, keys: &[TraitKey]) -> io::Result<Vec<PktTrait>> {
+        TraitStore::get_traits(self, keys)
+    }
+}
+
+/// Socket setting the traits of its SYN.
+pub(crate) struct SynTraitsSocket<F>(pub F);
+
+impl<F: AsFd> SetTraits for SynTraitsSocket<F> {
+    fn set_traits(&mut self, traits: &[PktTrait]) -> io::Result<()> {
+        setsockopt(&self.0, TcpSynTraitsSet::default(), &traits)
+    }
+
+    fn get_traits(&self, keys: &[TraitKey]) -> io::Result<Vec<PktTrait>> {
+        getsockopt(&self.0, TcpSynTraits(keys))
+    }
+}
+
+/// Most 64-bit traits the kernel has room for.
+const MAX_U64_TRAITS: TraitKey = ((KERNEL_TRAITS_SIZE - 16) / 8) as TraitKey;
+
+fn u64_traits(keys: Range<TraitKey>) -> Vec<PktTrait> {
+    keys.map(|key| (key, u64::from(key)).into()).collect()
+}
+
+/// Traits present among the first keys, absent ones left out.
+fn present(s: &impl SetTraits) -> io::Result<Vec<(TraitKey, TraitValue)>> {
+    let keys: Vec<TraitKey> = (0..=MAX_U64_TRAITS).collect();
+    let traits = s.get_traits(&keys)?;
+
+    Ok(traits
+        .iter()
+        .filter_map(|t| Some((t.key, t.value()?)))
+        .collect())
+}
+
+fn enospc(res: io::Result<()>) -> bool {
+    res.err().and_then(|err| err.raw_os_error()) == Some(libc::ENOSPC)
+}
+
+pub(crate) fn enospc_for_too_many_traits_on_first_set(s: &mut impl SetTraits) -> io::Result<()> {
+    assert!(enospc(s.set_traits(&u64_traits(0..MAX_U64_TRAITS + 1))));
+    // None of them set
+    assert!(present(s)?.is_empty());
+
+    Ok(())
+}
+
+pub(crate) fn enospc_for_too_many_traits_on_second_set(s: &mut impl SetTraits) -> io::Result<()> {
+    let half = MAX_U64_TRAITS / 2;
+
+    s.set_traits(&u64_traits(0..half))?;
+    assert!(enospc(s.set_traits(&u64_traits(half..MAX_U64_TRAITS + 1))));
+    // First set kept, none of the second
+    let want: Vec<_> = (0..half)
+        .map(|key| (key, TraitValue::U64(key.into())))
+        .collect();
+    assert_eq!(want, present(s)?);
+
+    Ok(())
+}
@@ -0,0 +1,162 @@
+use nix::libc;
+use nix::sys::socket::setsockopt;
+use std::net::UdpSocket;
+use std::os::fd::AsFd;
+
+use crate::common::*;
+use skb_traits::*;
+
+#[test]
+fn filter_detached_when_guard_dropped() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let obj = load_bpf()?;
+    let prog = obj.get_prog_by_name("set_trait")?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    setsockopt(&s, RcvPktTraits, &true)?;
+
+    let filter = attach_bpf(&s, &prog)?;
+    let traits = send_and_recv_traits(&s)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U16(207))), traits.get(42));
+
+    drop(filter);
+    assert!(send_and_recv_traits(&s)?.is_none());
+
+    let err = setsockopt(&s, SoDetachBpf, &()).unwrap_err();
+    assert_eq!(nix::errno::Errno::ENOENT, err);
+
+    Ok(())
+}
+
+#[test]
+fn can_replace_attached_filter() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let obj = load_bpf()?;
+    let one = obj.get_prog_by_name("set_trait")?;
+    let two = obj.get_prog_by_name("set_two_traits")?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    setsockopt(&s, RcvPktTraits, &true)?;
+
+    let filter = attach_bpf(&s, &one)?;
+    filter.replace(&two)?;
+
+    let traits = send_and_recv_traits(&s)?.ok_or("no traits")?;
+    assert_eq!(Ok(None), traits.get(42));
+    assert_eq!(Ok(Some(TraitValue::U16(0x1616))), traits.get(16));
+    assert_eq!(Ok(Some(TraitValue::U32(0x3232_3232))), traits.get(32));
+
+    filter.detach()?;
+    assert!(send_and_recv_traits(&s)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn kept_filter_stays_attached() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let obj = load_bpf()?;
+    let prog = obj.get_prog_by_name("set_trait")?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    setsockopt(&s, RcvPktTraits, &true)?;
+
+    attach_bpf(&s, &prog)?.keep();
+    assert!(send_and_recv_traits(&s)?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn locked_filter_cant_be_detached() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let obj = load_bpf()?;
+    let prog = obj.get_prog_by_name("set_trait")?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    setsockopt(&s, RcvPktTraits, &true)?;
+
+    let filter = attach_bpf(&s, &prog)?;
+    filter.lock()?;
+
+    let err = filter.detach().unwrap_err();
+    assert_eq!(Some(libc::EPERM), err.raw_os_error());
+    assert!(send_and_recv_traits(&s)?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn socket_filter_prog_accepted() -> TestResult {
+    skip_unless!(TraitSetKfunc);
+
+    let obj = load_bpf()?;
+    let prog = obj.get_prog_by_name("set_trait")?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    let prog = SocketFilterProg::new(prog.as_fd())?;
+    attach_bpf(&s, prog)?.keep();
+
+    Ok(())
+}
+
+#[test]
+fn other_prog_types_rejected() -> TestResult {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/bpf/xdp_pass.bpf.o");
+    let obj = libbpf_rs::ObjectBuilder::default()
+        .open_file(path)?
+        .load()?;
+    let prog = obj.get_prog_by_name("xdp_pass")?;
+
+    let err = SocketFilterProg::new(prog.as_fd()).unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn can_attach_pinned_prog() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let obj = load_bpf()?;
+    let prog = obj.get_prog_by_name("set_trait")?;
+    let pinned = pin_bpf(&prog, "set_trait")?;
+    drop(obj);
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    setsockopt(&s, RcvPktTraits, &true)?;
+
+    let filter = attach_pinned(&s, pinned.path())?;
+    let traits = send_and_recv_traits(&s)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U16(207))), traits.get(42));
+
+    drop(filter);
+    assert!(send_and_recv_traits(&s)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn pinned_prog_of_other_type_rejected() -> TestResult {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/bpf/xdp_pass.bpf.o");
+    let obj = libbpf_rs::ObjectBuilder::default()
+        .open_file(path)?
+        .load()?;
+    let prog = obj.get_prog_by_name("xdp_pass")?;
+    let pinned = pin_bpf(&prog, "xdp_pass")?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    let err = attach_pinned(&s, pinned.path()).unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
@@ -0,0 +1,45 @@
+use axum::extract::ConnectInfo;
+use axum::routing::get;
+use axum::Router;
+use nix::sys::socket::{setsockopt, SockaddrStorage};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+use crate::common::*;
+use skb_traits::*;
+
+async fn trait_42(ConnectInfo(traits): ConnectInfo<SynTraits>) -> String {
+    format!("{:?}", traits.get(42))
+}
+
+#[tokio::test]
+async fn handler_can_extract_syn_traits() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = tokio::net::TcpListener::bind(LOOPBACK_V4).await?;
+    setsockopt(&ln, TcpSaveSynTraits, &true)?;
+    let addr = ln.local_addr()?;
+
+    let app = Router::new().route("/", get(trait_42));
+    tokio::spawn(async move {
+        axum::serve(ln, app.into_make_service_with_connect_info::<SynTraits>()).await
+    });
+
+    let c = tcp_socket_v4()?;
+    setsockopt(&c, TcpSynTraitsSet::default(), &[(42, 0xaaaa_u16).into()])?;
+    connect(&c, &SockaddrStorage::from(addr))?;
+
+    let c = std::net::TcpStream::from(c);
+    c.set_nonblocking(true)?;
+    let mut c = tokio::net::TcpStream::from_std(c)?;
+
+    c.write_all(b"GET / HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await?;
+    let mut resp = String::new();
+    c.read_to_string(&mut resp).await?;
+
+    assert!(resp.starts_with("HTTP/1.1 200 OK"));
+    assert!(resp.ends_with("Some(U16(43690))"));
+
+    Ok(())
+}
@@ -0,0 +1,64 @@
+use hyper::service::{service_fn, Service};
+use hyper::{Request, Response};
+use nix::sys::socket::{setsockopt, SockaddrStorage};
+use std::convert::Infallible;
+
+use crate::common::*;
+use skb_traits::*;
+
+#[tokio::test]
+async fn acceptor_reads_syn_traits() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = tokio::net::TcpListener::bind(LOOPBACK_V4).await?;
+    let acceptor = SynTraitsAcceptor::new(ln)?;
+
+    let c = tcp_socket_v4()?;
+    let t = [(42, 0xaaaa_u16).into(), (43, 0xbbbb_bbbb_u32).into()];
+    setsockopt(&c, TcpSynTraitsSet::default(), &t)?;
+    connect(&c, &SockaddrStorage::from(acceptor.local_addr()?))?;
+
+    let (_p, _, traits) = acceptor.accept().await?;
+    assert_eq!(Some(TraitValue::U16(0xaaaa)), traits.get(42));
+    assert_eq!(Some(TraitValue::U32(0xbbbb_bbbb)), traits.get(43));
+    assert_eq!(None, traits.get(44));
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn acceptor_yields_no_traits_when_none_sent() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = tokio::net::TcpListener::bind(LOOPBACK_V4).await?;
+    let acceptor = SynTraitsAcceptor::new(ln)?;
+
+    let _c = std::net::TcpStream::connect(acceptor.local_addr()?)?;
+
+    let (_p, _, traits) = acceptor.accept().await?;
+    assert!(traits.is_empty());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn service_sees_syn_traits_in_request_extensions() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let traits = SynTraits::from(vec![(42, 207_u16).into()]);
+    let svc = WithSynTraits::new(
+        service_fn(|req: Request<String>| async move {
+            let traits = req.extensions().get::<SynTraits>();
+            Ok::<_, Infallible>(Response::new(format!("{:?}", traits)))
+        }),
+        traits.clone(),
+    );
+
+    let resp = svc.call(Request::new(String::new())).await?;
+    assert_eq!(format!("{:?}", Some(traits)), resp.into_body());
+
+    Ok(())
+}
@@ -0,0 +1,102 @@
+use nix::sys::socket::{getsockopt, setsockopt};
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::os::fd::AsFd;
+
+use crate::common::*;
+use skb_traits::ebpf::load_xdp_meta_stamp;
+use skb_traits::*;
+
+fn traits() -> Vec<(TraitKey, TraitValue)> {
+    vec![
+        (42, TraitValue::U16(207)),
+        (7, TraitValue::U32(0x0707_0707)),
+    ]
+}
+
+/// Replaces the pass-all XDP program on veth0 with one writing trait
+/// records.
+fn stamp_veth0(veth: &mut Veth) -> Result<BpfLink, Box<dyn std::error::Error>> {
+    veth.detach_veth0_xdp()?;
+    let prog = load_xdp_meta_stamp(&traits())?;
+
+    Ok(attach_xdp(prog.as_fd(), if_index(VETH0)?)?)
+}
+
+#[test]
+fn xdp_record_becomes_udp_traits() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let ns = TestNetns::new()?;
+    let mut veth = Veth::new(&ns)?;
+    let _xdp = stamp_veth0(&mut veth)?;
+    let bridge = MetaBridge::attach(if_index(VETH0)?)?;
+
+    let rx = UdpSocket::bind((VETH0_IP, 0))?;
+    setsockopt(&rx, RcvPktTraits, &true)?;
+    let tx = veth.peer().run(|| UdpSocket::bind((VETH1_IP, 0)))??;
+    tx.connect(rx.local_addr()?)?;
+    rx.connect(tx.local_addr()?)?;
+
+    tx.send(b"x")?;
+    let got = recv_traits(&rx)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U16(207))), got.get(42));
+    assert_eq!(Ok(Some(TraitValue::U32(0x0707_0707))), got.get(7));
+
+    // Records alone don't make traits
+    bridge.detach()?;
+    tx.send(b"x")?;
+    assert!(recv_traits(&rx)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn xdp_record_becomes_syn_traits() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits, TraitSetKfunc);
+    let ns = TestNetns::new()?;
+    let mut veth = Veth::new(&ns)?;
+    let _xdp = stamp_veth0(&mut veth)?;
+    let _bridge = MetaBridge::attach(if_index(VETH0)?)?;
+
+    let ln = TcpListener::bind((VETH0_IP, 0))?;
+    setsockopt(&ln, TcpSaveSynTraits, &true)?;
+
+    let addr = ln.local_addr()?;
+    let _c = veth.peer().run(|| TcpStream::connect(addr))?;
+    let (p, _) = ln.accept()?;
+
+    assert_eq!(
+        getsockopt(&p, TcpSynTraits(&[42, 7])),
+        Ok(vec![(42, 207_u16).into(), (7, 0x0707_0707_u32).into()]),
+    );
+
+    Ok(())
+}
+
+#[test]
+fn reference_programs_record_ingress_interface() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/bpf/trait_meta.bpf.o");
+    let obj = libbpf_rs::ObjectBuilder::default()
+        .open_file(path)?
+        .load()?;
+    let classify = obj.get_prog_by_name("xdp_classify")?;
+    let to_traits = obj.get_prog_by_name("tc_meta_to_traits")?;
+
+    let ns = TestNetns::new()?;
+    let mut veth = Veth::new(&ns)?;
+    veth.detach_veth0_xdp()?;
+    let veth0 = if_index(VETH0)?;
+    let _xdp = attach_xdp(classify.as_fd(), veth0)?;
+    let _tc = attach_tc(to_traits.as_fd(), veth0, TcDirection::Ingress)?;
+
+    let rx = UdpSocket::bind((VETH0_IP, 0))?;
+    setsockopt(&rx, RcvPktTraits, &true)?;
+    let tx = veth.peer().run(|| UdpSocket::bind((VETH1_IP, 0)))??;
+    tx.send_to(b"x", rx.local_addr()?)?;
+
+    let got = recv_traits(&rx)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U32(veth0))), got.get(1));
+    assert_eq!(Ok(Some(TraitValue::U32(0))), got.get(2));
+
+    Ok(())
+}
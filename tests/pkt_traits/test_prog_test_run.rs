@@ -0,0 +1,68 @@
+use std::net::Ipv4Addr;
+use std::os::fd::AsFd;
+
+use crate::common::*;
+use skb_traits::*;
+
+const ETH_HLEN: usize = 14;
+const XDP_PASS: u32 = 2;
+
+fn packet() -> Vec<u8> {
+    udp_packet_v4(
+        (Ipv4Addr::new(192, 0, 2, 1), 1234),
+        (Ipv4Addr::new(192, 0, 2, 2), 53),
+        b"hello",
+    )
+}
+
+#[test]
+fn socket_filter_traits_unsupported() -> TestResult {
+    skip_unless!(TraitSetKfunc);
+    let obj = load_bpf()?;
+    let prog = obj.get_prog_by_name("set_trait")?;
+    let pkt = packet();
+
+    let out = ProgTestRun::new(prog.as_fd()).data(&pkt).run()?;
+    // Socket filters see the packet from the network header on
+    assert_eq!((pkt.len() - ETH_HLEN) as u32, out.retval);
+    assert_eq!(pkt, out.data);
+    assert!(out.meta.is_empty());
+
+    let err = out.traits().unwrap_err();
+    assert_eq!(std::io::ErrorKind::Unsupported, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn repeated_runs() -> TestResult {
+    skip_unless!(TraitSetKfunc);
+    let obj = load_bpf()?;
+    let prog = obj.get_prog_by_name("set_two_traits")?;
+    let pkt = packet();
+
+    let out = ProgTestRun::new(prog.as_fd())
+        .data(&pkt)
+        .repeat(100)
+        .run()?;
+    assert_eq!((pkt.len() - ETH_HLEN) as u32, out.retval);
+
+    Ok(())
+}
+
+#[test]
+fn xdp_without_metadata() -> TestResult {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/bpf/xdp_pass.bpf.o");
+    let obj = libbpf_rs::ObjectBuilder::default()
+        .open_file(path)?
+        .load()?;
+    let prog = obj.get_prog_by_name("xdp_pass")?;
+    let pkt = packet();
+
+    let out = ProgTestRun::new(prog.as_fd()).data(&pkt).run()?;
+    assert_eq!(XDP_PASS, out.retval);
+    assert_eq!(pkt, out.data);
+    assert_eq!(None, out.traits()?);
+
+    Ok(())
+}
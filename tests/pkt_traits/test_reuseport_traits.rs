@@ -0,0 +1,50 @@
+use std::net::UdpSocket;
+use std::os::fd::AsFd;
+
+use crate::common::*;
+use skb_traits::ebpf::load_trait_stamp_tc;
+use skb_traits::*;
+
+const SHARD_TRAIT_KEY: TraitKey = 1;
+
+#[test]
+fn trait_picks_reuseport_socket() -> TestResult {
+    skip_unless!(TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/bpf/reuseport.bpf.o");
+    let obj = libbpf_rs::ObjectBuilder::default()
+        .open_file(path)?
+        .load()?;
+    let prog = obj.get_prog_by_name("reuseport_by_trait")?;
+    let record = obj.get_prog_by_name("record_shard_trait")?;
+    let map = obj
+        .maps()
+        .find(|map| map.name() == REUSEPORT_SOCKS_MAP)
+        .ok_or("map missing")?;
+
+    let array = ReuseportSockArray::new(map.as_fd())?;
+    let group = ReuseportGroup::bind_udp("127.0.0.1:0".parse()?, array)?;
+    group.attach(&prog)?;
+
+    // Traits have to be noted before socket lookup, tcx runs programs in
+    // the order attached
+    let lo = if_index("lo")?;
+    let stamp = load_trait_stamp_tc(&[(SHARD_TRAIT_KEY, TraitValue::U32(5))])?;
+    let _stamp = attach_tc(stamp.as_fd(), lo, TcDirection::Ingress)?;
+    let _record = attach_tc(&record, lo, TcDirection::Ingress)?;
+
+    let tx = UdpSocket::bind("127.0.0.1:0")?;
+    for _ in 0..8 {
+        tx.send_to(b"x", group.local_addr()?)?;
+    }
+
+    let mut buf = [0u8; 16];
+    for (slot, sock) in group.sockets().iter().enumerate() {
+        sock.set_nonblocking(true)?;
+        let got = sock.recv(&mut buf).is_ok();
+        assert_eq!(slot == 5, got, "slot {}", slot);
+    }
+
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use std::net::{TcpListener, TcpStream};
+use std::os::fd::AsFd;
+
+use crate::common::*;
+use skb_traits::ebpf::load_trait_stamp_tc;
+use skb_traits::*;
+
+const DISPATCH_TRAIT_KEY: TraitKey = 1;
+
+#[test]
+fn trait_picks_listener() -> TestResult {
+    skip_unless!(TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/bpf/sk_lookup.bpf.o");
+    let obj = libbpf_rs::ObjectBuilder::default()
+        .open_file(path)?
+        .load()?;
+    let prog = obj.get_prog_by_name("dispatch_by_trait")?;
+    let record = obj.get_prog_by_name("record_dispatch_trait")?;
+    let map = |name| {
+        obj.maps()
+            .find(|map| map.name() == name)
+            .ok_or("map missing")
+    };
+    let dispatch = TraitDispatch::new(
+        map(DISPATCH_SOCKS_MAP)?.as_fd(),
+        map(DISPATCH_ROUTES_MAP)?.as_fd(),
+    )?;
+
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    dispatch.set_target(0, &ln)?;
+    dispatch.add_route(7, 0)?;
+    let _link = attach_sk_lookup(&prog)?;
+
+    // Traits have to be noted before socket lookup, tcx runs programs in
+    // the order attached
+    let lo = if_index("lo")?;
+    let stamp = load_trait_stamp_tc(&[(DISPATCH_TRAIT_KEY, TraitValue::U32(7))])?;
+    let _stamp = attach_tc(stamp.as_fd(), lo, TcDirection::Ingress)?;
+    let _record = attach_tc(&record, lo, TcDirection::Ingress)?;
+
+    // Nothing listens on the port connected to
+    let port = TcpListener::bind("127.0.0.1:0")?.local_addr()?.port();
+    let c = TcpStream::connect(("127.0.0.1", port))?;
+    let (p, _) = ln.accept()?;
+    assert_eq!(c.local_addr()?, p.peer_addr()?);
+
+    // Unrouted values take the regular path
+    dispatch.remove_route(7)?;
+    assert!(TcpStream::connect(("127.0.0.1", port)).is_err());
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+use nix::cmsg_space;
+use nix::sys::socket::{recvmsg, ControlMessageOwned, MsgFlags, UnknownCmsg};
+use socket2::{Domain, SockAddr, Socket, Type};
+use std::io::IoSliceMut;
+use std::net::{SocketAddr, TcpListener};
+use std::os::fd::AsRawFd;
+
+use crate::common::*;
+use skb_traits::*;
+
+#[test]
+fn can_toggle_save_syn_traits() -> TestResult {
+    skip_unless!(TcpSaveSynTraits);
+
+    let s = Socket::new(Domain::IPV4, Type::STREAM, None)?;
+
+    assert!(!s.save_syn_traits()?);
+    s.set_save_syn_traits(true)?;
+    assert!(s.save_syn_traits()?);
+    s.set_save_syn_traits(false)?;
+    assert!(!s.save_syn_traits()?);
+
+    Ok(())
+}
+
+#[test]
+fn can_toggle_recv_pkt_traits() -> TestResult {
+    skip_unless!(RcvPktTraits);
+
+    let s = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
+
+    assert!(!s.recv_pkt_traits()?);
+    s.set_recv_pkt_traits(true)?;
+    assert!(s.recv_pkt_traits()?);
+
+    Ok(())
+}
+
+#[test]
+fn can_send_and_recv_traits_before_connect() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = Socket::new(Domain::IPV4, Type::STREAM, None)?;
+    ln.set_save_syn_traits(true)?;
+    ln.bind(&SockAddr::from(SocketAddr::from(LOOPBACK_V4)))?;
+    ln.listen(1)?;
+    let ln = TcpListener::from(ln);
+
+    let c = Socket::new(Domain::IPV4, Type::STREAM, None)?;
+    c.set_syn_traits(&[(42, 0xaaaa_u16).into(), (43, 0xbbbb_bbbb_u32).into()])?;
+    c.connect(&SockAddr::from(ln.local_addr()?))?;
+
+    let (p, _) = ln.accept()?;
+    let p = Socket::from(p);
+    let want: Vec<PktTrait> = vec![(42, 0xaaaa_u16).into(), (43, 0xbbbb_bbbb_u32).into()];
+    assert_eq!(want, p.syn_traits(&[42, 43])?);
+
+    Ok(())
+}
+
+#[test]
+fn can_attach_bpf_and_recv_traits() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let obj = load_bpf()?;
+    let prog = obj.get_prog_by_name("set_trait")?;
+
+    let s = Socket::new(Domain::IPV4, Type::DGRAM, None)?;
+    s.bind(&SockAddr::from(SocketAddr::from(LOOPBACK_V4)))?;
+    s.attach_bpf(&prog)?;
+    s.set_recv_pkt_traits(true)?;
+
+    s.send_to(b"x", &s.local_addr()?)?;
+
+    let mut buf = [0u8; 1];
+    let mut iov = [IoSliceMut::new(&mut buf)];
+    let mut cbuf = cmsg_space!([u8; 16 + 8 * 64]);
+    let msg = recvmsg::<()>(s.as_raw_fd(), &mut iov, Some(&mut cbuf), MsgFlags::empty())?;
+
+    let traits_data = msg.cmsgs()?.find_map(|cm| match cm {
+        ControlMessageOwned::Unknown(UnknownCmsg {
+            cmsg_header,
+            data_bytes,
+        }) if cmsg_header.cmsg_type == SCM_PKT_TRAITS => Some(data_bytes),
+        _ => None,
+    });
+    let traits = PktTraits::try_from(traits_data.ok_or("no traits cmsg")?)?;
+    assert_eq!(Ok(Some(TraitValue::U16(207))), traits.get(42));
+
+    Ok(())
+}
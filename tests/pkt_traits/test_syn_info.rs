@@ -0,0 +1,79 @@
+use nix::sys::socket::{getsockopt, setsockopt, SockaddrStorage};
+use std::net::TcpListener;
+
+use crate::common::*;
+use skb_traits::*;
+
+#[test]
+pub fn can_enable_syn_info_on_listener() -> TestResult {
+    skip_unless!(TcpSaveSynTraits);
+
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    enable_syn_info(&ln, SaveSyn::WithMac)?;
+
+    assert_eq!(Ok(SaveSyn::WithMac), getsockopt(&ln, TcpSaveSyn));
+    assert_eq!(Ok(true), getsockopt(&ln, TcpSaveSynTraits));
+
+    Ok(())
+}
+
+#[test]
+pub fn syn_info_has_headers_and_traits() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    enable_syn_info(&ln, SaveSyn::NetworkAndTransport)?;
+
+    let c = tcp_socket_v4()?;
+    let t = [(42, 0xaaaa_u16).into(), (43, 0xbbbb_cccc_u32).into()];
+    setsockopt(&c, TcpSynTraitsSet::default(), &t)?;
+    connect(&c, &SockaddrStorage::from(ln.local_addr()?))?;
+    let (p, _) = ln.accept()?;
+
+    let info = syn_info(&p, &[42, 43, 44])?;
+    assert_eq!(SaveSyn::NetworkAndTransport, info.mode);
+    assert_eq!(Some(TraitValue::U16(0xaaaa)), info.traits.get(42));
+    assert_eq!(Some(TraitValue::U32(0xbbbb_cccc)), info.traits.get(43));
+    assert_eq!(None, info.traits.get(44));
+
+    let syn = info.saved_syn().ok_or("no saved SYN")??;
+    assert_eq!(ln.local_addr()?.port(), syn.tcp.dst_port);
+
+    Ok(())
+}
+
+#[test]
+pub fn syn_info_decodes_mac_header() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    enable_syn_info(&ln, SaveSyn::WithMac)?;
+
+    let _c = std::net::TcpStream::connect(ln.local_addr()?)?;
+    let (p, _) = ln.accept()?;
+
+    let info = syn_info(&p, &[])?;
+    let syn = info.saved_syn().ok_or("no saved SYN")??;
+    assert!(syn.eth.is_some());
+    assert!(info.traits.is_empty());
+
+    Ok(())
+}
+
+#[test]
+pub fn syn_info_empty_when_not_enabled() -> TestResult {
+    skip_unless!(TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    let _c = std::net::TcpStream::connect(ln.local_addr()?)?;
+    let (p, _) = ln.accept()?;
+
+    let info = syn_info(&p, &[42])?;
+    assert_eq!(SynInfo::default(), info);
+    assert!(info.saved_syn().is_none());
+
+    Ok(())
+}
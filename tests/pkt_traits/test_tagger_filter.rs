@@ -0,0 +1,106 @@
+use nix::sys::socket::setsockopt;
+use std::net::{IpAddr, Ipv4Addr, UdpSocket};
+use std::os::fd::AsFd;
+
+use crate::common::*;
+use skb_traits::*;
+
+fn load_tagger() -> Result<libbpf_rs::Object, Box<dyn std::error::Error>> {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/bpf/tagger.bpf.o");
+    let obj = libbpf_rs::ObjectBuilder::default()
+        .open_file(path)?
+        .load()?;
+
+    Ok(obj)
+}
+
+fn tagger(obj: &libbpf_rs::Object) -> Result<Tagger, Box<dyn std::error::Error>> {
+    let map = |name| {
+        obj.maps()
+            .find(|map| map.name() == name)
+            .ok_or(format!("map '{name}' missing"))
+    };
+    let flows = map(TAGGER_FLOWS_MAP)?;
+    let prefixes = map(TAGGER_PREFIXES_MAP)?;
+
+    Ok(Tagger::new(flows.as_fd(), prefixes.as_fd())?)
+}
+
+#[test]
+fn tagger_applies_rules_at_runtime() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let obj = load_tagger()?;
+    let prog = obj.get_prog_by_name("tagger_sk")?;
+    let tagger = tagger(&obj)?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    setsockopt(&s, RcvPktTraits, &true)?;
+    let _filter = attach_bpf(&s, &prog)?;
+
+    // No rules, no traits
+    assert!(send_and_recv_traits(&s)?.is_none());
+
+    let loopback = TagMatch::SrcPrefix {
+        addr: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)),
+        len: 8,
+    };
+    tagger.add_rule(&loopback, &[PktTrait::from((42, 207u16))])?;
+
+    let traits = send_and_recv_traits(&s)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U16(207))), traits.get(42));
+
+    // Flow rule takes precedence over the prefix
+    let flow = TagMatch::Flow {
+        proto: libc::IPPROTO_UDP as u8,
+        src: s.local_addr()?,
+        dst: s.local_addr()?,
+    };
+    tagger.add_rule(
+        &flow,
+        &[
+            PktTrait::from((16, 0x1616u16)),
+            PktTrait::from((32, 0x3232_3232u32)),
+            PktTrait::from((63, u64::MAX)),
+        ],
+    )?;
+
+    let traits = send_and_recv_traits(&s)?.ok_or("no traits")?;
+    assert_eq!(Ok(None), traits.get(42));
+    assert_eq!(Ok(Some(TraitValue::U16(0x1616))), traits.get(16));
+    assert_eq!(Ok(Some(TraitValue::U32(0x3232_3232))), traits.get(32));
+    assert_eq!(Ok(Some(TraitValue::U64(u64::MAX))), traits.get(63));
+
+    assert!(tagger.remove_rule(&flow)?);
+    assert!(tagger.remove_rule(&loopback)?);
+    assert!(send_and_recv_traits(&s)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn tagger_tc_tags_at_ingress() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let obj = load_tagger()?;
+    let prog = obj.get_prog_by_name("tagger_tc")?;
+    let tagger = tagger(&obj)?;
+    let _link = attach_tc(prog.as_fd(), if_index("lo")?, TcDirection::Ingress)?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    setsockopt(&s, RcvPktTraits, &true)?;
+    assert!(send_and_recv_traits(&s)?.is_none());
+
+    let loopback = TagMatch::SrcPrefix {
+        addr: IpAddr::V4(Ipv4Addr::new(127, 0, 0, 0)),
+        len: 8,
+    };
+    tagger.add_rule(&loopback, &[PktTrait::from((42, 207u16))])?;
+
+    let traits = send_and_recv_traits(&s)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U16(207))), traits.get(42));
+
+    Ok(())
+}
@@ -1,14 +1,11 @@
 use nix::errno::Errno;
-use nix::sys::socket::{
-    getsockopt, setsockopt, socket, sockopt, AddressFamily, SockFlag, SockProtocol, SockType,
-    SockaddrStorage,
-};
+use nix::sys::socket::{getsockopt, setsockopt, sockopt, SockaddrStorage};
 use nix::{libc, setsockopt_impl};
 use std::mem;
-use std::net::Ipv4Addr;
 use std::net::{TcpListener, TcpStream};
-use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::os::fd::{AsFd, AsRawFd};
 
+use crate::common::trait_sets::{self, SynTraitsSocket};
 use crate::common::*;
 use skb_traits::*;
 
@@ -24,10 +21,10 @@ setsockopt_impl!(
     sockopt::SetUsize
 );
 
-const LOOPBACK_V4: (Ipv4Addr, u16) = (Ipv4Addr::LOCALHOST, 0);
-
 #[test]
 pub fn can_toggle_save_syn_traits_flag() -> TestResult {
+    skip_unless!(TcpSaveSynTraits);
+
     let ln = TcpListener::bind("127.0.0.1:0")?;
 
     assert_eq!(Ok(false), getsockopt(&ln, TcpSaveSynTraits));
@@ -41,6 +38,8 @@ pub fn can_toggle_save_syn_traits_flag() -> TestResult {
 
 #[test]
 pub fn can_set_save_syn_traits_flag_only_to_zero_or_one() -> TestResult {
+    skip_unless!(TcpSaveSynTraits);
+
     let ln = TcpListener::bind("127.0.0.1:0")?;
 
     assert_eq!(Ok(()), setsockopt(&ln, TcpSaveSynTraitsInt, &0));
@@ -58,11 +57,14 @@ pub fn cant_enable_trait_saving_on_connected_socket() -> TestResult {
 
 #[test]
 pub fn traits_empty_when_not_enabled() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let obj = load_bpf()?;
     let prog = obj.get_prog_by_name("set_trait")?;
 
     let ln = TcpListener::bind("127.0.0.1:0")?;
-    setsockopt(&ln, SoAttachBpf, &prog.as_fd().as_raw_fd())?;
+    setsockopt(&ln, SoAttachBpf::new(), &prog.as_fd())?;
     setsockopt(&ln, TcpSaveSynTraits, &false)?;
 
     let _c = TcpStream::connect(ln.local_addr()?);
@@ -76,6 +78,9 @@ pub fn traits_empty_when_not_enabled() -> TestResult {
 
 #[test]
 pub fn trait_len_zero_when_absent() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let ln = TcpListener::bind("127.0.0.1:0")?;
     setsockopt(&ln, TcpSaveSynTraits, &true)?;
 
@@ -89,11 +94,14 @@ pub fn trait_len_zero_when_absent() -> TestResult {
 
 #[test]
 pub fn can_read_one_trait_set_by_socket_filter() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let obj = load_bpf()?;
     let prog = obj.get_prog_by_name("set_trait")?;
 
     let ln = TcpListener::bind("127.0.0.1:0")?;
-    setsockopt(&ln, SoAttachBpf, &prog.as_fd().as_raw_fd())?;
+    setsockopt(&ln, SoAttachBpf::new(), &prog.as_fd())?;
     setsockopt(&ln, TcpSaveSynTraits, &true)?;
 
     let _c = TcpStream::connect(ln.local_addr()?);
@@ -109,11 +117,14 @@ pub fn can_read_one_trait_set_by_socket_filter() -> TestResult {
 
 #[test]
 pub fn can_read_two_traits_set_by_socket_filter() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let obj = load_bpf()?;
     let prog = obj.get_prog_by_name("set_two_traits")?;
 
     let ln = TcpListener::bind("127.0.0.1:0")?;
-    setsockopt(&ln, SoAttachBpf, &prog.as_fd().as_raw_fd())?;
+    setsockopt(&ln, SoAttachBpf::new(), &prog.as_fd())?;
     setsockopt(&ln, TcpSaveSynTraits, &true)?;
 
     let _c = TcpStream::connect(ln.local_addr()?);
@@ -151,17 +162,10 @@ pub fn saved_traits_cleared_on_disconnect() -> TestResult {
     todo!()
 }
 
-fn tcp_socket_v4() -> nix::Result<OwnedFd> {
-    socket(
-        AddressFamily::Inet,
-        SockType::Stream,
-        SockFlag::empty(),
-        SockProtocol::Tcp,
-    )
-}
-
 #[test]
 pub fn setting_empty_traits_yields_error() -> TestResult {
+    skip_unless!(TcpSynTraits);
+
     let c = tcp_socket_v4()?;
 
     assert_eq!(
@@ -189,6 +193,8 @@ fn set_tcp_syn_traits(fd: impl AsFd, val: &[u8]) -> nix::Result<()> {
 
 #[test]
 pub fn einval_on_set_for_short_buffer() -> TestResult {
+    skip_unless!(TcpSynTraits);
+
     let c = tcp_socket_v4()?;
 
     const GOOD_SIZE: usize = mem::size_of::<PktTrait>();
@@ -215,6 +221,8 @@ pub fn einval_on_set_for_short_buffer() -> TestResult {
 
 #[test]
 pub fn can_set_one_trait() -> TestResult {
+    skip_unless!(TcpSynTraits);
+
     let c = tcp_socket_v4()?;
 
     let traits = [(42, 0xcfcf_u16).into()];
@@ -225,6 +233,8 @@ pub fn can_set_one_trait() -> TestResult {
 
 #[test]
 pub fn can_set_two_traits() -> TestResult {
+    skip_unless!(TcpSynTraits);
+
     let c = tcp_socket_v4()?;
 
     let traits = [(0xa, 0xaaaa_u16).into(), (0xb, 0xbbbb_bbbb_u32).into()];
@@ -235,6 +245,8 @@ pub fn can_set_two_traits() -> TestResult {
 
 #[test]
 pub fn can_get_back_set_trait() -> TestResult {
+    skip_unless!(TcpSynTraits);
+
     let c = tcp_socket_v4()?;
 
     let traits = [(42, 0xcfcf_u16).into()];
@@ -250,6 +262,9 @@ pub fn can_get_back_set_trait() -> TestResult {
 
 #[test]
 pub fn can_send_and_recv_u16_trait() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let ln = TcpListener::bind(LOOPBACK_V4)?;
     setsockopt(&ln, TcpSaveSynTraits, &true)?;
 
@@ -269,6 +284,9 @@ pub fn can_send_and_recv_u16_trait() -> TestResult {
 
 #[test]
 pub fn can_send_and_recv_u32_trait() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let ln = TcpListener::bind(LOOPBACK_V4)?;
     setsockopt(&ln, TcpSaveSynTraits, &true)?;
 
@@ -288,6 +306,9 @@ pub fn can_send_and_recv_u32_trait() -> TestResult {
 
 #[test]
 pub fn can_send_and_recv_u64_trait() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let ln = TcpListener::bind(LOOPBACK_V4)?;
     setsockopt(&ln, TcpSaveSynTraits, &true)?;
 
@@ -307,6 +328,9 @@ pub fn can_send_and_recv_u64_trait() -> TestResult {
 
 #[test]
 pub fn can_send_and_recv_many_traits() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let ln = TcpListener::bind(LOOPBACK_V4)?;
     setsockopt(&ln, TcpSaveSynTraits, &true)?;
 
@@ -335,6 +359,8 @@ pub fn can_send_and_recv_many_traits() -> TestResult {
 
 #[test]
 pub fn zero_length_trait_ignored_on_set() -> TestResult {
+    skip_unless!(TcpSynTraits);
+
     let c = tcp_socket_v4()?;
 
     assert_eq!(
@@ -347,6 +373,9 @@ pub fn zero_length_trait_ignored_on_set() -> TestResult {
 
 #[test]
 pub fn cant_set_trait_on_listening_or_connected_socket() -> TestResult {
+    skip_unless!(TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let ln = TcpListener::bind(LOOPBACK_V4)?;
     let c = TcpStream::connect(ln.local_addr()?)?;
 
@@ -362,16 +391,24 @@ pub fn cant_set_trait_on_listening_or_connected_socket() -> TestResult {
     Ok(())
 }
 
-#[ignore]
 #[test]
 pub fn enospc_for_too_many_traits_on_first_set() -> TestResult {
-    todo!()
+    skip_unless!(TcpSynTraits);
+
+    let mut c = SynTraitsSocket(tcp_socket_v4()?);
+    trait_sets::enospc_for_too_many_traits_on_first_set(&mut c)?;
+
+    Ok(())
 }
 
-#[ignore]
 #[test]
 pub fn enospc_for_too_many_traits_on_second_set() -> TestResult {
-    todo!()
+    skip_unless!(TcpSynTraits);
+
+    let mut c = SynTraitsSocket(tcp_socket_v4()?);
+    trait_sets::enospc_for_too_many_traits_on_second_set(&mut c)?;
+
+    Ok(())
 }
 
 #[test]
@@ -0,0 +1,102 @@
+use nix::sys::socket::setsockopt;
+use std::net::UdpSocket;
+use std::os::fd::AsFd;
+
+use crate::common::*;
+use skb_traits::ebpf::load_trait_stamp_tc;
+use skb_traits::*;
+
+/// Sends a datagram from the far end of the veth pair, so that it enters
+/// the test namespace at veth0.
+fn send_over_veth(
+    veth: &Veth,
+    rx: &UdpSocket,
+) -> Result<Option<PktTraits>, Box<dyn std::error::Error>> {
+    let tx = veth.peer().run(|| UdpSocket::bind((VETH1_IP, 0)))??;
+    tx.connect(rx.local_addr()?)?;
+    rx.connect(tx.local_addr()?)?;
+
+    tx.send(b"x")?;
+    recv_traits(rx)
+}
+
+#[test]
+fn tc_ingress_traits_reach_socket() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let ns = TestNetns::new()?;
+    let veth = Veth::new(&ns)?;
+
+    let prog = load_trait_stamp_tc(&[(42, TraitValue::U16(207))])?;
+    let link = attach_tc(prog.as_fd(), if_index(VETH0)?, TcDirection::Ingress)?;
+
+    let rx = UdpSocket::bind((VETH0_IP, 0))?;
+    setsockopt(&rx, RcvPktTraits, &true)?;
+
+    let traits = send_over_veth(&veth, &rx)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U16(207))), traits.get(42));
+
+    drop(link);
+    assert!(send_over_veth(&veth, &rx)?.is_none());
+
+    Ok(())
+}
+
+#[test]
+fn tc_egress_traits_cross_veth() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let ns = TestNetns::new()?;
+    let veth = Veth::new(&ns)?;
+
+    let prog = load_trait_stamp_tc(&[(7, TraitValue::U32(0x0707_0707))])?;
+    // Traits cross into the test namespace along with the packet
+    let _link = veth
+        .peer()
+        .run(|| attach_tc(prog.as_fd(), if_index(VETH1)?, TcDirection::Egress))??;
+
+    let rx = UdpSocket::bind((VETH0_IP, 0))?;
+    setsockopt(&rx, RcvPktTraits, &true)?;
+
+    let traits = send_over_veth(&veth, &rx)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U32(0x0707_0707))), traits.get(7));
+
+    Ok(())
+}
+
+#[test]
+fn tagger_tc_at_ingress() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let ns = TestNetns::new()?;
+    let veth = Veth::new(&ns)?;
+
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/bpf/tagger.bpf.o");
+    let obj = libbpf_rs::ObjectBuilder::default()
+        .open_file(path)?
+        .load()?;
+    let prog = obj.get_prog_by_name("tagger_tc")?;
+    let map = |name| {
+        obj.maps()
+            .find(|map| map.name() == name)
+            .ok_or("map missing")
+    };
+    let tagger = Tagger::new(
+        map(TAGGER_FLOWS_MAP)?.as_fd(),
+        map(TAGGER_PREFIXES_MAP)?.as_fd(),
+    )?;
+
+    let _link = attach_tc(&prog, if_index(VETH0)?, TcDirection::Ingress)?;
+    tagger.add_rule(
+        &TagMatch::SrcPrefix {
+            addr: "10.0.0.2".parse()?,
+            len: 32,
+        },
+        &[PktTrait::from((9, 0x99u16))],
+    )?;
+
+    let rx = UdpSocket::bind((VETH0_IP, 0))?;
+    setsockopt(&rx, RcvPktTraits, &true)?;
+
+    let traits = send_over_veth(&veth, &rx)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U16(0x99))), traits.get(9));
+
+    Ok(())
+}
@@ -0,0 +1,51 @@
+use nix::sys::socket::{setsockopt, SockaddrStorage};
+use tokio_stream::StreamExt;
+use tonic::transport::server::Connected;
+
+use crate::common::*;
+use skb_traits::*;
+
+#[tokio::test]
+async fn connect_info_carries_syn_traits() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = tokio::net::TcpListener::bind(LOOPBACK_V4).await?;
+    let mut incoming = SynTraitsIncoming::new(ln)?;
+
+    let c = tcp_socket_v4()?;
+    let t = [
+        (42, 0xaaaa_u16).into(),
+        (43, 0xbbbb_bbbb_cccc_cccc_u64).into(),
+    ];
+    setsockopt(&c, TcpSynTraitsSet::default(), &t)?;
+    connect(&c, &SockaddrStorage::from(incoming.local_addr()?))?;
+
+    let p = incoming.next().await.ok_or("listener closed")??;
+    let info = p.connect_info();
+
+    assert_eq!(Some(TraitValue::U16(0xaaaa)), info.traits.get(42));
+    assert_eq!(
+        Some(TraitValue::U64(0xbbbb_bbbb_cccc_cccc)),
+        info.traits.get(43)
+    );
+    assert_eq!(incoming.local_addr().ok(), info.tcp.local_addr());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn connect_info_has_no_traits_when_none_sent() -> TestResult {
+    skip_unless!(TcpSaveSynTraits, TcpSynTraits);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let ln = tokio::net::TcpListener::bind(LOOPBACK_V4).await?;
+    let mut incoming = SynTraitsIncoming::new(ln)?;
+
+    let _c = std::net::TcpStream::connect(incoming.local_addr()?)?;
+
+    let p = incoming.next().await.ok_or("listener closed")??;
+    assert!(p.connect_info().traits.is_empty());
+
+    Ok(())
+}
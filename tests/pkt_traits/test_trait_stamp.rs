@@ -0,0 +1,39 @@
+use nix::sys::socket::setsockopt;
+use std::net::UdpSocket;
+use std::os::fd::AsFd;
+
+use crate::common::*;
+use skb_traits::ebpf::load_trait_stamp;
+use skb_traits::*;
+
+#[test]
+fn stamp_sets_traits() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
+    let prog = load_trait_stamp(&[
+        (16, TraitValue::U16(0x1616)),
+        (32, TraitValue::U32(0x3232_3232)),
+        (63, TraitValue::U64(0x6363_6363_6363_6363)),
+    ])?;
+
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+    setsockopt(&s, RcvPktTraits, &true)?;
+    setsockopt(&s, SoAttachBpf::new(), &prog.as_fd())?;
+
+    let traits = send_and_recv_traits(&s)?.ok_or("no traits")?;
+    assert_eq!(Ok(Some(TraitValue::U16(0x1616))), traits.get(16));
+    assert_eq!(Ok(Some(TraitValue::U32(0x3232_3232))), traits.get(32));
+    assert_eq!(
+        Ok(Some(TraitValue::U64(0x6363_6363_6363_6363))),
+        traits.get(63)
+    );
+
+    Ok(())
+}
+
+#[test]
+fn stamp_rejects_bad_key() {
+    let err = load_trait_stamp(&[(MAX_KEY + 1, TraitValue::U16(1))]).unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+}
@@ -12,6 +12,8 @@ use skb_traits::*;
 
 #[test]
 fn can_get_set_rcv_pkt_traits_sockopt() -> TestResult {
+    skip_unless!(RcvPktTraits);
+
     let s = UdpSocket::bind("127.0.0.1:0")?;
 
     assert_eq!(Ok(false), getsockopt(&s, RcvPktTraits));
@@ -25,11 +27,14 @@ fn can_get_set_rcv_pkt_traits_sockopt() -> TestResult {
 
 #[test]
 fn can_recv_traits() -> TestResult {
+    skip_unless!(RcvPktTraits, PktTraits, TraitSetKfunc);
+    let _ns = TestNetns::with_lo_xdp()?;
+
     let obj = load_bpf()?;
     let prog = obj.get_prog_by_name("set_trait")?;
 
     let s = UdpSocket::bind("127.0.0.1:0")?;
-    setsockopt(&s, SoAttachBpf, &prog.as_fd().as_raw_fd())?;
+    setsockopt(&s, SoAttachBpf::new(), &prog.as_fd())?;
     setsockopt(&s, RcvPktTraits, &true)?;
 
     s.send_to(b"x", s.local_addr()?)?;
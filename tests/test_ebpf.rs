@@ -0,0 +1,153 @@
+use skb_traits::ebpf::*;
+use skb_traits::TraitValue;
+
+const KFUNC_ID: u32 = 4242;
+
+/// Instructions calling the kfunc for one trait, after the value is stored.
+fn trait_set_call(key: i32, size: i32) -> [BpfInsn; 7] {
+    [
+        BpfInsn::mov64_reg(R1, R6),
+        BpfInsn::mov64_imm(R2, key),
+        BpfInsn::mov64_reg(R3, R10),
+        BpfInsn::add64_imm(R3, -8),
+        BpfInsn::mov64_imm(R4, size),
+        BpfInsn::mov64_imm(R5, 0),
+        BpfInsn::call_kfunc(KFUNC_ID),
+    ]
+}
+
+const EPILOGUE: [BpfInsn; 2] = [BpfInsn::ldx_mem(BPF_W, R0, R6, 0), BpfInsn::exit()];
+
+#[test]
+fn insn_encoding() {
+    let insn = BpfInsn::new(BPF_ALU64 | BPF_MOV | BPF_X, R6, R1, 0, 0);
+    assert_eq!(0xbf, insn.code);
+    assert_eq!(0x16, insn.regs);
+    assert_eq!(R6, insn.dst());
+    assert_eq!(R1, insn.src());
+
+    let call = BpfInsn::call_kfunc(KFUNC_ID);
+    assert_eq!(0x85, call.code);
+    assert_eq!(BPF_PSEUDO_KFUNC_CALL, call.src());
+    assert_eq!(KFUNC_ID as i32, call.imm);
+
+    let [lo, hi] = BpfInsn::ld_imm64(R1, 0x1122_3344_5566_7788);
+    assert_eq!(0x18, lo.code);
+    assert_eq!(0x5566_7788, lo.imm);
+    assert_eq!(0, hi.code);
+    assert_eq!(0x1122_3344, hi.imm);
+
+    assert_eq!(8, std::mem::size_of::<BpfInsn>());
+}
+
+#[test]
+fn no_traits_accepts_packet() {
+    let insns = trait_stamp_insns(&[], KFUNC_ID);
+
+    let mut want = vec![BpfInsn::mov64_reg(R6, R1)];
+    want.extend(EPILOGUE);
+    assert_eq!(want, insns);
+}
+
+#[test]
+fn stamps_each_width() {
+    let insns = trait_stamp_insns(
+        &[
+            (1, TraitValue::U16(0xbeef)),
+            (2, TraitValue::U32(0xdead_beef)),
+            (63, TraitValue::U64(0x0102_0304_0506_0708)),
+        ],
+        KFUNC_ID,
+    );
+
+    let mut want = vec![BpfInsn::mov64_reg(R6, R1)];
+    want.push(BpfInsn::st_mem(BPF_H, R10, -8, 0xbeef));
+    want.extend(trait_set_call(1, 2));
+    want.push(BpfInsn::st_mem(BPF_W, R10, -8, 0xdead_beef_u32 as i32));
+    want.extend(trait_set_call(2, 4));
+    want.extend(BpfInsn::ld_imm64(R1, 0x0102_0304_0506_0708));
+    want.push(BpfInsn::stx_mem(BPF_DW, R10, R1, -8));
+    want.extend(trait_set_call(63, 8));
+    want.extend(EPILOGUE);
+
+    assert_eq!(want, insns);
+}
+
+/// Raw BTF with an INT, a FUNC_PROTO with one param and two FUNCs.
+fn test_btf() -> Vec<u8> {
+    let strings = b"\0int\0foo\0bar\0x\0";
+    let (int_off, foo_off, bar_off, x_off) = (1u32, 5u32, 9u32, 13u32);
+
+    let mut types: Vec<u32> = vec![];
+    // [1] INT 'int' size 4
+    types.extend([int_off, 1 << 24, 4, 32]);
+    // [2] FUNC_PROTO (anon) return [1] vlen 1, param 'x' [1]
+    types.extend([0, (13 << 24) | 1, 1, x_off, 1]);
+    // [3] FUNC 'foo' proto [2]
+    types.extend([foo_off, 12 << 24, 2]);
+    // [4] FUNC 'bar' proto [2]
+    types.extend([bar_off, 12 << 24, 2]);
+    let types: Vec<u8> = types.iter().flat_map(|v| v.to_ne_bytes()).collect();
+
+    let mut btf = vec![];
+    btf.extend(0xeb9fu16.to_ne_bytes());
+    btf.extend([1, 0]);
+    btf.extend(24u32.to_ne_bytes());
+    btf.extend(0u32.to_ne_bytes());
+    btf.extend((types.len() as u32).to_ne_bytes());
+    btf.extend((types.len() as u32).to_ne_bytes());
+    btf.extend((strings.len() as u32).to_ne_bytes());
+    btf.extend(types);
+    btf.extend(strings);
+    btf
+}
+
+#[test]
+fn find_func_in_btf() -> std::io::Result<()> {
+    let btf = test_btf();
+
+    assert_eq!(Some(3), find_btf_func(&btf, "foo")?);
+    assert_eq!(Some(4), find_btf_func(&btf, "bar")?);
+    // Only functions count
+    assert_eq!(None, find_btf_func(&btf, "int")?);
+    assert_eq!(None, find_btf_func(&btf, "baz")?);
+
+    Ok(())
+}
+
+#[test]
+fn bad_btf_rejected() {
+    let btf = test_btf();
+
+    let err = find_btf_func(&btf[..btf.len() - 8], "foo").unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+
+    let err = find_btf_func(b"\x00\x00", "foo").unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidData, err.kind());
+}
+
+#[test]
+fn find_func_in_vmlinux() -> std::io::Result<()> {
+    if !std::path::Path::new("/sys/kernel/btf/vmlinux").exists() {
+        return Ok(());
+    }
+
+    assert!(vmlinux_func_id("tcp_v4_rcv")? > 0);
+
+    let err = vmlinux_func_id("no_such_function").unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn tc_stamp_lets_packet_through() {
+    let insns = trait_stamp_tc_insns(&[(1, TraitValue::U16(1))], KFUNC_ID);
+
+    let mut want = vec![BpfInsn::mov64_reg(R6, R1)];
+    want.push(BpfInsn::st_mem(BPF_H, R10, -8, 1));
+    want.extend(trait_set_call(1, 2));
+    want.extend([BpfInsn::mov64_imm(R0, TC_ACT_OK), BpfInsn::exit()]);
+
+    assert_eq!(want, insns);
+}
@@ -4,8 +4,51 @@ mod common;
 // build them as individual integration test crates. Recipe documented at:
 // https://zerotomastery.io/blog/complete-guide-to-testing-code-in-rust/?utm_source=pocket_shared#Integration-testing
 
+#[path = "pkt_traits/test_attached_filter.rs"]
+mod test_attached_filter;
+
+#[path = "pkt_traits/test_meta_bridge.rs"]
+mod test_meta_bridge;
+
+#[path = "pkt_traits/test_prog_test_run.rs"]
+mod test_prog_test_run;
+
+#[path = "pkt_traits/test_reuseport_traits.rs"]
+mod test_reuseport_traits;
+
+#[path = "pkt_traits/test_sk_lookup_traits.rs"]
+mod test_sk_lookup_traits;
+
+#[path = "pkt_traits/test_syn_info.rs"]
+mod test_syn_info;
+
+#[path = "pkt_traits/test_tagger_filter.rs"]
+mod test_tagger_filter;
+
 #[path = "pkt_traits/test_tcp_syn_traits.rs"]
 mod test_tcp_syn_traits;
 
+#[path = "pkt_traits/test_tcx_traits.rs"]
+mod test_tcx_traits;
+
+#[path = "pkt_traits/test_trait_stamp.rs"]
+mod test_trait_stamp;
+
 #[path = "pkt_traits/test_udp_pkt_traits.rs"]
 mod test_udp_pkt_traits;
+
+#[cfg(feature = "axum")]
+#[path = "pkt_traits/test_axum_syn_traits.rs"]
+mod test_axum_syn_traits;
+
+#[cfg(feature = "hyper")]
+#[path = "pkt_traits/test_hyper_syn_traits.rs"]
+mod test_hyper_syn_traits;
+
+#[cfg(feature = "socket2")]
+#[path = "pkt_traits/test_socket2_traits.rs"]
+mod test_socket2_traits;
+
+#[cfg(feature = "tonic")]
+#[path = "pkt_traits/test_tonic_syn_traits.rs"]
+mod test_tonic_syn_traits;
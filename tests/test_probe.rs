@@ -0,0 +1,81 @@
+use skb_traits::{probe, Feature, Features};
+
+#[path = "common/netns.rs"]
+mod netns;
+
+use netns::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn probe_succeeds_on_any_kernel() -> TestResult {
+    let features = Netns::create()?.run(probe)??;
+
+    // Control messages are only checked for with the kfunc at hand
+    if features.pkt_traits {
+        assert!(features.rcv_pkt_traits);
+        assert!(features.trait_set_kfunc);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn probe_runs_in_test_netns() -> TestResult {
+    let _ns = TestNetns::new()?;
+
+    let features = probe()?;
+    assert_eq!(Netns::create()?.run(probe)??, features);
+
+    Ok(())
+}
+
+#[test]
+fn missing_lists_absent_features() {
+    let features = Features {
+        rcv_pkt_traits: true,
+        tcp_syn_traits: true,
+        ..Default::default()
+    };
+
+    assert!(!features.all());
+    assert_eq!(
+        vec![
+            Feature::PktTraits,
+            Feature::TcpSaveSynTraits,
+            Feature::TraitSetKfunc
+        ],
+        features.missing()
+    );
+}
+
+#[test]
+fn all_when_nothing_missing() {
+    let features = Features {
+        rcv_pkt_traits: true,
+        pkt_traits: true,
+        tcp_save_syn_traits: true,
+        tcp_syn_traits: true,
+        trait_set_kfunc: true,
+    };
+
+    assert!(features.all());
+    assert!(features.missing().is_empty());
+}
+
+#[test]
+fn report_has_line_per_feature() {
+    let features = Features {
+        pkt_traits: true,
+        ..Default::default()
+    };
+
+    assert_eq!(
+        "SO_RCV_PKT_TRAITS: no\n\
+         SO_PKT_TRAITS: yes\n\
+         TCP_SAVE_SYN_TRAITS: no\n\
+         TCP_SYN_TRAITS: no\n\
+         bpf_skb_trait_set: no\n",
+        features.to_string()
+    );
+}
@@ -0,0 +1,172 @@
+use std::io;
+use std::net::{SocketAddr, TcpStream, UdpSocket};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+
+use skb_traits::ebpf::*;
+use skb_traits::sockopt::getsockopt;
+use skb_traits::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const BPF_FUNC_SK_SELECT_REUSEPORT: i32 = 82;
+
+const LOCALHOST: &str = "127.0.0.1:0";
+
+/// Selector sending every packet to the same slot.
+fn select_slot(array: &ReuseportSockArray, slot: u32) -> io::Result<OwnedFd> {
+    let mut insns = vec![
+        BpfInsn::mov64_reg(R6, R1),
+        BpfInsn::st_mem(BPF_W, R10, -4, slot as i32),
+        BpfInsn::mov64_reg(R1, R6),
+    ];
+    insns.extend(BpfInsn::ld_map_fd(R2, array.map().as_raw_fd()));
+    insns.extend([
+        BpfInsn::mov64_reg(R3, R10),
+        BpfInsn::add64_imm(R3, -4),
+        BpfInsn::mov64_imm(R4, 0),
+        BpfInsn::call_helper(BPF_FUNC_SK_SELECT_REUSEPORT),
+        BpfInsn::mov64_imm(R0, SK_PASS),
+        BpfInsn::exit(),
+    ]);
+
+    load_sk_reuseport_prog("select_slot", &insns)
+}
+
+/// Sends a datagram from a fresh port, so that flow hashes differ.
+fn send_to(addr: SocketAddr) -> io::Result<()> {
+    let tx = UdpSocket::bind(LOCALHOST)?;
+    tx.send_to(b"x", addr)?;
+    Ok(())
+}
+
+/// Number of datagrams queued on each socket, draining them.
+fn drain(socks: &[UdpSocket]) -> io::Result<Vec<usize>> {
+    let mut buf = [0u8; 16];
+
+    socks
+        .iter()
+        .map(|s| {
+            s.set_nonblocking(true)?;
+            let mut n = 0;
+            loop {
+                match s.recv(&mut buf) {
+                    Ok(_) => n += 1,
+                    Err(err) if err.kind() == io::ErrorKind::WouldBlock => return Ok(n),
+                    Err(err) => return Err(err),
+                }
+            }
+        })
+        .collect()
+}
+
+#[test]
+fn group_fills_array() -> TestResult {
+    let group = ReuseportGroup::bind_udp(LOCALHOST.parse()?, ReuseportSockArray::create(4)?)?;
+    let port = group.local_addr()?.port();
+
+    assert_eq!(4, group.sockets().len());
+    for (slot, sock) in group.sockets().iter().enumerate() {
+        assert_eq!(port, sock.local_addr()?.port());
+        assert_eq!(
+            Some(getsockopt(sock, SoCookie)?),
+            group.array().cookie(slot as u32)?
+        );
+    }
+
+    Ok(())
+}
+
+#[test]
+fn remove_empties_slot() -> TestResult {
+    let group = ReuseportGroup::bind_udp(LOCALHOST.parse()?, ReuseportSockArray::create(2)?)?;
+
+    assert!(group.array().remove(1)?);
+    assert!(!group.array().remove(1)?);
+    assert_eq!(None, group.array().cookie(1)?);
+    assert!(group.array().cookie(0)?.is_some());
+
+    Ok(())
+}
+
+#[test]
+fn selector_steers_udp() -> TestResult {
+    let group = ReuseportGroup::bind_udp(LOCALHOST.parse()?, ReuseportSockArray::create(4)?)?;
+    let addr = group.local_addr()?;
+
+    let prog = select_slot(group.array(), 2)?;
+    group.attach(prog.as_fd())?;
+    for _ in 0..16 {
+        send_to(addr)?;
+    }
+    assert_eq!(vec![0, 0, 16, 0], drain(group.sockets())?);
+
+    Ok(())
+}
+
+#[test]
+fn empty_slot_falls_back_to_hash() -> TestResult {
+    let group = ReuseportGroup::bind_udp(LOCALHOST.parse()?, ReuseportSockArray::create(4)?)?;
+    let addr = group.local_addr()?;
+
+    let prog = select_slot(group.array(), 3)?;
+    group.attach(prog.as_fd())?;
+    group.array().remove(3)?;
+    for _ in 0..16 {
+        send_to(addr)?;
+    }
+    assert_eq!(16, drain(group.sockets())?.iter().sum::<usize>());
+
+    Ok(())
+}
+
+#[test]
+fn selector_steers_tcp() -> TestResult {
+    let group = ReuseportGroup::bind_tcp(LOCALHOST.parse()?, ReuseportSockArray::create(3)?)?;
+
+    let prog = select_slot(group.array(), 1)?;
+    group.attach(prog.as_fd())?;
+    let c = TcpStream::connect(group.local_addr()?)?;
+
+    let (p, _) = group.sockets()[1].accept()?;
+    assert_eq!(c.local_addr()?, p.peer_addr()?);
+
+    Ok(())
+}
+
+#[test]
+fn detach_restores_hashing() -> TestResult {
+    let group = ReuseportGroup::bind_udp(LOCALHOST.parse()?, ReuseportSockArray::create(2)?)?;
+
+    let prog = select_slot(group.array(), 0)?;
+    group.attach(prog.as_fd())?;
+    group.detach()?;
+
+    let err = group.detach().unwrap_err();
+    assert_eq!(Some(libc::ENOENT), err.raw_os_error());
+
+    Ok(())
+}
+
+#[test]
+fn attach_rejects_other_prog_types() -> TestResult {
+    let group = ReuseportGroup::bind_udp(LOCALHOST.parse()?, ReuseportSockArray::create(1)?)?;
+    let prog = load_tc_prog(
+        "tc_pass",
+        &[BpfInsn::mov64_imm(R0, TC_ACT_OK), BpfInsn::exit()],
+    )?;
+
+    let err = group.attach(prog.as_fd()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn array_rejects_other_maps() -> TestResult {
+    let tagger = Tagger::create()?;
+
+    let err = ReuseportSockArray::new(tagger.flows_map()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
@@ -0,0 +1,326 @@
+use std::net::{Ipv4Addr, Ipv6Addr};
+
+use skb_traits::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+// Linux-like SYN options: MSS, SACK permitted, timestamps, NOP, wscale
+const TCP_OPTS: [u8; 20] = [
+    0x02, 0x04, 0xff, 0xd7, // MSS 65495
+    0x04, 0x02, // SACK permitted
+    0x08, 0x0a, 0x00, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, // TS val 1, ecr 0
+    0x01, // NOP
+    0x03, 0x03, 0x07, // WS 7
+];
+
+fn tcp_hdr(opts: &[u8]) -> Vec<u8> {
+    let doff = (20 + opts.len()) / 4;
+    let mut h = vec![
+        0xa0,
+        0x00, // sport 40960
+        0x1f,
+        0x90, // dport 8080
+        0x00,
+        0x00,
+        0x00,
+        0x2a, // seq 42
+        0x00,
+        0x00,
+        0x00,
+        0x00, // ack 0
+        (doff << 4) as u8,
+        0x02, // doff, SYN
+        0xff,
+        0xff, // window 65535
+        0x00,
+        0x00, // checksum
+        0x00,
+        0x00, // urgent ptr
+    ];
+    h.extend_from_slice(opts);
+    h
+}
+
+fn ipv4_hdr(proto: u8) -> Vec<u8> {
+    vec![
+        0x45, 0x00, // version, IHL, TOS
+        0x00, 0x3c, // total len
+        0x12, 0x34, // id
+        0x40, 0x00, // DF
+        0x40, proto, // TTL 64, protocol
+        0x00, 0x00, // checksum
+        127, 0, 0, 1, // src
+        127, 0, 0, 2, // dst
+    ]
+}
+
+fn ipv6_hdr(next: u8) -> Vec<u8> {
+    let mut h = vec![
+        0x60, 0x00, 0x00, 0x01, // version, TC, flow label 1
+        0x00, 0x28, // payload len
+        next, 0x40, // next header, hop limit 64
+    ];
+    h.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+    h.extend_from_slice(&Ipv6Addr::LOCALHOST.octets());
+    h
+}
+
+#[test]
+fn can_parse_ipv4_syn() -> TestResult {
+    let buf = [ipv4_hdr(6), tcp_hdr(&TCP_OPTS)].concat();
+    let syn = SavedSyn::parse(&buf)?;
+
+    let IpHeader::V4(ip) = &syn.ip else {
+        panic!("expected IPv4, got {:?}", syn.ip);
+    };
+    assert_eq!(Ipv4Addr::new(127, 0, 0, 1), ip.src);
+    assert_eq!(Ipv4Addr::new(127, 0, 0, 2), ip.dst);
+    assert!(ip.dont_fragment());
+    assert_eq!(64, syn.ip.ttl());
+
+    assert_eq!(40960, syn.tcp.src_port);
+    assert_eq!(8080, syn.tcp.dst_port);
+    assert_eq!(42, syn.tcp.seq);
+    assert_eq!(TcpHeader::SYN, syn.tcp.flags);
+    assert_eq!(40, syn.tcp.header_len);
+    assert_eq!(65535, syn.tcp.window);
+
+    assert_eq!(
+        vec![
+            TcpOption::Mss(65495),
+            TcpOption::SackPermitted,
+            TcpOption::Timestamps { val: 1, ecr: 0 },
+            TcpOption::Nop,
+            TcpOption::WindowScale(7),
+        ],
+        syn.options
+    );
+    assert_eq!(Some(65495), syn.mss());
+    assert_eq!(Some(7), syn.window_scale());
+    assert!(syn.sack_permitted());
+    assert_eq!(Some((1, 0)), syn.timestamps());
+
+    Ok(())
+}
+
+#[test]
+fn can_parse_ipv6_syn_with_ext_headers() -> TestResult {
+    let hop_by_hop = [60, 0, 1, 4, 0, 0, 0, 0]; // next: dest opts, PadN
+    let dest_opts = [6, 0, 1, 4, 0, 0, 0, 0]; // next: TCP, PadN
+    let buf = [
+        ipv6_hdr(0),
+        hop_by_hop.to_vec(),
+        dest_opts.to_vec(),
+        tcp_hdr(&TCP_OPTS),
+    ]
+    .concat();
+    let syn = SavedSyn::parse(&buf)?;
+
+    let IpHeader::V6(ip) = &syn.ip else {
+        panic!("expected IPv6, got {:?}", syn.ip);
+    };
+    assert_eq!(Ipv6Addr::LOCALHOST, ip.src);
+    assert_eq!(1, ip.flow_label);
+    assert_eq!(
+        vec![0, 60],
+        ip.ext_headers.iter().map(|e| e.kind).collect::<Vec<_>>()
+    );
+    assert_eq!(8080, syn.tcp.dst_port);
+    assert_eq!(Some(65495), syn.mss());
+
+    Ok(())
+}
+
+#[test]
+fn unknown_and_fast_open_options_preserved() -> TestResult {
+    let opts = [
+        0x22, 0x0a, 1, 2, 3, 4, 5, 6, 7, 8, // TFO cookie
+        0xfd, 0x04, 0xab, 0xcd, // experimental, unknown
+        0x01, 0x01, // NOP, NOP
+    ];
+    let buf = [ipv4_hdr(6), tcp_hdr(&opts)].concat();
+    let syn = SavedSyn::parse(&buf)?;
+
+    assert_eq!(Some(&[1, 2, 3, 4, 5, 6, 7, 8][..]), syn.fast_open_cookie());
+    assert_eq!(
+        TcpOption::Unknown {
+            kind: 0xfd,
+            data: vec![0xab, 0xcd]
+        },
+        syn.options[1]
+    );
+
+    Ok(())
+}
+
+#[test]
+fn experimental_fast_open_keeps_its_kind() -> TestResult {
+    let opts = [
+        0xfe, 0x08, 0xf9, 0x89, 1, 2, 3, 4, // experimental TFO cookie
+    ];
+    let buf = [ipv4_hdr(6), tcp_hdr(&opts)].concat();
+    let syn = SavedSyn::parse(&buf)?;
+
+    assert_eq!(TcpOption::FastOpenExp(vec![1, 2, 3, 4]), syn.options[0]);
+    assert_eq!(254, syn.options[0].kind());
+    assert_eq!(Some(&[1, 2, 3, 4][..]), syn.fast_open_cookie());
+
+    Ok(())
+}
+
+#[test]
+fn options_end_at_eol() -> TestResult {
+    let opts = [0x02, 0x04, 0x05, 0xb4, 0x00, 0xff, 0xff, 0xff];
+    let buf = [ipv4_hdr(6), tcp_hdr(&opts)].concat();
+
+    assert_eq!(
+        vec![TcpOption::Mss(1460), TcpOption::Eol],
+        SavedSyn::parse(&buf)?.options
+    );
+
+    Ok(())
+}
+
+#[test]
+fn truncated_input_yields_error() {
+    let buf = [ipv4_hdr(6), tcp_hdr(&TCP_OPTS)].concat();
+
+    assert!(matches!(
+        SavedSyn::parse(&[]),
+        Err(SavedSynError::Truncated { .. })
+    ));
+    assert!(matches!(
+        SavedSyn::parse(&buf[..19]),
+        Err(SavedSynError::Truncated {
+            what: "IPv4 header",
+            ..
+        })
+    ));
+    assert!(matches!(
+        SavedSyn::parse(&buf[..30]),
+        Err(SavedSynError::Truncated {
+            what: "TCP header",
+            ..
+        })
+    ));
+    assert!(matches!(
+        SavedSyn::parse(&buf[..50]),
+        Err(SavedSynError::Truncated {
+            what: "TCP options",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn inconsistent_input_yields_error() {
+    let mut bad_ihl = [ipv4_hdr(6), tcp_hdr(&[])].concat();
+    bad_ihl[0] = 0x44;
+    assert_eq!(
+        Err(SavedSynError::BadHeaderLength {
+            what: "IPv4 header",
+            len: 16
+        }),
+        SavedSyn::parse(&bad_ihl)
+    );
+
+    let udp = [ipv4_hdr(17), tcp_hdr(&[])].concat();
+    assert_eq!(Err(SavedSynError::NotTcp(17)), SavedSyn::parse(&udp));
+
+    let v5 = [vec![0x50], tcp_hdr(&[])].concat();
+    assert_eq!(
+        Err(SavedSynError::UnsupportedIpVersion(5)),
+        SavedSyn::parse(&v5)
+    );
+
+    let bad_mss = [ipv4_hdr(6), tcp_hdr(&[0x02, 0x03, 0x05, 0x01])].concat();
+    assert_eq!(
+        Err(SavedSynError::BadOptionLength { kind: 2, len: 3 }),
+        SavedSyn::parse(&bad_mss)
+    );
+
+    let overrun = [ipv4_hdr(6), tcp_hdr(&[0x01, 0xfd, 0x08, 0x00])].concat();
+    assert_eq!(
+        Err(SavedSynError::BadOptionLength { kind: 0xfd, len: 8 }),
+        SavedSyn::parse(&overrun)
+    );
+
+    let trailing = [ipv4_hdr(6), tcp_hdr(&[]), vec![0]].concat();
+    assert_eq!(
+        Err(SavedSynError::TrailingBytes(1)),
+        SavedSyn::parse(&trailing)
+    );
+}
+
+fn eth_hdr(ethertype: u16, vlans: &[(u16, u16)]) -> Vec<u8> {
+    let mut h = vec![
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x01, // dst
+        0x02, 0x00, 0x00, 0x00, 0x00, 0x02, // src
+    ];
+    for (tpid, tci) in vlans {
+        h.extend_from_slice(&tpid.to_be_bytes());
+        h.extend_from_slice(&tci.to_be_bytes());
+    }
+    h.extend_from_slice(&ethertype.to_be_bytes());
+    h
+}
+
+#[test]
+fn can_parse_ipv4_syn_with_mac() -> TestResult {
+    let buf = [eth_hdr(0x0800, &[]), ipv4_hdr(6), tcp_hdr(&TCP_OPTS)].concat();
+    let syn = SavedSyn::parse_with_mac(&buf)?;
+
+    let want = EthHeader {
+        dst: [0x02, 0, 0, 0, 0, 0x01],
+        src: [0x02, 0, 0, 0, 0, 0x02],
+        vlans: vec![],
+        ethertype: 0x0800,
+    };
+    assert_eq!(Some(want), syn.eth);
+    assert!(matches!(syn.ip, IpHeader::V4(_)));
+    assert_eq!(Some(65495), syn.mss());
+
+    Ok(())
+}
+
+#[test]
+fn can_parse_syn_with_stacked_vlans() -> TestResult {
+    let vlans = [(0x88a8, 0x0064), (0x8100, 0xa00a)];
+    let buf = [eth_hdr(0x86dd, &vlans), ipv6_hdr(6), tcp_hdr(&TCP_OPTS)].concat();
+    let syn = SavedSyn::parse_with_mac(&buf)?;
+
+    let eth = syn.eth.ok_or("no Ethernet header")?;
+    assert_eq!(0x86dd, eth.ethertype);
+    assert_eq!(2, eth.vlans.len());
+    assert_eq!(100, eth.vlans[0].vid());
+    assert_eq!(0, eth.vlans[0].pcp());
+    assert_eq!(10, eth.vlans[1].vid());
+    assert_eq!(5, eth.vlans[1].pcp());
+    assert!(matches!(syn.ip, IpHeader::V6(_)));
+
+    Ok(())
+}
+
+#[test]
+fn inconsistent_mac_header_yields_error() {
+    let arp = [eth_hdr(0x0806, &[]), ipv4_hdr(6), tcp_hdr(&[])].concat();
+    assert_eq!(
+        Err(SavedSynError::UnsupportedEtherType(0x0806)),
+        SavedSyn::parse_with_mac(&arp)
+    );
+
+    let mismatch = [eth_hdr(0x86dd, &[]), ipv4_hdr(6), tcp_hdr(&[])].concat();
+    assert_eq!(
+        Err(SavedSynError::UnsupportedIpVersion(4)),
+        SavedSyn::parse_with_mac(&mismatch)
+    );
+
+    let short_vlan = eth_hdr(0x8100, &[])[..14].to_vec();
+    assert!(matches!(
+        SavedSyn::parse_with_mac(&short_vlan),
+        Err(SavedSynError::Truncated {
+            what: "VLAN tag",
+            ..
+        })
+    ));
+}
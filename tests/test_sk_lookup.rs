@@ -0,0 +1,166 @@
+use std::io;
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+
+use skb_traits::ebpf::*;
+use skb_traits::sockopt::getsockopt;
+use skb_traits::*;
+
+#[path = "common/netns.rs"]
+mod netns;
+
+use netns::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const BPF_FUNC_MAP_LOOKUP_ELEM: i32 = 1;
+const BPF_FUNC_SK_RELEASE: i32 = 86;
+const BPF_FUNC_SK_ASSIGN: i32 = 124;
+
+/// Offset of `local_port` in `struct bpf_sk_lookup`.
+const SK_LOOKUP_LOCAL_PORT_OFF: i16 = 60;
+
+const LOCALHOST: &str = "127.0.0.1:0";
+
+/// Dispatcher sending lookups for a local port to the socket in slot 0.
+fn dispatch_port(dispatch: &TraitDispatch, port: u16) -> io::Result<OwnedFd> {
+    let mut insns = vec![
+        BpfInsn::mov64_reg(R6, R1),
+        BpfInsn::ldx_mem(BPF_W, R1, R6, SK_LOOKUP_LOCAL_PORT_OFF),
+        BpfInsn::jmp_imm(BPF_JNE, R1, port.into(), 14),
+        BpfInsn::st_mem(BPF_W, R10, -4, 0),
+    ];
+    insns.extend(BpfInsn::ld_map_fd(R1, dispatch.socks_map().as_raw_fd()));
+    insns.extend([
+        BpfInsn::mov64_reg(R2, R10),
+        BpfInsn::add64_imm(R2, -4),
+        BpfInsn::call_helper(BPF_FUNC_MAP_LOOKUP_ELEM),
+        BpfInsn::jmp_imm(BPF_JEQ, R0, 0, 7),
+        BpfInsn::mov64_reg(R7, R0),
+        BpfInsn::mov64_reg(R1, R6),
+        BpfInsn::mov64_reg(R2, R7),
+        BpfInsn::mov64_imm(R3, 0),
+        BpfInsn::call_helper(BPF_FUNC_SK_ASSIGN),
+        BpfInsn::mov64_reg(R1, R7),
+        BpfInsn::call_helper(BPF_FUNC_SK_RELEASE),
+        BpfInsn::mov64_imm(R0, SK_PASS),
+        BpfInsn::exit(),
+    ]);
+
+    load_sk_lookup_prog("dispatch_port", &insns)
+}
+
+/// Port nobody listens on, most likely.
+fn free_port() -> io::Result<u16> {
+    Ok(TcpListener::bind(LOCALHOST)?.local_addr()?.port())
+}
+
+#[test]
+fn routes_round_trip() -> TestResult {
+    let dispatch = TraitDispatch::create(4)?;
+
+    dispatch.add_route(100, 1)?;
+    dispatch.add_route(200, 1)?;
+    dispatch.add_route(300, 2)?;
+    assert_eq!(Some(1), dispatch.route(200)?);
+    assert_eq!(None, dispatch.route(400)?);
+
+    let mut routes = dispatch.list_routes()?;
+    routes.sort();
+    assert_eq!(vec![(100, 1), (200, 1), (300, 2)], routes);
+
+    assert!(dispatch.remove_route(200)?);
+    assert!(!dispatch.remove_route(200)?);
+    assert_eq!(None, dispatch.route(200)?);
+
+    Ok(())
+}
+
+#[test]
+fn targets_round_trip() -> TestResult {
+    let dispatch = TraitDispatch::create(4)?;
+    let ln = TcpListener::bind(LOCALHOST)?;
+
+    dispatch.set_target(3, &ln)?;
+    assert_eq!(Some(getsockopt(&ln, SoCookie)?), dispatch.target_cookie(3)?);
+    assert_eq!(None, dispatch.target_cookie(0)?);
+
+    assert!(dispatch.remove_target(3)?);
+    assert!(!dispatch.remove_target(3)?);
+
+    Ok(())
+}
+
+#[test]
+fn rejects_other_maps() -> TestResult {
+    let tagger = Tagger::create()?;
+    let dispatch = TraitDispatch::create(1)?;
+
+    let err = TraitDispatch::new(tagger.flows_map(), dispatch.routes_map()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+    let err = TraitDispatch::new(dispatch.socks_map(), tagger.flows_map()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn attach_rejects_other_prog_types() -> TestResult {
+    let prog = load_tc_prog(
+        "tc_pass",
+        &[BpfInsn::mov64_imm(R0, TC_ACT_OK), BpfInsn::exit()],
+    )?;
+
+    let err = attach_sk_lookup(prog.as_fd()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn dispatch_tcp_until_detached() -> TestResult {
+    let _ns = TestNetns::new()?;
+
+    let dispatch = TraitDispatch::create(1)?;
+    let ln = TcpListener::bind(LOCALHOST)?;
+    dispatch.set_target(0, &ln)?;
+    let port = free_port()?;
+
+    let prog = dispatch_port(&dispatch, port)?;
+    let link = attach_sk_lookup(prog.as_fd())?;
+    link.prog_id()?;
+
+    let c = TcpStream::connect(("127.0.0.1", port))?;
+    let (p, _) = ln.accept()?;
+    assert_eq!(c.local_addr()?, p.peer_addr()?);
+    assert_eq!(port, p.local_addr()?.port());
+
+    link.detach()?;
+    let err = TcpStream::connect(("127.0.0.1", port)).unwrap_err();
+    assert_eq!(io::ErrorKind::ConnectionRefused, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn dispatch_udp() -> TestResult {
+    let _ns = TestNetns::new()?;
+
+    let dispatch = TraitDispatch::create(1)?;
+    let rx = UdpSocket::bind(LOCALHOST)?;
+    dispatch.set_target(0, &rx)?;
+    let port = free_port()?;
+
+    let prog = dispatch_port(&dispatch, port)?;
+    let _link = attach_sk_lookup(prog.as_fd())?;
+
+    let tx = UdpSocket::bind(LOCALHOST)?;
+    tx.send_to(b"x", ("127.0.0.1", port))?;
+
+    let mut buf = [0u8; 16];
+    let (n, from) = rx.recv_from(&mut buf)?;
+    assert_eq!(b"x", &buf[..n]);
+    assert_eq!(tx.local_addr()?, from);
+
+    Ok(())
+}
@@ -0,0 +1,127 @@
+use std::io;
+use std::net::UdpSocket;
+use std::time::Duration;
+
+use skb_traits::cbpf::{self, FilterBuilder, ACCEPT, BPF_B, BPF_H};
+use skb_traits::sockopt::{getsockopt, setsockopt};
+use skb_traits::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+fn bind(addr: &str) -> io::Result<UdpSocket> {
+    let s = UdpSocket::bind(addr)?;
+    s.set_read_timeout(Some(Duration::from_millis(100)))?;
+    Ok(s)
+}
+
+/// Sends a datagram to the socket from another one and tells if it came
+/// through the filter.
+fn passes(s: &UdpSocket) -> io::Result<bool> {
+    let addr = s.local_addr()?;
+    let c = UdpSocket::bind((addr.ip(), 0))?;
+    c.send_to(b"x", addr)?;
+
+    let mut buf = [0u8; 1];
+    match s.recv(&mut buf) {
+        Ok(_) => Ok(true),
+        Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(false),
+        Err(err) => Err(err),
+    }
+}
+
+#[test]
+fn builder_emits_instructions_in_order() {
+    let prog = FilterBuilder::new()
+        .ld_abs(BPF_H, 2)
+        .jeq(53, 0, 1)
+        .ret(ACCEPT)
+        .ret(0)
+        .build();
+
+    assert_eq!(
+        vec![
+            SockFilter::stmt(0x28, 2),
+            SockFilter::jump(0x15, 53, 0, 1),
+            SockFilter::stmt(0x06, u32::MAX),
+            SockFilter::stmt(0x06, 0),
+        ],
+        prog
+    );
+}
+
+#[test]
+fn no_filter_reads_back_empty() -> TestResult {
+    let s = bind("127.0.0.1:0")?;
+
+    assert_eq!(Vec::<SockFilter>::new(), getsockopt(&s, SoGetFilter)?);
+
+    Ok(())
+}
+
+#[test]
+fn can_read_back_attached_filter() -> TestResult {
+    let s = bind("127.0.0.1:0")?;
+    let prog = cbpf::udp_dst_port(53);
+
+    setsockopt(&s, SoAttachFilter, &prog)?;
+    assert_eq!(prog, getsockopt(&s, SoGetFilter)?);
+
+    Ok(())
+}
+
+#[test]
+fn drop_all_filter_drops() -> TestResult {
+    let s = bind("127.0.0.1:0")?;
+
+    setsockopt(&s, SoAttachFilter, &cbpf::accept_all())?;
+    assert!(passes(&s)?);
+    setsockopt(&s, SoAttachFilter, &cbpf::drop_all())?;
+    assert!(!passes(&s)?);
+
+    Ok(())
+}
+
+#[test]
+fn udp_dst_port_filter_matches_port() -> TestResult {
+    for addr in ["127.0.0.1:0", "[::1]:0"] {
+        let s = bind(addr)?;
+        let port = s.local_addr()?.port();
+
+        setsockopt(&s, SoAttachFilter, &cbpf::udp_dst_port(port))?;
+        assert!(passes(&s)?, "{}", addr);
+        setsockopt(&s, SoAttachFilter, &cbpf::udp_dst_port(port ^ 1))?;
+        assert!(!passes(&s)?, "{}", addr);
+        setsockopt(&s, SoAttachFilter, &cbpf::tcp_dst_port(port))?;
+        assert!(!passes(&s)?, "{}", addr);
+    }
+
+    Ok(())
+}
+
+#[test]
+fn filter_detached_when_guard_dropped() -> TestResult {
+    let s = bind("127.0.0.1:0")?;
+
+    let filter = attach_filter(&s, &cbpf::drop_all())?;
+    assert!(!passes(&s)?);
+    drop(filter);
+    assert!(passes(&s)?);
+    assert!(getsockopt(&s, SoGetFilter)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn invalid_program_rejected() -> TestResult {
+    let s = bind("127.0.0.1:0")?;
+
+    // Doesn't end with a return
+    let prog = FilterBuilder::new().ld_abs(BPF_B, 0).build();
+    let err = setsockopt(&s, SoAttachFilter, &prog).unwrap_err();
+    assert_eq!(Some(libc::EINVAL), err.raw_os_error());
+
+    let err = setsockopt(&s, SoAttachFilter, &[]).unwrap_err();
+    assert_eq!(Some(libc::EINVAL), err.raw_os_error());
+
+    Ok(())
+}
@@ -0,0 +1,84 @@
+use std::net::{TcpListener, TcpStream, UdpSocket};
+use std::os::fd::AsFd;
+
+use skb_traits::sockopt::{getsockopt, setsockopt};
+use skb_traits::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+#[test]
+fn can_set_and_get_save_syn_mode() -> TestResult {
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+
+    setsockopt(&ln, TcpSaveSyn, &SaveSyn::NetworkAndTransport)?;
+    assert_eq!(SaveSyn::NetworkAndTransport, getsockopt(&ln, TcpSaveSyn)?);
+    setsockopt(&ln, TcpSaveSyn, &SaveSyn::Off)?;
+    assert_eq!(SaveSyn::Off, getsockopt(&ln, TcpSaveSyn)?);
+
+    Ok(())
+}
+
+#[test]
+fn saved_syn_empty_when_not_enabled() -> TestResult {
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    let _c = TcpStream::connect(ln.local_addr()?);
+    let (p, _) = ln.accept()?;
+
+    assert!(getsockopt(&p, TcpSavedSyn)?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn non_prog_fd_rejected() -> TestResult {
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+
+    let err = setsockopt(&s, SoAttachBpf::new(), &s.as_fd()).unwrap_err();
+    assert_eq!(Some(libc::EINVAL), err.raw_os_error());
+
+    Ok(())
+}
+
+#[test]
+fn non_bpf_fd_is_not_socket_filter() -> TestResult {
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+
+    let err = SocketFilterProg::new(s.as_fd()).unwrap_err();
+    assert_eq!(std::io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn detach_without_filter_fails() -> TestResult {
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+
+    let err = setsockopt(&s, SoDetachBpf, &()).unwrap_err();
+    assert_eq!(Some(libc::ENOENT), err.raw_os_error());
+
+    Ok(())
+}
+
+#[test]
+fn filter_lock_cant_be_released() -> TestResult {
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+
+    assert!(!getsockopt(&s, SoLockFilter)?);
+    setsockopt(&s, SoLockFilter, &true)?;
+    assert!(getsockopt(&s, SoLockFilter)?);
+
+    let err = setsockopt(&s, SoLockFilter, &false).unwrap_err();
+    assert_eq!(Some(libc::EPERM), err.raw_os_error());
+
+    Ok(())
+}
+
+#[test]
+fn attach_pinned_missing_path_fails() -> TestResult {
+    let s = UdpSocket::bind("127.0.0.1:0")?;
+
+    let err = attach_pinned(&s, "/sys/fs/bpf/skb_traits_no_such_prog").unwrap_err();
+    assert_eq!(std::io::ErrorKind::NotFound, err.kind());
+
+    Ok(())
+}
@@ -0,0 +1,255 @@
+use nix::sys::socket::{getsockopt, setsockopt};
+use std::net::{TcpListener, TcpStream};
+
+use skb_traits::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+const P0F_DB: &str = "
+; Excerpt in p0f.fp format
+
+[mtu]
+
+label = Ethernet or modem
+sig   = 1500
+
+[tcp:request]
+
+label = s:unix:Linux:3.11 and newer
+sig   = *:64:0:*:mss*20,10:mss,sok,ts,nop,ws:df,id+:0
+sig   = *:64:0:*:mss*20,7:mss,sok,ts,nop,ws:df,id+:0
+
+label = s:unix:Linux:2.6.x
+sig   = *:64:0:*:mss*4,6:mss,sok,ts,nop,ws:df,id+:0
+
+label = s:win:Windows:7 or 8
+sig   = *:128:0:*:8192,8:mss,nop,ws,nop,nop,sok:df,id+:0
+
+label = g:unix:Linux:loopback
+sig   = *:64:0:65495:*,*:mss,sok,ts,nop,ws:df:0
+sig   = *:64:0:65495:*,*:mss,sok,ts,nop,ws:df,id+:0
+
+[tcp:response]
+
+label = s:unix:Linux:3.x
+sig   = *:64:0:*:mss*10,0:mss:df:0
+";
+
+fn linux_syn() -> SavedSyn {
+    SavedSyn {
+        eth: None,
+        ip: IpHeader::V4(Ipv4Header {
+            tos: 0,
+            total_len: 60,
+            id: 0x1234,
+            flags_frag_off: 0x4000,
+            ttl: 57,
+            protocol: 6,
+            checksum: 0,
+            src: [192, 0, 2, 1].into(),
+            dst: [192, 0, 2, 2].into(),
+            options: vec![],
+        }),
+        tcp: TcpHeader {
+            src_port: 40000,
+            dst_port: 443,
+            seq: 1,
+            ack: 0,
+            header_len: 40,
+            flags: TcpHeader::SYN,
+            window: 64240,
+            checksum: 0,
+            urgent_ptr: 0,
+        },
+        options: vec![
+            TcpOption::Mss(1460),
+            TcpOption::SackPermitted,
+            TcpOption::Timestamps { val: 1, ecr: 0 },
+            TcpOption::Nop,
+            TcpOption::WindowScale(7),
+        ],
+    }
+}
+
+#[test]
+fn fingerprint_derived_from_syn() {
+    let fp = SynFingerprint::new(&linux_syn());
+
+    assert_eq!(4, fp.ip_version);
+    assert_eq!(64, fp.initial_ttl());
+    assert_eq!(Some(1500), fp.mtu());
+    assert_eq!(Quirks::DF | Quirks::NONZERO_ID, fp.quirks);
+    assert_eq!(
+        "4:64+7:0:1460:mss*44,7:mss,sok,ts,nop,ws:df,id+:0",
+        fp.to_string()
+    );
+    assert_eq!("64240_2-4-8-1-3_1460_7", fp.ja4t());
+}
+
+#[test]
+fn fingerprint_reports_quirks() {
+    let mut syn = linux_syn();
+    syn.tcp.seq = 0;
+    syn.tcp.ack = 42;
+    syn.tcp.flags |= TcpHeader::ECE | TcpHeader::CWR | TcpHeader::PSH;
+    syn.options = vec![
+        TcpOption::Timestamps { val: 0, ecr: 1 },
+        TcpOption::WindowScale(15),
+        TcpOption::Eol,
+    ];
+    if let IpHeader::V4(h) = &mut syn.ip {
+        h.flags_frag_off = 0;
+        h.id = 0;
+    }
+
+    let fp = SynFingerprint::new(&syn);
+    assert_eq!(
+        "id-,ecn,seq-,ack+,pushf+,ts1-,ts2+,exws",
+        fp.quirks.to_string()
+    );
+    assert_eq!(
+        vec![
+            OptionLayout::Timestamps,
+            OptionLayout::WindowScale,
+            OptionLayout::Eol(6)
+        ],
+        fp.layout
+    );
+}
+
+#[test]
+fn experimental_fast_open_in_layout() {
+    let mut syn = linux_syn();
+    syn.options = vec![
+        TcpOption::FastOpenExp(vec![0; 8]),
+        TcpOption::Mss(1460),
+        TcpOption::Eol,
+    ];
+
+    // 12 bytes of TFO, 4 of MSS and the EOL leave 3 of the 20 option bytes
+    let fp = SynFingerprint::new(&syn);
+    assert_eq!(
+        vec![
+            OptionLayout::Other(254),
+            OptionLayout::Mss,
+            OptionLayout::Eol(3)
+        ],
+        fp.layout
+    );
+}
+
+#[test]
+fn signature_round_trips() -> TestResult {
+    for s in [
+        "*:64:0:*:mss*20,10:mss,sok,ts,nop,ws:df,id+:0",
+        "4:128-:4:1460:%8192,*:mss,nop,nop,sok,?30,eol+1::*",
+        "6:255:0:*:mtu*4,0:mss:flow:+",
+    ] {
+        assert_eq!(s, s.parse::<SynSignature>()?.to_string());
+    }
+
+    Ok(())
+}
+
+#[test]
+fn raw_fingerprint_parses_as_signature() -> TestResult {
+    let fp = SynFingerprint::new(&linux_syn());
+    let sig: SynSignature = fp.to_string().parse()?;
+
+    assert!(sig.matches(&fp));
+
+    Ok(())
+}
+
+#[test]
+fn signature_matches_fingerprint() -> TestResult {
+    let fp = SynFingerprint::new(&linux_syn());
+
+    let sig: SynSignature = "*:64:0:*:mss*44,7:mss,sok,ts,nop,ws:df,id+:0".parse()?;
+    assert!(sig.matches(&fp));
+
+    let sig: SynSignature = "6:64:0:*:mss*44,7:mss,sok,ts,nop,ws:df,id+:0".parse()?;
+    assert!(!sig.matches(&fp));
+    let sig: SynSignature = "*:128:0:*:mss*44,7:mss,sok,ts,nop,ws:df,id+:0".parse()?;
+    assert!(!sig.matches(&fp));
+    let sig: SynSignature = "*:128-:0:*:mss*44,7:mss,sok,ts,nop,ws:df,id+:0".parse()?;
+    assert!(sig.matches(&fp));
+    let sig: SynSignature = "*:64:0:*:%8,7:mss,sok,ts,nop,ws:df,id+:0".parse()?;
+    assert!(sig.matches(&fp));
+    let sig: SynSignature = "*:64:0:*:mtu*44,7:mss,sok,ts,nop,ws:df,id+:0".parse()?;
+    assert!(!sig.matches(&fp));
+    let sig: SynSignature = "*:64:0:*:mss*44,7:mss,sok,ts,nop,ws:df:0".parse()?;
+    assert!(!sig.matches(&fp));
+    let sig: SynSignature = "*:64:0:*:mss*44,7:mss,nop,ws,sok,ts:df,id+:0".parse()?;
+    assert!(!sig.matches(&fp));
+
+    Ok(())
+}
+
+#[test]
+fn bad_signature_yields_error() {
+    assert_eq!(
+        Err(SignatureError::FieldCount(3)),
+        "4:64:0".parse::<SynSignature>()
+    );
+    assert_eq!(
+        Err(SignatureError::BadField {
+            field: "quirks",
+            value: "nope".to_string()
+        }),
+        "*:64:0:*:*,*:mss:df,nope:0".parse::<SynSignature>()
+    );
+    assert_eq!(
+        Err(SignatureError::BadField {
+            field: "olayout",
+            value: "eol".to_string()
+        }),
+        "*:64:0:*:*,*:mss,eol:df:0".parse::<SynSignature>()
+    );
+}
+
+#[test]
+fn can_look_up_fingerprint_in_db() -> TestResult {
+    let db: SignatureDb = P0F_DB.parse()?;
+    assert_eq!(6, db.len());
+
+    let mut syn = linux_syn();
+    syn.tcp.window = 1460 * 20;
+    let fp = SynFingerprint::new(&syn);
+    assert_eq!(Some("s:unix:Linux:3.11 and newer"), db.lookup(&fp));
+
+    syn.options.pop();
+    let fp = SynFingerprint::new(&syn);
+    assert_eq!(None, db.lookup(&fp));
+
+    Ok(())
+}
+
+#[test]
+fn bad_db_yields_error_with_line() {
+    let db = "[tcp:request]\nsig = *:64:0:*:*,*:mss:df:0\n";
+    assert_eq!(
+        Err(SignatureError::Line(
+            2,
+            Box::new(SignatureError::MissingLabel)
+        )),
+        db.parse::<SignatureDb>()
+    );
+}
+
+#[test]
+fn can_fingerprint_loopback_syn() -> TestResult {
+    let db: SignatureDb = P0F_DB.parse()?;
+
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    setsockopt(&ln, TcpSaveSyn, &SaveSyn::NetworkAndTransport)?;
+    let _c = TcpStream::connect(ln.local_addr()?)?;
+    let (p, _) = ln.accept()?;
+
+    let syn = SavedSyn::parse(&getsockopt(&p, TcpSavedSyn)?)?;
+    let fp = SynFingerprint::from(&syn);
+
+    assert_eq!(Some("g:unix:Linux:loopback"), db.lookup(&fp), "{}", fp);
+
+    Ok(())
+}
@@ -0,0 +1,149 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::AsFd;
+
+use skb_traits::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+fn flow(proto: i32, src: &str, dst: &str) -> Result<TagMatch, Box<dyn std::error::Error>> {
+    Ok(TagMatch::Flow {
+        proto: proto as u8,
+        src: src.parse::<SocketAddr>()?,
+        dst: dst.parse::<SocketAddr>()?,
+    })
+}
+
+#[test]
+fn add_list_remove_rules() -> TestResult {
+    let tagger = Tagger::create()?;
+    assert!(tagger.list_rules()?.is_empty());
+
+    let udp = flow(libc::IPPROTO_UDP, "192.0.2.1:1234", "192.0.2.2:53")?;
+    let net = TagMatch::SrcPrefix {
+        addr: IpAddr::V6("2001:db8::".parse::<Ipv6Addr>()?),
+        len: 32,
+    };
+    let udp_traits = [PktTrait::from((42, 207u16))];
+    let net_traits = [PktTrait::from((1, 0x11u32)), PktTrait::from((2, 0x22u64))];
+
+    tagger.add_rule(&udp, &udp_traits)?;
+    tagger.add_rule(&net, &net_traits)?;
+
+    let rules = tagger.list_rules()?;
+    assert_eq!(
+        vec![(udp, udp_traits.to_vec()), (net, net_traits.to_vec())],
+        rules
+    );
+
+    assert!(tagger.remove_rule(&udp)?);
+    assert!(!tagger.remove_rule(&udp)?);
+    assert_eq!(vec![(net, net_traits.to_vec())], tagger.list_rules()?);
+
+    Ok(())
+}
+
+#[test]
+fn add_rule_replaces_traits() -> TestResult {
+    let tagger = Tagger::create()?;
+    let net = TagMatch::SrcPrefix {
+        addr: IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)),
+        len: 8,
+    };
+
+    tagger.add_rule(&net, &[PktTrait::from((1, 1u16))])?;
+    tagger.add_rule(&net, &[PktTrait::from((2, 2u16))])?;
+
+    assert_eq!(
+        vec![(net, vec![PktTrait::from((2, 2u16))])],
+        tagger.list_rules()?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn prefix_host_bits_ignored() -> TestResult {
+    let tagger = Tagger::create()?;
+    let traits = [PktTrait::from((1, 1u16))];
+
+    tagger.add_rule(
+        &TagMatch::SrcPrefix {
+            addr: IpAddr::V4(Ipv4Addr::new(10, 1, 2, 3)),
+            len: 16,
+        },
+        &traits,
+    )?;
+
+    let net = TagMatch::SrcPrefix {
+        addr: IpAddr::V4(Ipv4Addr::new(10, 1, 0, 0)),
+        len: 16,
+    };
+    assert_eq!(vec![(net, traits.to_vec())], tagger.list_rules()?);
+    assert!(tagger.remove_rule(&net)?);
+
+    Ok(())
+}
+
+#[test]
+fn bad_rules_rejected() -> TestResult {
+    let tagger = Tagger::create()?;
+    let udp = flow(libc::IPPROTO_UDP, "192.0.2.1:1234", "192.0.2.2:53")?;
+
+    let check = |on: &TagMatch, traits: &[PktTrait]| {
+        let err = tagger.add_rule(on, traits).unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind(), "{err}");
+    };
+
+    // No traits, too many traits, trait without value, key out of range
+    check(&udp, &[]);
+    check(&udp, &[PktTrait::from((1, 1u16)); MAX_RULE_TRAITS + 1]);
+    check(&udp, &[PktTrait::from(1)]);
+    check(&udp, &[PktTrait::from((MAX_KEY + 1, 1u16))]);
+
+    let traits = [PktTrait::from((1, 1u16))];
+    // Mixed families
+    check(
+        &flow(libc::IPPROTO_UDP, "192.0.2.1:1", "[2001:db8::1]:2")?,
+        &traits,
+    );
+    // Ports for a protocol without them
+    check(
+        &flow(libc::IPPROTO_ICMP, "192.0.2.1:1", "192.0.2.2:0")?,
+        &traits,
+    );
+    // Prefix too long
+    check(
+        &TagMatch::SrcPrefix {
+            addr: IpAddr::V4(Ipv4Addr::LOCALHOST),
+            len: 33,
+        },
+        &traits,
+    );
+
+    assert!(tagger.list_rules()?.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn maps_checked_on_new() -> TestResult {
+    let tagger = Tagger::create()?;
+
+    // Maps swapped
+    let err = Tagger::new(tagger.prefixes_map(), tagger.flows_map()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    // Not a map at all
+    let sock = std::net::UdpSocket::bind("127.0.0.1:0")?;
+    let err = Tagger::new(sock.as_fd(), tagger.prefixes_map()).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    // Rules are shared through the same maps
+    let other = Tagger::new(tagger.flows_map(), tagger.prefixes_map())?;
+    let rule = flow(libc::IPPROTO_TCP, "[2001:db8::1]:1", "[2001:db8::2]:80")?;
+    other.add_rule(&rule, &[PktTrait::from((3, 3u32))])?;
+    assert_eq!(1, tagger.list_rules()?.len());
+
+    Ok(())
+}
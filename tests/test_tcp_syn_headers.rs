@@ -1,7 +1,13 @@
-use nix::sys::socket::{getsockopt, setsockopt};
+use nix::sys::socket::{getsockopt, setsockopt, sockopt};
+use socket2::{Domain, Socket, Type};
 use std::net::{TcpListener, TcpStream};
 
-use skb_traits::{TcpSaveSyn, TcpSavedSyn};
+use skb_traits::{IpHeader, SaveSyn, SavedSyn, TcpHeader, TcpSaveSyn, TcpSavedSyn};
+
+#[path = "common/netns.rs"]
+mod netns;
+
+use netns::*;
 
 type TestResult = Result<(), Box<dyn std::error::Error>>;
 
@@ -9,9 +15,14 @@ type TestResult = Result<(), Box<dyn std::error::Error>>;
 pub fn can_toggle_syn_saving() -> TestResult {
     let ln = TcpListener::bind("127.0.0.1:0")?;
 
-
-    assert_eq!(Ok(()), setsockopt(&ln, TcpSaveSyn, &true));
-    assert_eq!(Ok(true), getsockopt(&ln, TcpSaveSyn));
+    assert_eq!(
+        Ok(()),
+        setsockopt(&ln, TcpSaveSyn, &SaveSyn::NetworkAndTransport)
+    );
+    assert_eq!(
+        Ok(SaveSyn::NetworkAndTransport),
+        getsockopt(&ln, TcpSaveSyn)
+    );
 
     Ok(())
 }
@@ -22,8 +33,124 @@ pub fn saved_syn_empty_when_not_enabled() -> TestResult {
     let _c = TcpStream::connect(ln.local_addr()?);
     let (p, _) = ln.accept()?;
 
-    assert_eq!(Ok(false), getsockopt(&ln, TcpSaveSyn));
+    assert_eq!(Ok(SaveSyn::Off), getsockopt(&ln, TcpSaveSyn));
+    assert_eq!(Ok(vec![]), getsockopt(&p, TcpSavedSyn));
+
+    Ok(())
+}
+
+#[test]
+pub fn can_parse_saved_syn() -> TestResult {
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    setsockopt(&ln, TcpSaveSyn, &SaveSyn::NetworkAndTransport)?;
+
+    let c = TcpStream::connect(ln.local_addr()?)?;
+    let (p, _) = ln.accept()?;
+
+    let syn = SavedSyn::parse(&getsockopt(&p, TcpSavedSyn)?)?;
+    assert!(matches!(syn.ip, IpHeader::V4(_)));
+    assert_eq!(c.local_addr()?.port(), syn.tcp.src_port);
+    assert_eq!(ln.local_addr()?.port(), syn.tcp.dst_port);
+    assert_eq!(TcpHeader::SYN, syn.tcp.flags & TcpHeader::SYN);
+    assert!(syn.mss().is_some());
+
+    Ok(())
+}
+
+fn saved_syn_v6(fast_open: bool) -> Result<(Vec<u8>, u16), Box<dyn std::error::Error>> {
+    let ln = TcpListener::bind("[::1]:0")?;
+    setsockopt(&ln, TcpSaveSyn, &SaveSyn::NetworkAndTransport)?;
+
+    let c = Socket::new(Domain::IPV6, Type::STREAM, None)?;
+    setsockopt(&c, sockopt::TcpFastOpenConnect, &fast_open)?;
+    c.connect(&ln.local_addr()?.into())?;
+    if fast_open {
+        // SYN goes out on first write
+        c.send(b"x")?;
+    }
+    let (p, _) = ln.accept()?;
+
+    Ok((getsockopt(&p, TcpSavedSyn)?, ln.local_addr()?.port()))
+}
+
+#[test]
+pub fn saved_ipv6_syn_returned_intact() -> TestResult {
+    let (buf, port) = saved_syn_v6(false)?;
+    assert!(buf.len() > 64);
+
+    let syn = SavedSyn::parse(&buf)?;
+    assert!(matches!(syn.ip, IpHeader::V6(_)));
+    assert_eq!(port, syn.tcp.dst_port);
+    assert!(syn.mss().is_some());
+
+    Ok(())
+}
+
+#[test]
+pub fn saved_ipv6_syn_with_fast_open_returned_intact() -> TestResult {
+    let (buf, port) = saved_syn_v6(true)?;
+
+    let syn = SavedSyn::parse(&buf)?;
+    assert_eq!(port, syn.tcp.dst_port);
+    assert_eq!(Some(&[][..]), syn.fast_open_cookie());
+    assert!(syn.mss().is_some());
+    assert!(syn.window_scale().is_some());
+
+    Ok(())
+}
+
+#[test]
+pub fn saved_syn_cleared_after_read() -> TestResult {
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    setsockopt(&ln, TcpSaveSyn, &SaveSyn::NetworkAndTransport)?;
+    let _c = TcpStream::connect(ln.local_addr()?)?;
+    let (p, _) = ln.accept()?;
+
+    assert!(!getsockopt(&p, TcpSavedSyn)?.is_empty());
     assert_eq!(Ok(vec![]), getsockopt(&p, TcpSavedSyn));
 
     Ok(())
 }
+
+#[test]
+pub fn saved_syn_with_mac_has_eth_header() -> TestResult {
+    let ln = TcpListener::bind("127.0.0.1:0")?;
+    setsockopt(&ln, TcpSaveSyn, &SaveSyn::WithMac)?;
+    assert_eq!(Ok(SaveSyn::WithMac), getsockopt(&ln, TcpSaveSyn));
+
+    let c = TcpStream::connect(ln.local_addr()?)?;
+    let (p, _) = ln.accept()?;
+
+    let syn = SavedSyn::parse_with_mac(&getsockopt(&p, TcpSavedSyn)?)?;
+    let eth = syn.eth.ok_or("no Ethernet header")?;
+    assert_eq!([0; 6], eth.src);
+    assert_eq!([0; 6], eth.dst);
+    assert_eq!(0x0800, eth.ethertype);
+    assert!(matches!(syn.ip, IpHeader::V4(_)));
+    assert_eq!(c.local_addr()?.port(), syn.tcp.src_port);
+
+    Ok(())
+}
+
+#[test]
+pub fn saved_syn_with_mac_over_veth() -> TestResult {
+    let ns = TestNetns::new()?;
+    let veth = Veth::new(&ns)?;
+
+    let ln = TcpListener::bind((VETH0_IP, 0))?;
+    setsockopt(&ln, TcpSaveSyn, &SaveSyn::WithMac)?;
+
+    let c = veth
+        .peer()
+        .run(|| Socket::new(Domain::IPV4, Type::STREAM, None))??;
+    c.connect(&ln.local_addr()?.into())?;
+    let (p, _) = ln.accept()?;
+
+    let syn = SavedSyn::parse_with_mac(&getsockopt(&p, TcpSavedSyn)?)?;
+    let eth = syn.eth.ok_or("no Ethernet header")?;
+    assert_eq!(VETH1_MAC, eth.src);
+    assert_eq!(VETH0_MAC, eth.dst);
+    assert!(eth.vlans.is_empty());
+
+    Ok(())
+}
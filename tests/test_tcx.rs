@@ -0,0 +1,103 @@
+use std::io;
+use std::os::fd::AsFd;
+
+use skb_traits::ebpf::*;
+use skb_traits::*;
+
+#[path = "common/netns.rs"]
+mod netns;
+
+use netns::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+fn tc_pass() -> io::Result<std::os::fd::OwnedFd> {
+    load_tc_prog(
+        "tc_pass",
+        &[BpfInsn::mov64_imm(R0, TC_ACT_OK), BpfInsn::exit()],
+    )
+}
+
+#[test]
+fn link_detached_on_drop() -> TestResult {
+    let _ns = TestNetns::new()?;
+
+    let lo = if_index("lo")?;
+    let prog = tc_pass()?;
+
+    for dir in [TcDirection::Ingress, TcDirection::Egress] {
+        let link = attach_tc(prog.as_fd(), lo, dir)?;
+        assert_eq!(LinkTarget::Tc { ifindex: lo, dir }, link.target());
+
+        let id = link.prog_id()?;
+        assert!(tc_prog_ids(lo, dir)?.contains(&id));
+
+        drop(link);
+        assert!(!tc_prog_ids(lo, dir)?.contains(&id));
+    }
+
+    Ok(())
+}
+
+#[test]
+fn pinned_link_outlives_drop() -> TestResult {
+    let _ns = TestNetns::new()?;
+
+    let lo = if_index("lo")?;
+    let prog = tc_pass()?;
+    let path = format!("/sys/fs/bpf/tcx_pinned_{}", std::process::id());
+
+    let link = attach_tc(prog.as_fd(), lo, TcDirection::Ingress)?;
+    let id = link.prog_id()?;
+    link.pin(&path)?;
+    drop(link);
+    assert!(tc_prog_ids(lo, TcDirection::Ingress)?.contains(&id));
+
+    std::fs::remove_file(&path)?;
+    assert!(!tc_prog_ids(lo, TcDirection::Ingress)?.contains(&id));
+
+    Ok(())
+}
+
+#[test]
+fn detach_while_pinned() -> TestResult {
+    let _ns = TestNetns::new()?;
+
+    let lo = if_index("lo")?;
+    let prog = tc_pass()?;
+    let path = format!("/sys/fs/bpf/tcx_detach_{}", std::process::id());
+
+    let link = attach_tc(prog.as_fd(), lo, TcDirection::Egress)?;
+    let id = link.prog_id()?;
+    link.pin(&path)?;
+    link.detach()?;
+    let attached = tc_prog_ids(lo, TcDirection::Egress)?.contains(&id);
+    std::fs::remove_file(&path)?;
+
+    assert!(!attached);
+
+    Ok(())
+}
+
+#[test]
+fn other_prog_types_rejected() -> TestResult {
+    let lo = if_index("lo")?;
+    let prog = load_socket_filter("sk_pass", &[BpfInsn::mov64_imm(R0, 0), BpfInsn::exit()])?;
+
+    let err = attach_tc(prog.as_fd(), lo, TcDirection::Ingress).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn unknown_device() -> TestResult {
+    let err = if_index("no-such-dev").unwrap_err();
+    assert_eq!(Some(libc::ENODEV), err.raw_os_error());
+
+    let prog = tc_pass()?;
+    let err = attach_tc(prog.as_fd(), u32::MAX, TcDirection::Ingress).unwrap_err();
+    assert_eq!(Some(libc::ENODEV), err.raw_os_error());
+
+    Ok(())
+}
@@ -0,0 +1,74 @@
+#![cfg(feature = "tower")]
+
+use http::Request;
+use std::convert::Infallible;
+use tower::{service_fn, Layer, ServiceExt};
+
+use skb_traits::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+async fn echo_route(req: Request<()>) -> Result<Option<TraitRoute>, Infallible> {
+    Ok(req.extensions().get::<TraitRoute>().cloned())
+}
+
+fn request(traits: Option<Vec<PktTrait>>) -> Request<()> {
+    let mut req = Request::new(());
+    if let Some(traits) = traits {
+        req.extensions_mut().insert(SynTraits::from(traits));
+    }
+    req
+}
+
+fn policy() -> TraitPolicy {
+    TraitPolicy::new(TraitAction::Allow)
+        .require(TraitMatch::OneOf(42, vec![207, 208]))
+        .rule(TraitMatch::Present(16), TraitAction::Route("tagged".into()))
+}
+
+#[tokio::test]
+async fn allowed_request_reaches_inner_service() -> TestResult {
+    let svc = TraitPolicyLayer::new(policy()).layer(service_fn(echo_route));
+
+    let resp = svc.oneshot(request(Some(vec![(42, 208_u16).into()]))).await;
+    assert_eq!(None, resp.map_err(|e| e.to_string())?);
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn denied_request_yields_error() -> TestResult {
+    let svc = TraitPolicyLayer::new(policy()).layer(service_fn(echo_route));
+
+    let err = svc
+        .oneshot(request(Some(vec![(42, 209_u16).into()])))
+        .await
+        .unwrap_err();
+    assert!(err.is::<PolicyDenied>());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_without_traits_evaluated_as_empty_set() -> TestResult {
+    let svc = TraitPolicyLayer::new(policy()).layer(service_fn(echo_route));
+
+    let err = svc.oneshot(request(None)).await.unwrap_err();
+    assert!(err.is::<PolicyDenied>());
+
+    Ok(())
+}
+
+#[tokio::test]
+async fn routed_request_carries_route_in_extensions() -> TestResult {
+    let svc = TraitPolicyLayer::new(policy()).layer(service_fn(echo_route));
+
+    let traits = vec![(16, 0x1616_u16).into(), (42, 207_u16).into()];
+    let resp = svc.oneshot(request(Some(traits))).await;
+    assert_eq!(
+        Some(TraitRoute("tagged".into())),
+        resp.map_err(|e| e.to_string())?
+    );
+
+    Ok(())
+}
@@ -0,0 +1,33 @@
+use skb_traits::*;
+
+const CLIENT_ID: Key<u32> = Key::new(42);
+const ZONE: Key<u16> = Key::new(63);
+
+#[test]
+fn key_carries_width() {
+    assert_eq!(42, CLIENT_ID.key());
+    assert_eq!(TraitWidth::U32, CLIENT_ID.width());
+    assert_eq!(2, ZONE.width().bytes());
+    assert_eq!(Some(TraitWidth::U64), TraitWidth::from_bytes(8));
+    assert_eq!(None, TraitWidth::from_bytes(3));
+}
+
+#[test]
+fn value_converts_only_to_matching_type() {
+    let v = TraitValue::U32(0xdead_beef);
+
+    assert_eq!(Some(0xdead_beef_u32), v.to_typed());
+    assert_eq!(None, v.to_typed::<u16>());
+    assert_eq!(None, v.to_typed::<u64>());
+}
+
+#[test]
+fn syn_traits_typed_lookup() {
+    let traits = SynTraits::from(vec![
+        PktTrait::from((42, 7_u32)),
+        PktTrait::from((63, 7_u32)),
+    ]);
+
+    assert_eq!(Some(7), traits.get_typed(CLIENT_ID));
+    assert_eq!(None, traits.get_typed(ZONE));
+}
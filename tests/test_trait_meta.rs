@@ -0,0 +1,144 @@
+use std::io;
+use std::net::Ipv4Addr;
+use std::os::fd::AsFd;
+
+use skb_traits::ebpf::*;
+use skb_traits::*;
+
+#[path = "common/netns.rs"]
+mod netns;
+#[path = "common/packet.rs"]
+mod packet;
+
+use netns::*;
+use packet::*;
+
+type TestResult = Result<(), Box<dyn std::error::Error>>;
+
+fn traits() -> Vec<(TraitKey, TraitValue)> {
+    vec![
+        (1, TraitValue::U16(0x1234)),
+        (63, TraitValue::U64(0x0102_0304_0506_0708)),
+    ]
+}
+
+#[test]
+fn record_round_trip() -> TestResult {
+    let meta = encode_trait_meta(&traits())?;
+
+    assert_eq!(4 + 2 * 12, meta.len());
+    assert_eq!(Some(traits()), decode_trait_meta(&meta));
+
+    Ok(())
+}
+
+#[test]
+fn empty_record() -> TestResult {
+    let meta = encode_trait_meta(&[])?;
+
+    assert_eq!(4, meta.len());
+    assert_eq!(Some(vec![]), decode_trait_meta(&meta));
+
+    Ok(())
+}
+
+#[test]
+fn encode_rejects_bad_traits() {
+    let too_many = vec![(1, TraitValue::U16(1)); MAX_META_TRAITS + 1];
+    let err = encode_trait_meta(&too_many).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    let err = encode_trait_meta(&[(MAX_KEY + 1, TraitValue::U16(1))]).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+}
+
+#[test]
+fn decode_rejects_bad_records() -> TestResult {
+    let meta = encode_trait_meta(&traits())?;
+
+    assert_eq!(None, decode_trait_meta(&[]));
+    assert_eq!(None, decode_trait_meta(&[0; 16]));
+    // Truncated entry
+    assert_eq!(None, decode_trait_meta(&meta[..meta.len() - 1]));
+
+    // Entries of unknown length are skipped
+    let mut meta = meta;
+    meta[4 + 1] = 3;
+    assert_eq!(Some(traits()[1..].to_vec()), decode_trait_meta(&meta));
+
+    Ok(())
+}
+
+#[test]
+fn xdp_stamp_writes_record() -> TestResult {
+    let prog = load_xdp_meta_stamp(&traits())?;
+    let pkt = udp_packet_v4(
+        (Ipv4Addr::new(192, 0, 2, 1), 1234),
+        (Ipv4Addr::new(192, 0, 2, 2), 53),
+        b"hello",
+    );
+
+    let out = ProgTestRun::new(prog.as_fd()).data(&pkt).run()?;
+    assert_eq!(XDP_PASS as u32, out.retval);
+    assert_eq!(pkt, out.data);
+    assert_eq!(encode_trait_meta(&traits())?, out.meta);
+    assert_eq!(Some(traits()), out.traits()?);
+
+    Ok(())
+}
+
+#[test]
+fn xdp_stamp_rejects_bad_traits() {
+    let too_many = vec![(1, TraitValue::U16(1)); MAX_META_TRAITS + 1];
+    let err = load_xdp_meta_stamp(&too_many).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+}
+
+#[test]
+fn converter_sets_every_entry() {
+    let insns = meta_to_traits_insns(4242);
+
+    let calls = insns
+        .iter()
+        .filter(|insn| *insn == &BpfInsn::call_kfunc(4242))
+        .count();
+    assert_eq!(MAX_META_TRAITS, calls);
+    assert_eq!(
+        [BpfInsn::mov64_imm(R0, TC_ACT_OK), BpfInsn::exit()],
+        insns[insns.len() - 2..]
+    );
+}
+
+#[test]
+fn attach_xdp_rejects_tc_prog() -> TestResult {
+    let _ns = TestNetns::new()?;
+
+    let prog = load_tc_prog(
+        "tc_pass",
+        &[BpfInsn::mov64_imm(R0, TC_ACT_OK), BpfInsn::exit()],
+    )?;
+
+    let err = attach_xdp(prog.as_fd(), if_index("lo")?).unwrap_err();
+    assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+
+    Ok(())
+}
+
+#[test]
+fn xdp_link_detached_on_drop() -> TestResult {
+    let _ns = TestNetns::new()?;
+    let prog = load_xdp_meta_stamp(&traits())?;
+    let lo = if_index("lo")?;
+
+    let link = attach_xdp(prog.as_fd(), lo)?;
+    assert_eq!(LinkTarget::Xdp { ifindex: lo }, link.target());
+    link.prog_id()?;
+    link.detach()?;
+
+    // Free to attach again once detached
+    let link = attach_xdp(prog.as_fd(), lo)?;
+    drop(link);
+    let _link = attach_xdp(prog.as_fd(), lo)?;
+
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use skb_traits::*;
+
+fn traits() -> Vec<PktTrait> {
+    vec![(16, 0x1616_u16).into(), (42, 207_u16).into(), 64.into()]
+}
+
+#[test]
+fn present_and_absent_match_on_key() {
+    assert!(TraitMatch::Present(16).matches(&traits()));
+    assert!(!TraitMatch::Present(17).matches(&traits()));
+    assert!(TraitMatch::Absent(17).matches(&traits()));
+    assert!(!TraitMatch::Absent(42).matches(&traits()));
+}
+
+#[test]
+fn zero_length_trait_treated_as_absent() {
+    assert!(!TraitMatch::Present(64).matches(&traits()));
+    assert!(TraitMatch::Absent(64).matches(&traits()));
+}
+
+#[test]
+fn one_of_matches_on_value() {
+    assert!(TraitMatch::OneOf(42, vec![207, 208]).matches(&traits()));
+    assert!(!TraitMatch::OneOf(42, vec![208]).matches(&traits()));
+    assert!(!TraitMatch::OneOf(17, vec![207]).matches(&traits()));
+}
+
+#[test]
+fn can_combine_matches() {
+    let both = TraitMatch::All(vec![TraitMatch::Present(16), TraitMatch::Present(42)]);
+    let either = TraitMatch::Any(vec![TraitMatch::Present(17), TraitMatch::Present(42)]);
+
+    assert!(both.matches(&traits()));
+    assert!(either.matches(&traits()));
+    assert!(!TraitMatch::Not(Box::new(either)).matches(&traits()));
+    assert!(TraitMatch::All(vec![]).matches(&[]));
+    assert!(!TraitMatch::Any(vec![]).matches(&[]));
+}
+
+#[test]
+fn first_matching_rule_wins() {
+    let policy = TraitPolicy::new(TraitAction::Allow)
+        .rule(TraitMatch::Present(16), TraitAction::Route("a".into()))
+        .rule(TraitMatch::Present(42), TraitAction::Route("b".into()));
+
+    assert_eq!(&TraitAction::Route("a".into()), policy.evaluate(&traits()));
+}
+
+#[test]
+fn default_action_applies_when_no_rule_matches() {
+    let policy =
+        TraitPolicy::new(TraitAction::Deny).rule(TraitMatch::Present(17), TraitAction::Allow);
+
+    assert_eq!(&TraitAction::Deny, policy.evaluate(&traits()));
+    assert_eq!(&TraitAction::Deny, policy.evaluate(&[]));
+}
+
+#[test]
+fn require_denies_when_condition_unmet() {
+    let policy = TraitPolicy::new(TraitAction::Allow)
+        .require(TraitMatch::OneOf(42, vec![207, 208]))
+        .require(TraitMatch::Present(16));
+
+    assert_eq!(&TraitAction::Allow, policy.evaluate(&traits()));
+    assert_eq!(&TraitAction::Deny, policy.evaluate(&[(42, 209_u16).into()]));
+    assert_eq!(&TraitAction::Deny, policy.evaluate(&[(42, 207_u16).into()]));
+}
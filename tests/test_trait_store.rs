@@ -0,0 +1,188 @@
+use std::io;
+
+use skb_traits::*;
+
+#[path = "common/trait_sets.rs"]
+mod trait_sets;
+
+fn errno(res: io::Result<impl Sized>) -> Option<i32> {
+    res.err().and_then(|err| err.raw_os_error())
+}
+
+#[test]
+fn set_get_delete() -> io::Result<()> {
+    let mut store = TraitStore::new();
+
+    store.set(42, TraitValue::U16(0xcfcf), StoreFlags::empty())?;
+    assert_eq!(Some(TraitValue::U16(0xcfcf)), store.get(42)?);
+    assert_eq!(None, store.get(43)?);
+
+    store.set(42, TraitValue::U16(0xaaaa), StoreFlags::empty())?;
+    assert_eq!(Some(TraitValue::U16(0xaaaa)), store.get(42)?);
+
+    assert!(store.delete(42)?);
+    assert!(!store.delete(42)?);
+    assert!(store.is_empty());
+
+    Ok(())
+}
+
+#[test]
+fn keys_out_of_range_rejected() {
+    let mut store = TraitStore::new();
+
+    assert_eq!(
+        Some(libc::EINVAL),
+        errno(store.set(MAX_KEY + 1, TraitValue::U16(1), StoreFlags::empty()))
+    );
+    assert_eq!(Some(libc::EINVAL), errno(store.get(MAX_KEY + 1)));
+    assert_eq!(Some(libc::EINVAL), errno(store.delete(MAX_KEY + 1)));
+}
+
+#[test]
+fn width_fixed_until_deleted() -> io::Result<()> {
+    let mut store = TraitStore::new();
+    store.set(7, TraitValue::U32(1), StoreFlags::empty())?;
+
+    assert_eq!(
+        Some(libc::EBUSY),
+        errno(store.set(7, TraitValue::U64(1), StoreFlags::empty()))
+    );
+
+    store.delete(7)?;
+    store.set(7, TraitValue::U64(1), StoreFlags::empty())?;
+
+    Ok(())
+}
+
+#[test]
+fn no_overwrite_flag() -> io::Result<()> {
+    let mut store = TraitStore::new();
+
+    store.set(1, TraitValue::U16(1), StoreFlags::NO_OVERWRITE)?;
+    assert_eq!(
+        Some(libc::EEXIST),
+        errno(store.set(1, TraitValue::U16(2), StoreFlags::NO_OVERWRITE))
+    );
+    assert_eq!(Some(TraitValue::U16(1)), store.get(1)?);
+
+    assert_eq!(
+        Some(libc::EINVAL),
+        errno(store.set(2, TraitValue::U16(1), StoreFlags::from_bits(2)))
+    );
+
+    Ok(())
+}
+
+#[test]
+fn typed_get_zero_extends_narrower_values() -> io::Result<()> {
+    let mut store = TraitStore::new();
+    store.set(1, TraitValue::U16(0xabcd), StoreFlags::empty())?;
+    store.set(2, TraitValue::U64(1), StoreFlags::empty())?;
+
+    assert_eq!(Some(0xabcd_u32), store.get_typed(Key::<u32>::new(1))?);
+    assert_eq!(
+        Some(libc::ENOSPC),
+        errno(store.get_typed(Key::<u32>::new(2)))
+    );
+    assert_eq!(None, store.get_typed(Key::<u64>::new(3))?);
+
+    Ok(())
+}
+
+#[test]
+fn enospc_when_full() -> io::Result<()> {
+    // Header and one 8-byte value
+    let mut store = TraitStore::with_capacity(24);
+
+    store.set(1, TraitValue::U64(1), StoreFlags::empty())?;
+    assert_eq!(24, store.size());
+    assert_eq!(
+        Some(libc::ENOSPC),
+        errno(store.set(2, TraitValue::U16(1), StoreFlags::empty()))
+    );
+
+    // Replacing a value takes no extra room
+    store.set(1, TraitValue::U64(2), StoreFlags::empty())?;
+
+    Ok(())
+}
+
+#[test]
+fn enospc_for_too_many_traits_on_first_set() -> io::Result<()> {
+    trait_sets::enospc_for_too_many_traits_on_first_set(&mut TraitStore::new())
+}
+
+#[test]
+fn enospc_for_too_many_traits_on_second_set() -> io::Result<()> {
+    trait_sets::enospc_for_too_many_traits_on_second_set(&mut TraitStore::new())
+}
+
+#[test]
+fn set_traits_like_sockopt() -> io::Result<()> {
+    let mut store = TraitStore::new();
+
+    assert_eq!(Some(libc::EINVAL), errno(store.set_traits(&[])));
+
+    let bad_len = PktTrait {
+        key: 1,
+        len: 3,
+        ..Default::default()
+    };
+    assert_eq!(Some(libc::EINVAL), errno(store.set_traits(&[bad_len])));
+
+    store.set_traits(&[42.into(), (43, 0xaaaa_u16).into()])?;
+    assert_eq!(
+        vec![PktTrait::from(42), PktTrait::from((43, 0xaaaa_u16))],
+        store.get_traits(&[42, 43])?
+    );
+
+    Ok(())
+}
+
+#[test]
+fn blob_layout() -> io::Result<()> {
+    let mut store = TraitStore::new();
+    store.set(0, TraitValue::U16(0x1111), StoreFlags::empty())?;
+    store.set(1, TraitValue::U32(0x2222_2222), StoreFlags::empty())?;
+    store.set(
+        2,
+        TraitValue::U64(0x3333_3333_3333_3333),
+        StoreFlags::empty(),
+    )?;
+
+    let mut want = vec![];
+    want.extend_from_slice(&0b110_u64.to_ne_bytes()); // high: U32 and U64
+    want.extend_from_slice(&0b101_u64.to_ne_bytes()); // low: U16 and U64
+    want.extend_from_slice(&0x1111_u16.to_ne_bytes());
+    want.extend_from_slice(&0x2222_2222_u32.to_ne_bytes());
+    want.extend_from_slice(&0x3333_3333_3333_3333_u64.to_ne_bytes());
+
+    assert_eq!(want, store.to_blob());
+    assert_eq!(want.len(), store.size());
+
+    Ok(())
+}
+
+#[test]
+fn round_trips_through_pkt_traits() -> io::Result<()> {
+    let mut store = TraitStore::new();
+    store.set(5, TraitValue::U64(5), StoreFlags::empty())?;
+    store.set(17, TraitValue::U16(17), StoreFlags::empty())?;
+    store.set(MAX_KEY, TraitValue::U32(63), StoreFlags::empty())?;
+
+    let traits = store.to_pkt_traits();
+    assert_eq!(Ok(Some(TraitValue::U16(17))), traits.get(17));
+    assert_eq!(Ok(None), traits.get(18));
+    assert_eq!(store, TraitStore::from(&traits));
+
+    Ok(())
+}
+
+#[test]
+fn empty_store_is_bare_header() {
+    let store = TraitStore::default();
+
+    assert_eq!(vec![0u8; 16], store.to_blob());
+    assert_eq!(KERNEL_TRAITS_SIZE, store.capacity());
+}
@@ -0,0 +1,41 @@
+use std::net::Ipv4Addr;
+
+#[path = "common/packet.rs"]
+mod packet;
+
+use packet::*;
+
+#[test]
+fn udp_packet_v4_layout() {
+    let pkt = udp_packet_v4(
+        (Ipv4Addr::new(192, 0, 2, 1), 1234),
+        (Ipv4Addr::new(192, 0, 2, 2), 53),
+        b"hello",
+    );
+
+    assert_eq!(14 + 20 + 8 + 5, pkt.len());
+    assert_eq!([0x08, 0x00], pkt[12..14]);
+
+    let ip = &pkt[14..34];
+    assert_eq!(0x45, ip[0]);
+    assert_eq!(33, u16::from_be_bytes([ip[2], ip[3]]));
+    assert_eq!(17, ip[9]);
+    assert_eq!([192, 0, 2, 1], ip[12..16]);
+    assert_eq!([192, 0, 2, 2], ip[16..20]);
+
+    // Summing a header with a valid checksum yields all ones
+    let mut sum: u32 = ip
+        .chunks(2)
+        .map(|w| u16::from_be_bytes([w[0], w[1]]) as u32)
+        .sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    assert_eq!(0xffff, sum);
+
+    let udp = &pkt[34..];
+    assert_eq!(1234, u16::from_be_bytes([udp[0], udp[1]]));
+    assert_eq!(53, u16::from_be_bytes([udp[2], udp[3]]));
+    assert_eq!(13, u16::from_be_bytes([udp[4], udp[5]]));
+    assert_eq!(b"hello", &udp[8..]);
+}